@@ -0,0 +1,107 @@
+//! Measures how many events per second the event pipeline delivers, both to `poll_event` and to
+//! an event sink.
+//!
+//! Run with `cargo bench --bench event_throughput --features testing`. Criterion saves the
+//! results as a baseline in `target/criterion`, so later runs report the change against it.
+//! Events are injected with `SystemTray::inject_event`, so the Qt event loop is never started
+//! and this works without a display. With the `stub` backend, only the Rust side of the pipeline
+//! is measured. The cost of polling an idle tray is measured by the `idle_poll` benchmark.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use system_tray::{Event, EventSink, SystemTray};
+
+/// The number of events injected per iteration, so each iteration measures a full queue.
+const EVENTS: u64 = 10_000;
+
+/// Counts the events it receives.
+struct CountingSink(AtomicU64);
+
+impl EventSink for CountingSink {
+    fn handle(&self, event: Event) {
+        black_box(event);
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn event_throughput(c: &mut Criterion) {
+    let tray = SystemTray::new("SystemTrayBench", "system-tray.event-throughput");
+    let click = Event::TrayClicked { x: 10, y: 20 };
+    let menu = Event::MenuItemClicked("file/recent/doc1".into());
+
+    let mut group = c.benchmark_group("event_throughput");
+    group.throughput(Throughput::Elements(EVENTS));
+    for (name, event) in [("TrayClicked", &click), ("MenuItemClicked", &menu)] {
+        group.bench_with_input(BenchmarkId::new("inject", name), event, |b, event| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    elapsed += inject(&tray, event);
+                    drain(&tray);
+                }
+                elapsed
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("poll_event", name), event, |b, event| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    inject(&tray, event);
+                    elapsed += drain(&tray);
+                }
+                elapsed
+            });
+        });
+    }
+    group.bench_with_input(
+        BenchmarkId::new("event_sink", "MenuItemClicked"),
+        &menu,
+        |b, event| {
+            let sink = Arc::new(CountingSink(AtomicU64::new(0)));
+            tray.set_event_sink(sink.clone()).unwrap();
+            b.iter_custom(|iters| {
+                sink.0.store(0, Ordering::Relaxed);
+                let elapsed = (0..iters).map(|_| inject(&tray, event)).sum();
+                assert_eq!(
+                    sink.0.load(Ordering::Relaxed),
+                    iters * EVENTS,
+                    "every injected event must reach the sink"
+                );
+                elapsed
+            });
+            tray.clear_event_sink().unwrap();
+        },
+    );
+    group.finish();
+}
+
+/// Injects `EVENTS` copies of `event`, returning how long it took.
+fn inject(tray: &SystemTray, event: &Event) -> Duration {
+    let started = Instant::now();
+    for _ in 0..EVENTS {
+        tray.inject_event(event).unwrap();
+    }
+    started.elapsed()
+}
+
+/// Polls the queued events until it is empty, returning how long it took.
+fn drain(tray: &SystemTray) -> Duration {
+    let started = Instant::now();
+    let mut received = 0;
+    while black_box(tray.poll_event().unwrap()) != Event::None {
+        received += 1;
+    }
+    let elapsed = started.elapsed();
+    assert_eq!(received, EVENTS, "every injected event must be polled");
+    elapsed
+}
+
+criterion_group!(benches, event_throughput);
+criterion_main!(benches);
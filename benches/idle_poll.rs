@@ -0,0 +1,53 @@
+//! Measures the cost of polling an idle tray and checks that it doesn't allocate.
+//!
+//! Run with `cargo bench --bench idle_poll`. Criterion saves the results as a baseline in
+//! `target/criterion`, so later runs report the change against it. The Qt event loop is never
+//! started, so this works without a display.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use system_tray::{Event, SystemTray};
+
+/// The number of idle polls checked for allocations.
+const ALLOCATION_CHECK_CALLS: u32 = 1_000_000;
+
+/// Counts every allocation made through the Rust global allocator.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn idle_poll(c: &mut Criterion) {
+    let tray = SystemTray::new("SystemTrayBench", "system-tray.idle-poll");
+    assert_eq!(tray.poll_event().unwrap(), Event::None);
+
+    // Checked before Criterion runs, as Criterion itself allocates while measuring.
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..ALLOCATION_CHECK_CALLS {
+        black_box(tray.poll_event().unwrap());
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+    assert_eq!(allocations, 0, "polling an idle tray must not allocate");
+
+    c.bench_function("idle poll_event", |b| b.iter(|| tray.poll_event().unwrap()));
+}
+
+criterion_group!(benches, idle_poll);
+criterion_main!(benches);
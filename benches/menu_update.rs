@@ -0,0 +1,87 @@
+//! Measures frequent tray updates, such as a status tooltip or a progress bar refreshed many
+//! times per second, and checks that they don't allocate on the Rust side.
+//!
+//! Run with `cargo bench --bench menu_update`. The Qt event loop is never started, so this works
+//! without a display. The cost of building a fresh `CString` per call is printed for
+//! comparison.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    ffi::CString,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use system_tray::SystemTray;
+
+const ITERATIONS: u32 = 1_000_000;
+
+/// Counts every allocation made through the Rust global allocator.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn main() {
+    let tray = SystemTray::new("SystemTrayBench", "system-tray.menu-update");
+    tray.add_progress_item("download", "Downloading").unwrap();
+    // Grow the reused buffer before measuring.
+    tray.set_accessible_name("Syncing: 100% done").unwrap();
+
+    let (elapsed, allocations) = measure(|i| {
+        tray.set_accessible_name(if i % 2 == 0 {
+            "Syncing: 10% done"
+        } else {
+            "Syncing: 20% done"
+        })
+        .unwrap()
+    });
+    report("set_accessible_name", elapsed, allocations);
+    assert_eq!(allocations, 0, "updating the tooltip must not allocate");
+
+    let (elapsed, allocations) =
+        measure(|i| tray.set_progress("download", (i % 101) as u8).unwrap());
+    report("set_progress", elapsed, allocations);
+    assert_eq!(allocations, 0, "updating the progress must not allocate");
+
+    let (elapsed, allocations) = measure(|_| {
+        black_box(CString::new("Syncing: 10% done").unwrap());
+    });
+    report("CString::new (baseline)", elapsed, allocations);
+}
+
+/// Runs `update` `ITERATIONS` times, returning the time taken and the allocations made.
+fn measure(mut update: impl FnMut(u32)) -> (Duration, usize) {
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for i in 0..ITERATIONS {
+        update(i);
+    }
+    let elapsed = started.elapsed();
+    (
+        elapsed,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations_before,
+    )
+}
+
+fn report(name: &str, elapsed: Duration, allocations: usize) {
+    println!(
+        "{name}: {:?} per call, {} allocations over {} calls",
+        elapsed / ITERATIONS,
+        allocations,
+        ITERATIONS
+    );
+}
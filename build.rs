@@ -1,19 +1,71 @@
-
 use std::env;
 use std::path::PathBuf;
+use std::process::Command;
 
 fn main() {
-    let dst = cmake::build("lib");
+    // The stub backend is pure Rust and must build without Qt, CMake or libclang.
+    if env::var_os("CARGO_FEATURE_STUB").is_some() {
+        return;
+    }
+    let static_qt = env::var_os("CARGO_FEATURE_STATIC_QT").is_some();
+    // The notification service only exists on freedesktop systems, so CMake ignores it elsewhere.
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let dbus_notifications = env::var_os("CARGO_FEATURE_DBUS_NOTIFICATIONS").is_some()
+        && !matches!(target_os.as_str(), "windows" | "macos");
+    let target = env::var("TARGET").unwrap();
+    let cross = target != env::var("HOST").unwrap();
+    // The sysroot holding the target's Qt when cross-compiling. CMake itself is pointed at the
+    // cross toolchain with CMAKE_TOOLCHAIN_FILE (or CMAKE_TOOLCHAIN_FILE_<target>), which the
+    // cmake crate passes on.
+    println!("cargo:rerun-if-env-changed=QT_SYSROOT");
+    println!("cargo:rerun-if-env-changed=CMAKE_TOOLCHAIN_FILE");
+    let sysroot = env::var("QT_SYSROOT").ok();
+
+    let mut config = cmake::Config::new("lib");
+    if static_qt {
+        config.define("QT6_BIND_STATIC_QT", "ON");
+    }
+    if dbus_notifications {
+        config.define("QT6_BIND_DBUS_NOTIFICATIONS", "ON");
+    }
+    if let Some(sysroot) = &sysroot {
+        config.define("CMAKE_SYSROOT", sysroot);
+        config.define("CMAKE_FIND_ROOT_PATH", sysroot);
+    }
+    let dst = config.build();
 
     println!("cargo:rustc-link-search=native={}/lib", dst.display());
+    if let Some(sysroot) = &sysroot {
+        println!("cargo:rustc-link-search=native={}/usr/lib", sysroot);
+        println!(
+            "cargo:rustc-link-search=native={}/usr/lib/{}",
+            sysroot,
+            multiarch_dir(&target)
+        );
+    }
     println!("cargo:rustc-link-lib=static=qt6-bind");
-    println!("cargo:rustc-link-lib=Qt6Widgets");
-    println!("cargo:rustc-link-lib=Qt6Gui");
-    println!("cargo:rustc-link-lib=Qt6Core");
+    if static_qt {
+        link_static_qt(dbus_notifications);
+    } else {
+        if dbus_notifications {
+            println!("cargo:rustc-link-lib=Qt6DBus");
+        }
+        println!("cargo:rustc-link-lib=Qt6Widgets");
+        println!("cargo:rustc-link-lib=Qt6Network");
+        println!("cargo:rustc-link-lib=Qt6Gui");
+        println!("cargo:rustc-link-lib=Qt6Core");
+    }
     println!("cargo:rustc-link-lib=stdc++");
     println!("cargo:rerun-inf-changed=lib/**");
 
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default();
+    if cross {
+        builder = builder.clang_arg(format!("--target={}", target));
+    }
+    if let Some(sysroot) = &sysroot {
+        builder = builder.clang_arg(format!("--sysroot={}", sysroot));
+    }
+    let bindings = builder
         .header("lib/src/lib.hpp")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
@@ -24,3 +76,85 @@ fn main() {
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
 }
+
+/// Returns the Debian-style multiarch library directory name for a Rust target triple, e.g.
+/// `aarch64-linux-gnu` for `aarch64-unknown-linux-gnu`.
+fn multiarch_dir(target: &str) -> String {
+    let mut parts = target.split('-');
+    let arch = match parts.next().unwrap_or_default() {
+        arch if arch.starts_with("armv7") => "arm",
+        "i586" | "i686" => "i386",
+        arch => arch,
+    };
+    let rest: Vec<&str> = parts.skip(1).collect();
+    format!("{}-{}", arch, rest.join("-"))
+}
+
+/// Links a static Qt build, found through its pkg-config files.
+///
+/// The platform and SVG plugins imported by `lib.cpp` (see `Q_IMPORT_PLUGIN`) are linked
+/// first, since static libraries must come before the libraries they depend on. QtDBus is
+/// linked too when `dbus_notifications` is set.
+fn link_static_qt(dbus_notifications: bool) {
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    println!("cargo:rerun-if-env-changed=QT_STATIC_PLUGIN_DIR");
+
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let (platform_plugin, platform_module) = match target_os.as_str() {
+        "windows" => ("qwindows", None),
+        "macos" => ("qcocoa", None),
+        _ => ("qxcb", Some("Qt6XcbQpa")),
+    };
+
+    let plugin_dir = env::var("QT_STATIC_PLUGIN_DIR")
+        .unwrap_or_else(|_| format!("{}/plugins", pkg_config(&["--variable=prefix", "Qt6Core"])));
+    println!("cargo:rustc-link-search=native={}/platforms", plugin_dir);
+    println!("cargo:rustc-link-search=native={}/imageformats", plugin_dir);
+    println!("cargo:rustc-link-lib=static={}", platform_plugin);
+    println!("cargo:rustc-link-lib=static=qsvg");
+
+    let mut modules = vec!["--static", "--libs", "Qt6Widgets", "Qt6Network", "Qt6Svg"];
+    modules.extend(platform_module);
+    if dbus_notifications {
+        modules.push("Qt6DBus");
+    }
+    let libs = pkg_config(&modules);
+    let mut flags = libs.split_whitespace();
+    while let Some(flag) = flags.next() {
+        if let Some(dir) = flag.strip_prefix("-L") {
+            println!("cargo:rustc-link-search=native={}", dir);
+        } else if let Some(lib) = flag.strip_prefix("-l") {
+            println!("cargo:rustc-link-lib={}", lib);
+        } else if flag == "-framework" {
+            if let Some(framework) = flags.next() {
+                println!("cargo:rustc-link-lib=framework={}", framework);
+            }
+        } else if flag == "-pthread" {
+            println!("cargo:rustc-link-lib=pthread");
+        }
+    }
+}
+
+/// Runs `pkg-config` with `args` and returns its output.
+fn pkg_config(args: &[&str]) -> String {
+    let mut command = Command::new("pkg-config");
+    command.args(args);
+    // Resolve the paths in the target Qt's .pc files inside the sysroot when cross-compiling.
+    if let (Ok(sysroot), None) = (
+        env::var("QT_SYSROOT"),
+        env::var_os("PKG_CONFIG_SYSROOT_DIR"),
+    ) {
+        command.env("PKG_CONFIG_SYSROOT_DIR", sysroot);
+    }
+    let output = command
+        .output()
+        .expect("static-qt requires pkg-config to locate the static Qt build");
+    if !output.status.success() {
+        panic!(
+            "pkg-config {} failed; point PKG_CONFIG_PATH at the static Qt build's lib/pkgconfig: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
@@ -1,43 +1,626 @@
 #include "lib.hpp" // Header with C-compatible AppEventType and AppEvent
 #include <QApplication>
+#include <QCheckBox>
+#include <QCursor>
+#include <QDesktopServices>
+#include <QFont>
 #include <QIcon>
+#include <QImageReader>
+#include <QKeySequence>
 #include <QMenu>
+#include <QMessageBox>
+#include <QPainter>
+#include <QPalette>
+#include <QMouseEvent>
+#include <QProgressBar>
+#include <QScreen>
+#include <QStyleHints>
 #include <QSystemTrayIcon>
+#include <QWidgetAction>
 #include <QBuffer>
 #include <string>
 #include <vector>
+#include <deque>
+#include <algorithm>
+#include <unordered_map>
+#include <unordered_set>
 #include <memory>
+#include <mutex>
+#include <atomic>
+#include <future>
+#include <functional>
+#include <QThread>
 #include <QTimer>
+#include <QUrl>
 #include <QSettings>
+#include <QLocalServer>
+#include <QLocalSocket>
 #include <QLoggingCategory>
+#include <QtGlobal>
+#include <QDir>
+#include <QJsonArray>
+#include <QJsonObject>
+#include <QLibrary>
+#include <QPluginLoader>
+
+#if defined(Q_OS_UNIX)
+#include <QSocketNotifier>
+#include <csignal>
+#include <sys/socket.h>
+#include <unistd.h>
+#elif defined(Q_OS_WIN)
+#include <qt_windows.h>
+#endif
+
+#ifdef QT6_BIND_STATIC_QT
+// Plugins can't be loaded at runtime from a static Qt, so the ones the tray needs are linked in:
+// the platform integration and the SVG image format for SVG icons.
+#include <QtPlugin>
+#if defined(Q_OS_WIN)
+Q_IMPORT_PLUGIN(QWindowsIntegrationPlugin)
+#elif defined(Q_OS_MACOS)
+Q_IMPORT_PLUGIN(QCocoaIntegrationPlugin)
+#else
+Q_IMPORT_PLUGIN(QXcbIntegrationPlugin)
+#endif
+Q_IMPORT_PLUGIN(QSvgPlugin)
+#endif
+
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+#include <QDBusArgument>
+#include <QDBusConnection>
+#include <QDBusConnectionInterface>
+#include <QDBusMessage>
+#include <QDBusMetaType>
+#include <QDBusPendingCallWatcher>
+#include <QDBusPendingReply>
+#endif
 
 Q_LOGGING_CATEGORY(lcNoWarnings, "no.warnings", QtInfoMsg)
 
+// The platform plugins QGuiApplication will try, in order, before it has been created.
+// QT_QPA_PLATFORM may hold plugin arguments after ':' and fallbacks after ';'.
+static std::vector<std::string> requestedPlatforms()
+{
+    std::vector<std::string> names;
+    for (const QString &entry : qEnvironmentVariable("QT_QPA_PLATFORM").split(';', Qt::SkipEmptyParts))
+    {
+        QString name = entry.section(':', 0, 0).trimmed();
+        if (!name.isEmpty())
+        {
+            names.push_back(name.toStdString());
+        }
+    }
+    if (!names.empty())
+    {
+        return names;
+    }
+#if defined(Q_OS_WIN)
+    return {"windows"};
+#elif defined(Q_OS_MACOS)
+    return {"cocoa"};
+#else
+    if (qEnvironmentVariableIsSet("WAYLAND_DISPLAY"))
+    {
+        return {"wayland", "xcb"};
+    }
+    return {"xcb"};
+#endif
+}
+
 class QtAppWrapper;
 
+// Quits the QApplication on Ctrl+C (SIGINT, or the console's Ctrl+C on Windows) for as long as
+// it exists, then restores the previous handling. A signal handler may only do
+// async-signal-safe work, so on Unix it writes to a socket watched by the event loop.
+class InterruptQuitter
+{
+public:
+    explicit InterruptQuitter(QApplication *app)
+    {
+#if defined(Q_OS_UNIX)
+        if (::socketpair(AF_UNIX, SOCK_STREAM, 0, signalFds) != 0)
+        {
+            return;
+        }
+        notifier = new QSocketNotifier(signalFds[0], QSocketNotifier::Read);
+        QObject::connect(notifier, &QSocketNotifier::activated, app, [app]()
+                         {
+            char byte;
+            if (::read(signalFds[0], &byte, 1) > 0) { app->quit(); } });
+        struct sigaction action = {};
+        action.sa_handler = onSignal;
+        sigemptyset(&action.sa_mask);
+        action.sa_flags = SA_RESTART;
+        installed = ::sigaction(SIGINT, &action, &previous) == 0;
+#elif defined(Q_OS_WIN)
+        target = app;
+        installed = SetConsoleCtrlHandler(onConsoleEvent, TRUE);
+#endif
+    }
+
+    ~InterruptQuitter()
+    {
+#if defined(Q_OS_UNIX)
+        if (installed)
+        {
+            ::sigaction(SIGINT, &previous, nullptr);
+        }
+        if (notifier)
+        {
+            delete notifier;
+            ::close(signalFds[0]);
+            ::close(signalFds[1]);
+        }
+#elif defined(Q_OS_WIN)
+        if (installed)
+        {
+            SetConsoleCtrlHandler(onConsoleEvent, FALSE);
+        }
+        target = nullptr;
+#endif
+    }
+
+private:
+#if defined(Q_OS_UNIX)
+    static void onSignal(int)
+    {
+        const char byte = 1;
+        ssize_t written = ::write(signalFds[1], &byte, 1);
+        (void)written;
+    }
+
+    static inline int signalFds[2] = {-1, -1};
+    QSocketNotifier *notifier = nullptr;
+    struct sigaction previous = {};
+#elif defined(Q_OS_WIN)
+    // Runs on a thread of its own, so the quit is queued to the Qt thread.
+    static BOOL WINAPI onConsoleEvent(DWORD type)
+    {
+        if (type != CTRL_C_EVENT || !target)
+        {
+            return FALSE;
+        }
+        QMetaObject::invokeMethod(target, []()
+                                  { QCoreApplication::quit(); }, Qt::QueuedConnection);
+        return TRUE;
+    }
+
+    static inline QApplication *target = nullptr;
+#endif
+    bool installed = false;
+};
+
+// Draws the built-in busy spinner: a ring of dots with a bright head that moves one dot per frame.
+static std::vector<QIcon> makeSpinnerFrames()
+{
+    constexpr int frameCount = 8;
+    constexpr int size = 64;
+    std::vector<QIcon> frames;
+    for (int frame = 0; frame < frameCount; ++frame)
+    {
+        QPixmap pixmap(size, size);
+        pixmap.fill(Qt::transparent);
+        QPainter painter(&pixmap);
+        painter.setRenderHint(QPainter::Antialiasing);
+        painter.setPen(Qt::NoPen);
+        painter.translate(size / 2.0, size / 2.0);
+        for (int dot = 0; dot < frameCount; ++dot)
+        {
+            // Dots further behind the head are more transparent.
+            int age = (frame - dot + frameCount) % frameCount;
+            painter.setBrush(QColor(128, 128, 128, 255 - age * 28));
+            painter.save();
+            painter.rotate(dot * 360.0 / frameCount);
+            painter.drawEllipse(QPointF(0, -size * 0.35), size * 0.09, size * 0.09);
+            painter.restore();
+        }
+        painter.end();
+        frames.emplace_back(pixmap);
+    }
+    return frames;
+}
+
+// A dark palette applied on request, independent of the system theme.
+static QPalette makeDarkPalette()
+{
+    const QColor window(45, 45, 45);
+    const QColor base(30, 30, 30);
+    const QColor disabled(127, 127, 127);
+
+    QPalette palette;
+    palette.setColor(QPalette::Window, window);
+    palette.setColor(QPalette::WindowText, Qt::white);
+    palette.setColor(QPalette::Base, base);
+    palette.setColor(QPalette::AlternateBase, window);
+    palette.setColor(QPalette::ToolTipBase, window);
+    palette.setColor(QPalette::ToolTipText, Qt::white);
+    palette.setColor(QPalette::Text, Qt::white);
+    palette.setColor(QPalette::Button, window);
+    palette.setColor(QPalette::ButtonText, Qt::white);
+    palette.setColor(QPalette::BrightText, Qt::red);
+    palette.setColor(QPalette::Highlight, QColor(42, 130, 218));
+    palette.setColor(QPalette::HighlightedText, Qt::black);
+    palette.setColor(QPalette::Disabled, QPalette::Text, disabled);
+    palette.setColor(QPalette::Disabled, QPalette::WindowText, disabled);
+    palette.setColor(QPalette::Disabled, QPalette::ButtonText, disabled);
+    return palette;
+}
+
+// The widget of a progress menu item. A widget in a menu receives the clicks instead of its
+// action, so the bar triggers the action itself, like clicking a regular item would.
+class ProgressItemBar : public QProgressBar
+{
+public:
+    explicit ProgressItemBar(QAction *action) : action(action) {}
+
+protected:
+    void mouseReleaseEvent(QMouseEvent *event) override
+    {
+        QProgressBar::mouseReleaseEvent(event);
+        if (action->isEnabled() && rect().contains(event->position().toPoint()))
+        {
+            action->trigger();
+        }
+    }
+
+private:
+    QAction *action;
+};
+
+// The widget of a tristate menu item, since a QAction can only be checked or not. Like the
+// progress bar, it triggers its action when clicked. A click checks an unchecked or partially
+// checked box and unchecks a checked one; only set_menu_item_check_state makes it partial.
+class TristateItemBox : public QCheckBox
+{
+public:
+    explicit TristateItemBox(QAction *action) : action(action) { setTristate(true); }
+
+protected:
+    void nextCheckState() override
+    {
+        setCheckState(checkState() == Qt::Checked ? Qt::Unchecked : Qt::Checked);
+        action->trigger();
+    }
+
+private:
+    QAction *action;
+};
+
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+// The "image-data" hint of the freedesktop notification specification, a DBus (iiibiiay) struct.
+struct NotificationImage
+{
+    int width = 0;
+    int height = 0;
+    int rowStride = 0;
+    bool hasAlpha = true;
+    int bitsPerSample = 8;
+    int channels = 4;
+    QByteArray data;
+};
+Q_DECLARE_METATYPE(NotificationImage)
+
+QDBusArgument &operator<<(QDBusArgument &argument, const NotificationImage &image)
+{
+    argument.beginStructure();
+    argument << image.width << image.height << image.rowStride << image.hasAlpha << image.bitsPerSample
+             << image.channels << image.data;
+    argument.endStructure();
+    return argument;
+}
+
+const QDBusArgument &operator>>(const QDBusArgument &argument, NotificationImage &image)
+{
+    argument.beginStructure();
+    argument >> image.width >> image.height >> image.rowStride >> image.hasAlpha >> image.bitsPerSample >>
+        image.channels >> image.data;
+    argument.endStructure();
+    return argument;
+}
+
+// Receives the signals of the freedesktop notification service, which QtDBus only delivers to
+// slots, and hands them on to the wrapper.
+class NotificationSignals : public QObject
+{
+    Q_OBJECT
+
+public:
+    using QObject::QObject;
+    std::function<void(uint, const QString &)> onActionInvoked;
+    std::function<void(uint, uint)> onClosed;
+
+public Q_SLOTS:
+    void actionInvoked(uint id, const QString &actionKey) { onActionInvoked(id, actionKey); }
+    void notificationClosed(uint id, uint reason) { onClosed(id, reason); }
+};
+#endif
+
 struct QtAppHandle
 {
     QtAppWrapper *impl;
 };
 
+// Everything needed to create a menu item's QAction once the application is running.
+struct MenuItemSpec
+{
+    std::string text;
+    std::string id;
+    bool enabled = true;
+    bool visible = true;
+    std::string url; // Opened with QDesktopServices on click, if not empty
+    std::string parent; // Path of the containing submenu, empty for the top level
+    bool submenu = false;
+    std::string accessibleDescription;
+    bool checkable = false;
+    bool checked = false;
+    bool separator = false;
+    bool bold = false;
+    bool italic = false;
+    bool progress = false;
+    int progressValue = 0; // 0 to 100
+    MenuRole role = MenuRoleTextHeuristic;
+    std::string shortcut; // In QKeySequence::PortableText, empty for none
+    ShortcutContext shortcutContext = ShortcutContextWindow;
+    bool tristate = false;
+    CheckState checkState = CheckStateUnchecked; // For tristate items, instead of checked
+    QByteArray iconData; // The encoded icon shown next to the text, empty for none
+    std::string iconFormat; // Empty to detect the format from the data
+
+    // Identifies the item in events and lookups: the ids from the top level joined with '/'.
+    std::string path() const { return parent.empty() ? id : parent + "/" + id; }
+};
+
 class QtAppWrapper
 {
 public:
     QtAppWrapper() = default;
+    ~QtAppWrapper()
+    {
+        resolveNotification(NotificationResultDismissed);
+        for (const AppEvent &event : event_queue)
+        {
+            dropEvent(event);
+        }
+        for (const AppEvent &event : pausedEvents)
+        {
+            dropEvent(event);
+        }
+    }
 
     void setAppId(const std::string &id) { appId = id; }
     void setOrganizationName(const std::string &name) { organizationName = name; }
 
     void setAppIcon(const unsigned char *data, size_t size, const char *format)
     {
-        iconData = QByteArray(reinterpret_cast<const char *>(data), size);
-        iconFormat = format;
+        setTrayIcon(data, size, format);
+        setApplicationIcon(data, size, format);
+    }
+
+    // A template (mask) icon is recolored by the platform to match the panel.
+    void setTrayIcon(const unsigned char *data, size_t size, const char *format, bool isTemplate = false)
+    {
+        QByteArray bytes(reinterpret_cast<const char *>(data), size);
+        std::string fmt = format;
+        runOnGuiThread([this, &bytes, &fmt, isTemplate]()
+                       {
+            iconData = bytes;
+            iconVariants.clear();
+            iconImage = QImage();
+            iconFormat = fmt;
+            iconIsTemplate = isTemplate;
+            themedIcon = false;
+            if (app) { applyAppIcon(); } });
+    }
+
+    // Each variant is the image data for a nominal size in pixels.
+    void setTrayIconVariants(std::vector<std::pair<int, QByteArray>> variants, const std::string &format)
+    {
+        runOnGuiThread([this, &variants, &format]()
+                       {
+            iconVariants = std::move(variants);
+            iconData.clear();
+            iconImage = QImage();
+            iconFormat = format;
+            iconIsTemplate = false;
+            themedIcon = false;
+            if (app) { applyAppIcon(); } });
+    }
+
+    // Variants as for setTrayIconVariants, one set per color scheme. applyAppIcon shows the set
+    // matching the current scheme, and is called again when the scheme changes.
+    void setTrayIconThemed(std::vector<std::pair<int, QByteArray>> light, std::vector<std::pair<int, QByteArray>> dark,
+                           const std::string &format)
+    {
+        runOnGuiThread([this, &light, &dark, &format]()
+                       {
+            lightIconVariants = std::move(light);
+            darkIconVariants = std::move(dark);
+            iconVariants.clear();
+            iconData.clear();
+            iconImage = QImage();
+            iconFormat = format;
+            iconIsTemplate = false;
+            themedIcon = true;
+            if (app) { applyAppIcon(); } });
+    }
+
+    void setTrayIconImage(QImage image)
+    {
+        runOnGuiThread([this, &image]()
+                       {
+            iconImage = std::move(image);
+            iconData.clear();
+            iconVariants.clear();
+            iconIsTemplate = false;
+            themedIcon = false;
+            if (app) { applyAppIcon(); } });
+    }
+
+    void setApplicationIcon(const unsigned char *data, size_t size, const char *format)
+    {
+        QByteArray bytes(reinterpret_cast<const char *>(data), size);
+        std::string fmt = format;
+        runOnGuiThread([this, &bytes, &fmt]()
+                       {
+            windowIconData = bytes;
+            windowIconFormat = fmt;
+            if (app) { applyAppIcon(); } });
+    }
+
+    void setFallbackIcon(const unsigned char *data, size_t size, const char *format)
+    {
+        QByteArray bytes(reinterpret_cast<const char *>(data), size);
+        std::string fmt = format;
+        runOnGuiThread([this, &bytes, &fmt]()
+                       {
+            fallbackIconData = bytes;
+            fallbackIconFormat = fmt;
+            if (app) { applyAppIcon(); } });
+    }
+
+    // The QApplication can only be created on the thread that runs the event loop, so this
+    // checks up front for the most common reason it would fail: no display to connect to.
+    bool initTray()
+    {
+        shouldInitTray = true;
+        return hasDisplay();
+    }
+
+    bool initApp()
+    {
+        if (!hasDisplay())
+        {
+            setError("no display is available");
+            return false;
+        }
+        return checkPlatformPlugin();
+    }
+
+    // QApplication aborts the whole process with qFatal when none of its platform plugins can
+    // be loaded, so the plugins are loaded on their own first to report why instead. Only
+    // plugins that are found and fail to load (typically because of a missing library such as
+    // libxcb-cursor0) or a platforms directory without the requested plugin count as a failure:
+    // Qt may still find plugins this check doesn't know about.
+    bool checkPlatformPlugin()
+    {
+#if defined(Q_OS_UNIX) && !defined(Q_OS_MACOS) && !defined(QT6_BIND_STATIC_QT)
+        if (QCoreApplication::instance())
+        {
+            return true;
+        }
+        QStringList dirs;
+        const QString overridden = qEnvironmentVariable("QT_QPA_PLATFORM_PLUGIN_PATH");
+        if (!overridden.isEmpty())
+        {
+            dirs << overridden;
+        }
+        for (const QString &path : QCoreApplication::libraryPaths())
+        {
+            dirs << path + "/platforms";
+        }
+        QStringList available;
+        QStringList failures;
+        for (const std::string &name : requestedPlatforms())
+        {
+            const QString key = QString::fromStdString(name);
+            for (const QString &dir : dirs)
+            {
+                QDir platforms(dir);
+                for (const QString &file : platforms.entryList(QDir::Files))
+                {
+                    if (!QLibrary::isLibrary(file))
+                    {
+                        continue;
+                    }
+                    QPluginLoader loader(platforms.absoluteFilePath(file));
+                    const QJsonArray keys = loader.metaData().value("MetaData").toObject().value("Keys").toArray();
+                    bool matches = false;
+                    for (const QJsonValue &value : keys)
+                    {
+                        const QString pluginKey = value.toString();
+                        if (!available.contains(pluginKey, Qt::CaseInsensitive))
+                        {
+                            available << pluginKey;
+                        }
+                        matches = matches || pluginKey.compare(key, Qt::CaseInsensitive) == 0;
+                    }
+                    if (!matches)
+                    {
+                        continue;
+                    }
+                    // Left loaded: QGuiApplication loads the same library again right after.
+                    if (loader.load())
+                    {
+                        return true;
+                    }
+                    failures << QString("Could not load the Qt platform plugin \"%1\": %2").arg(key, loader.errorString());
+                }
+            }
+        }
+        if (!failures.isEmpty())
+        {
+            setError(failures.join("; ").toStdString() +
+                     ". Install the libraries it is missing, or choose another plugin with QT_QPA_PLATFORM");
+            return false;
+        }
+        if (!available.isEmpty())
+        {
+            setError(QString("Could not find the Qt platform plugin \"%1\". Available platform plugins are: %2")
+                         .arg(QString::fromStdString(requestedPlatforms().front()), available.join(", "))
+                         .toStdString());
+            return false;
+        }
+#endif
+        return true;
+    }
+
+    bool hasDisplay() const
+    {
+#if defined(Q_OS_UNIX) && !defined(Q_OS_MACOS)
+        return qEnvironmentVariableIsSet("QT_QPA_PLATFORM") || qEnvironmentVariableIsSet("DISPLAY") ||
+               qEnvironmentVariableIsSet("WAYLAND_DISPLAY");
+#else
+        return true;
+#endif
     }
 
-    void initTray() { shouldInitTray = true; }
+    void setStyle(const std::string &name) { styleName = name; }
+    void setDisplayName(const std::string &name)
+    {
+        runOnGuiThread([this, &name]()
+                       {
+            displayName = name;
+            if (app) { QGuiApplication::setApplicationDisplayName(QString::fromStdString(name)); } });
+    }
+    void setDarkPalette(bool enabled) { useDarkPalette = enabled; }
+    void setQuitOnInterrupt(bool enabled) { quitOnInterrupt = enabled; }
+    void setTrayWaitTimeout(int ms) { trayWaitMs = ms; }
+    // Both settings only take effect before the QApplication is created. They are applied
+    // here, on the caller's thread, so that the environment isn't changed behind its back once
+    // the Qt thread runs. Qt 6 always has AA_EnableHighDpiScaling on, so the environment
+    // variable is its only off switch.
+    void setHighDpi(bool enabled)
+    {
+        if (enabled)
+        {
+            QGuiApplication::setHighDpiScaleFactorRoundingPolicy(Qt::HighDpiScaleFactorRoundingPolicy::PassThrough);
+            if (highDpiDisabledByEnv)
+            {
+                qunsetenv("QT_ENABLE_HIGHDPI_SCALING");
+                highDpiDisabledByEnv = false;
+            }
+        }
+        else
+        {
+            qputenv("QT_ENABLE_HIGHDPI_SCALING", "0");
+            highDpiDisabledByEnv = true;
+        }
+    }
 
     int run(int argc, char *argv[])
     {
+        beginTransition();
         QLoggingCategory::setFilterRules("qt.qsettings.warning=false\nqt.systemtrayicon.warning=false\n*.warning=false");
         if (!organizationName.empty())
         {
@@ -47,58 +630,125 @@ public:
         {
             QApplication::setApplicationName(QString::fromStdString(appId));
         }
-
         app = new QApplication(argc, argv);
+        if (!displayName.empty())
+        {
+            QGuiApplication::setApplicationDisplayName(QString::fromStdString(displayName));
+        }
+        QObject::connect(app, &QCoreApplication::aboutToQuit, [this]()
+                         { pushEvent({Quit, nullptr}); });
+        if (!styleName.empty())
+        {
+            QApplication::setStyle(QString::fromStdString(styleName));
+        }
+        if (useDarkPalette)
+        {
+            QApplication::setPalette(makeDarkPalette());
+        }
 
-        QIcon appIcon;
-        if (!iconData.isEmpty())
+        applyAppIcon();
+        std::unique_ptr<InterruptQuitter> interruptQuitter;
+        if (quitOnInterrupt)
         {
-            QPixmap pixmap;
-            if (pixmap.loadFromData(iconData, iconFormat.c_str()))
-            {
-                appIcon = QIcon(pixmap);
-                app->setWindowIcon(appIcon);
-            }
+            interruptQuitter = std::make_unique<InterruptQuitter>(app);
         }
 
-        if (shouldInitTray)
+        bool waitForTray = false;
+        if (shouldInitTray || !trays.empty())
         {
-            if (!QSystemTrayIcon::isSystemTrayAvailable())
+            if (!QSystemTrayIcon::isSystemTrayAvailable() && trayWaitMs > 0)
             {
-                return -1;
+                // The session may still be starting its panel; the icons are created anyway and
+                // Qt registers them once the host is up.
+                waitForTray = true;
             }
-
-            menu = new QMenu();
-            tray = new QSystemTrayIcon(appIcon);
-            tray->setContextMenu(menu);
-
-            QObject::connect(tray, &QSystemTrayIcon::activated, [this](QSystemTrayIcon::ActivationReason reason)
-                             {
-                if (reason == QSystemTrayIcon::Context) { /* Right-click, no event pushed */ }
-                else if (reason == QSystemTrayIcon::Trigger) {
-                    event_queue.push_back({TrayClicked, nullptr}); // Use the new enum value directly
-                } else if (reason == QSystemTrayIcon::DoubleClick) {
-                    event_queue.push_back({TrayDoubleClicked, nullptr}); // Use the new enum value directly
-                } });
-            tray->show();
-
-            for (const auto &item : pending_menu_items)
+            else if (!QSystemTrayIcon::isSystemTrayAvailable())
             {
-                addTrayMenuItem(item.first, item.second);
+                setError("No system tray is available");
+                interruptQuitter.reset();
+                delete app;
+                app = nullptr;
+                endTransition(false);
+                return -1;
             }
-            pending_menu_items.clear();
         }
-        return app->exec();
+        if (shouldInitTray)
+        {
+            setupTray();
+        }
+        for (QtAppWrapper *member : trays)
+        {
+            member->attach(app);
+        }
+        if (waitForTray)
+        {
+            waitForTrayHost(0, trayWaitInitialDelayMs);
+        }
+        endTransition(true);
+        int code = app->exec();
+        beginTransition();
+        resolveNotification(NotificationResultDismissed);
+        for (QtAppWrapper *member : trays)
+        {
+            member->beginTransition();
+            member->resolveNotification(NotificationResultDismissed);
+        }
+        // Run anything posted while the loop was shutting down so no runOnGuiThread caller is
+        // left waiting forever.
+        QCoreApplication::sendPostedEvents();
+        // Destroy the Qt objects, keeping the menus, so that run() can be called again.
+        for (QtAppWrapper *member : trays)
+        {
+            member->keepMenuItems();
+            member->detach(true);
+            member->endTransition(false);
+        }
+        keepMenuItems();
+        QApplication *finished = app;
+        detach(true);
+        interruptQuitter.reset();
+        delete finished;
+        endTransition(false);
+        return code;
+    }
+
+    // Adds a tray that runs on this wrapper's QApplication. The member keeps its own icon, menu
+    // and event queue; only the event loop is shared. May be called before or after run().
+    void addTray(QtAppWrapper *member)
+    {
+        runOnGuiThread([this, member]()
+                       {
+            member->shouldInitTray = true;
+            trays.push_back(member);
+            if (running) { member->attach(app); } });
+    }
+
+    // Removes a tray added with addTray, destroying its Qt objects if the event loop is running.
+    void removeTray(QtAppWrapper *member)
+    {
+        runOnGuiThread([this, member]()
+                       {
+            trays.erase(std::remove(trays.begin(), trays.end(), member), trays.end());
+            member->beginTransition();
+            member->detach(running);
+            member->endTransition(false); });
     }
 
     AppEvent pollEvent()
     {
+        // Fast path for idle polling loops: no lock, no allocation.
+        if (queuedEvents.load(std::memory_order_acquire) == 0)
+        {
+            return {None, nullptr};
+        }
+        std::lock_guard<std::mutex> lock(queueMutex);
         if (event_queue.empty())
         {
             return {None, nullptr};
         } // Use the new enum value directly
         AppEvent event = event_queue.front();
-        event_queue.erase(event_queue.begin());
+        event_queue.pop_front();
+        queuedEvents.store(event_queue.size(), std::memory_order_release);
         // Crucially, nullify the menu_id_str pointer in the *copied* event
         // to prevent accidental re-freeing if the queue or event are somehow re-used.
         // This is a defensive measure for C-style FFI.
@@ -107,123 +757,2657 @@ public:
         return event;
     }
 
-    void addTrayMenuItem(const std::string &text, const std::string &id_str)
+    void setEventQueueCapacity(size_t capacity, OverflowPolicy policy)
     {
-        if (!app)
+        std::lock_guard<std::mutex> lock(queueMutex);
+        queueCapacity = capacity;
+        overflowPolicy = policy;
+        if (overflowPolicy == Unbounded)
         {
-            pending_menu_items.push_back({text, id_str});
             return;
         }
-
-        if (!menu)
+        while (event_queue.size() > queueCapacity)
         {
-            menu = new QMenu();
-            if (tray)
+            if (overflowPolicy == DropOldest)
             {
-                tray->setContextMenu(menu);
+                dropEvent(event_queue.front());
+                event_queue.pop_front();
+            }
+            else
+            {
+                dropEvent(event_queue.back());
+                event_queue.pop_back();
             }
         }
+        queuedEvents.store(event_queue.size(), std::memory_order_release);
+    }
 
-        QAction *action = menu->addAction(QString::fromStdString(text));
-        QObject::connect(action, &QAction::triggered, [this, id_str]()
-                         {
-                             char *id_cstr = strdup(id_str.c_str());
-                             event_queue.push_back({MenuItemClicked, id_cstr}); // Use the new enum value directly
-                         });
+    // Events are pushed on the Qt thread, so switching callbacks there guarantees that none is
+    // in flight once this returns.
+    void setEventCallback(EventCallback callback, void *userData)
+    {
+        runOnGuiThread([&]()
+                       {
+            eventCallback = callback;
+            eventUserData = userData;
+            if (!callback) { return; }
+            std::deque<AppEvent> queued;
+            {
+                std::lock_guard<std::mutex> lock(queueMutex);
+                queued.swap(event_queue);
+                queuedEvents.store(0, std::memory_order_release);
+            }
+            for (const AppEvent &event : queued) {
+                callback(userData, event);
+            } });
     }
 
-    void requestQuitSafe()
+    // Bit n of mask enables events of AppEventType n.
+    void setEventMask(unsigned int mask)
     {
-        if (app)
-        {
-            QTimer::singleShot(0, app, &QApplication::quit);
-        }
+        runOnGuiThread([this, mask]()
+                       { eventMask = mask; });
     }
 
-private:
-    std::string appId;
-    std::string organizationName;
-    QByteArray iconData;
-    std::string iconFormat;
-    bool shouldInitTray = false;
-    std::vector<AppEvent> event_queue;
-    std::vector<std::pair<std::string, std::string>> pending_menu_items;
+    // Re-applies the icon, tooltip and menu to the QSystemTrayIcon. The icon is decoded anew, so
+    // platforms that skip an unchanged icon draw it again.
+    void refresh()
+    {
+        runOnGuiThread([this]()
+                       {
+            if (!tray) { return; }
+            applyAppIcon();
+            tray->setToolTip(tray->toolTip());
+            scheduleMenuRefresh(); });
+    }
 
-    QMenu *menu = nullptr;
-    QSystemTrayIcon *tray = nullptr;
-    QApplication *app = nullptr;
-};
+    void setEventsPaused(bool paused)
+    {
+        runOnGuiThread([this, paused]()
+                       {
+            eventsPaused = paused;
+            if (paused) { return; }
+            std::deque<AppEvent> held;
+            held.swap(pausedEvents);
+            for (const AppEvent &event : held) {
+                pushEvent(event);
+            } });
+    }
 
-extern "C"
-{
+    void setPausePolicy(PausePolicy policy)
+    {
+        runOnGuiThread([this, policy]()
+                       {
+            pausePolicy = policy;
+            if (policy != PauseDiscard) { return; }
+            for (const AppEvent &event : pausedEvents) {
+                dropEvent(event);
+            }
+            pausedEvents.clear(); });
+    }
 
-    QtAppHandle *create_qt_app() { return new QtAppHandle{new QtAppWrapper()}; }
-    void set_app_id(QtAppHandle *handle, const char *id)
+    void addTrayMenuItem(const MenuItemSpec &item)
     {
-        if (handle && handle->impl)
+        if (collectingLeftClickMenu)
         {
-            handle->impl->setAppId(id);
+            leftClickMenuReplacement.push_back(item);
+            return;
         }
-    }
-    void set_organization_name(QtAppHandle *handle, const char *name)
-    {
-        if (handle && handle->impl)
+        if (replacingMenu)
         {
-            handle->impl->setOrganizationName(name);
+            replacementMenuItems.push_back(item);
+            return;
         }
+        // Widgets may only be touched on the Qt thread. Before the tray is set up, the item is
+        // kept for setupTray instead.
+        runOnGuiThread([this, &item]()
+                       {
+            if (!app) {
+                pending_menu_items.push_back(item);
+                return;
+            }
+            insertMenuItem(item);
+            scheduleMenuRefresh(); });
     }
-    void set_app_icon_from_data(QtAppHandle *handle, const unsigned char *data, size_t size, const char *format)
+
+    // Creates the action, submenu or separator for `item`. Must run on the Qt thread.
+    void insertMenuItem(const MenuItemSpec &item)
     {
-        if (handle && handle->impl)
+        menuItems.push_back(item);
+
+        if (!menu)
         {
-            handle->impl->setAppIcon(data, size, format);
+            menu = createMenu();
+            applyContextMenu();
         }
-    }
-    void init_tray(QtAppHandle *handle)
-    {
-        if (handle && handle->impl)
+
+        QMenu *target = menu;
+        if (!item.parent.empty())
         {
-            handle->impl->initTray();
+            auto parent = submenus.find(item.parent);
+            if (parent == submenus.end())
+            {
+                setError("No submenu \"" + item.parent + "\" to add the menu item \"" + item.id + "\" to");
+                return;
+            }
+            target = parent->second;
         }
-    }
-    int run_qt_app(QtAppHandle *handle, int argc, char *argv[])
-    {
-        if (handle && handle->impl)
+        if (item.separator)
         {
-            return handle->impl->run(argc, argv);
+            target->addSeparator();
+            return;
+        }
+
+        const std::string id_str = item.path();
+        QAction *action;
+        if (item.submenu)
+        {
+            QMenu *submenu = target->addMenu(QString::fromStdString(item.text));
+            submenu->setTearOffEnabled(tearOffEnabled);
+            if (hasMenuFont)
+            {
+                submenu->setFont(menuFont);
+            }
+            submenus[id_str] = submenu;
+            action = submenu->menuAction();
+        }
+        else if (item.progress)
+        {
+            QWidgetAction *widgetAction = new QWidgetAction(target);
+            ProgressItemBar *bar = new ProgressItemBar(widgetAction);
+            bar->setRange(0, 100);
+            bar->setValue(item.progressValue);
+            bar->setFormat(QString::fromStdString(item.text) + QStringLiteral("  %p%"));
+            widgetAction->setText(QString::fromStdString(item.text));
+            widgetAction->setDefaultWidget(bar);
+            target->addAction(widgetAction);
+            progressBars[id_str] = bar;
+            action = widgetAction;
+        }
+        else if (item.tristate)
+        {
+            QWidgetAction *widgetAction = new QWidgetAction(target);
+            TristateItemBox *box = new TristateItemBox(widgetAction);
+            box->setText(QString::fromStdString(item.text));
+            box->setCheckState(qtCheckState(item.checkState));
+            widgetAction->setText(QString::fromStdString(item.text));
+            widgetAction->setDefaultWidget(box);
+            target->addAction(widgetAction);
+            action = widgetAction;
+        }
+        else
+        {
+            action = target->addAction(QString::fromStdString(item.text));
+        }
+        action->setData(QString::fromStdString(id_str));
+        action->setEnabled(item.enabled);
+        action->setVisible(item.visible);
+        action->setCheckable(item.checkable);
+        action->setChecked(item.checked);
+        action->setMenuRole(qtMenuRole(item.role));
+        if (!item.shortcut.empty())
+        {
+            action->setShortcut(QKeySequence(QString::fromStdString(item.shortcut), QKeySequence::PortableText));
+            action->setShortcutContext(qtShortcutContext(item.shortcutContext));
+            // Some platforms hide shortcuts in context menus, which the tray menu is.
+            action->setShortcutVisibleInContextMenu(true);
+        }
+        if (!item.iconData.isEmpty())
+        {
+            // For a submenu this is the icon of the entry that opens it.
+            QPixmap pixmap;
+            if (pixmap.loadFromData(item.iconData, item.iconFormat.empty() ? nullptr : item.iconFormat.c_str()))
+            {
+                action->setIcon(QIcon(pixmap));
+            }
+            else
+            {
+                setError("Failed to decode the icon of the menu item \"" + id_str + "\"");
+            }
+        }
+        if (persistedChecks.count(id_str))
+        {
+            persistCheckState(action, id_str);
+        }
+        if (item.bold || item.italic)
+        {
+            action->setFont(emphasizedFont(item.bold, item.italic));
+        }
+        if (!item.accessibleDescription.empty())
+        {
+            // Menu items have no accessible description of their own; Qt reports an action's
+            // "What's This?" text as the description of its menu entry.
+            action->setWhatsThis(QString::fromStdString(item.accessibleDescription));
+        }
+        actions[id_str] = action;
+        QObject::connect(action, &QAction::hovered, [this, id_str]()
+                         { pushEvent({MenuItemHovered, strdup(id_str.c_str())}); });
+        if (item.submenu)
+        {
+            return;
+        }
+        const QUrl url(QString::fromStdString(item.url));
+        QObject::connect(action, &QAction::triggered, [this, id_str, url]()
+                         {
+                             if (!url.isEmpty())
+                             {
+                                 QDesktopServices::openUrl(url);
+                             }
+                             char *id_cstr = strdup(id_str.c_str());
+                             pushEvent({MenuItemClicked, id_cstr}); // Use the new enum value directly
+                         });
+    }
+
+    // Items added between beginMenuReplace and finishMenuReplace are collected instead of
+    // added, then built into a new menu that replaces the current one in a single step.
+    void beginMenuReplace()
+    {
+        replacingMenu = true;
+        replacementMenuItems.clear();
+    }
+
+    void finishMenuReplace(bool apply)
+    {
+        std::vector<MenuItemSpec> items = std::move(replacementMenuItems);
+        replacementMenuItems.clear();
+        replacingMenu = false;
+        if (!apply)
+        {
+            return;
+        }
+        runOnGuiThread([this, &items]()
+                       {
+            if (!app) {
+                pending_menu_items = std::move(items);
+                return;
+            }
+            // The new menu is built while the previous one stays attached to the tray, so the
+            // user never sees a partially built menu.
+            QMenu *previous = menu;
+            menu = createMenu();
+            actions.clear();
+            submenus.clear();
+            progressBars.clear();
+            menuItems.clear();
+            for (const auto &item : items) {
+                insertMenuItem(item);
+            }
+            applyAccessibleName();
+            applyMenuFont();
+            applyContextMenu();
+            if (previous) { previous->deleteLater(); } });
+    }
+
+    void beginLeftClickMenu()
+    {
+        collectingLeftClickMenu = true;
+        leftClickMenuReplacement.clear();
+    }
+
+    void finishLeftClickMenu(bool apply)
+    {
+        std::vector<MenuItemSpec> items = std::move(leftClickMenuReplacement);
+        leftClickMenuReplacement.clear();
+        collectingLeftClickMenu = false;
+        if (!apply)
+        {
+            return;
+        }
+        runOnGuiThread([this, &items]()
+                       {
+            leftClickMenuItems = std::move(items);
+            buildLeftClickMenu(); });
+    }
+
+    // Rebuilds the left-click menu from leftClickMenuItems. Its items aren't tracked by id, as
+    // only their clicks matter. Must run on the Qt thread.
+    void buildLeftClickMenu()
+    {
+        if (leftClickMenu)
+        {
+            leftClickMenu->deleteLater();
+            leftClickMenu = nullptr;
+        }
+        if (!app || leftClickMenuItems.empty())
+        {
+            return;
+        }
+        leftClickMenu = new QMenu();
+        std::unordered_map<std::string, QMenu *> parents;
+        for (const MenuItemSpec &item : leftClickMenuItems)
+        {
+            QMenu *target = leftClickMenu;
+            if (!item.parent.empty())
+            {
+                auto parent = parents.find(item.parent);
+                if (parent == parents.end())
+                {
+                    setError("No submenu \"" + item.parent + "\" to add the menu item \"" + item.id + "\" to");
+                    continue;
+                }
+                target = parent->second;
+            }
+            if (item.separator)
+            {
+                target->addSeparator();
+                continue;
+            }
+            const std::string id_str = item.path();
+            if (item.submenu)
+            {
+                QMenu *submenu = target->addMenu(QString::fromStdString(item.text));
+                parents[id_str] = submenu;
+                QObject::connect(submenu->menuAction(), &QAction::hovered, [this, id_str]()
+                                 { pushEvent({MenuItemHovered, strdup(id_str.c_str())}); });
+                continue;
+            }
+            QAction *action = target->addAction(QString::fromStdString(item.text));
+            QObject::connect(action, &QAction::triggered, [this, id_str]()
+                             { pushEvent({MenuItemClicked, strdup(id_str.c_str())}); });
+            QObject::connect(action, &QAction::hovered, [this, id_str]()
+                             { pushEvent({MenuItemHovered, strdup(id_str.c_str())}); });
+        }
+    }
+
+    void popupMenu(const QPoint &pos)
+    {
+        postToGuiThread([this, pos]()
+                        {
+            if (menu) { menu->popup(pos); } });
+    }
+
+    void popupMenuAtCursor()
+    {
+        postToGuiThread([this]()
+                        {
+            if (menu) { menu->popup(QCursor::pos()); } });
+    }
+
+    // Queued calls run in order, so once an empty one has run, everything posted before it has.
+    void flush()
+    {
+        runOnGuiThread([]() {});
+    }
+
+    char *activeMenuItem()
+    {
+        char *id = nullptr;
+        runOnGuiThread([this, &id]()
+                       {
+            if (!menu || !menu->isVisible()) { return; }
+            if (QAction *action = menu->activeAction()) {
+                id = strdup(action->data().toString().toStdString().c_str());
+            } });
+        return id;
+    }
+
+    // Returns whether an item has the given path.
+    bool setActiveMenuItem(const std::string &id)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, &found]()
+                       {
+            found = actions.count(id) > 0;
+            for (const auto &item : pending_menu_items) {
+                found = found || item.path() == id;
+            }
+            if (!found) { return; }
+            activeItem = id;
+            if (menu && menu->isVisible()) { applyActiveItem(); } });
+        return found;
+    }
+
+    // Highlights activeItem, or the top-level submenu containing it, in the context menu.
+    void applyActiveItem()
+    {
+        if (!menu || activeItem.empty())
+        {
+            return;
+        }
+        auto action = actions.find(activeItem.substr(0, activeItem.find('/')));
+        if (action != actions.end())
+        {
+            menu->setActiveAction(action->second);
+        }
+    }
+
+    // Submenus are popups of their own, shown while the top-level menu stays open.
+    bool isMenuOpen()
+    {
+        bool open = false;
+        runOnGuiThread([this, &open]()
+                       {
+            open = menu && menu->isVisible(); });
+        return open;
+    }
+
+    void setMenuItemsEnabled(const char *const *ids, const bool *enabled, size_t count, bool *found)
+    {
+        runOnGuiThread([&]()
+                       {
+            for (size_t i = 0; i < count; ++i) {
+                const std::string id = ids[i];
+                found[i] = false;
+                auto action = actions.find(id);
+                if (action != actions.end()) {
+                    action->second->setEnabled(enabled[i]);
+                    found[i] = true;
+                    continue;
+                }
+                for (auto &item : pending_menu_items) {
+                    if (item.path() == id) {
+                        item.enabled = enabled[i];
+                        found[i] = true;
+                    }
+                }
+            } });
+    }
+
+    // Returns whether a menu item has the given id (a path).
+    bool setMenuItemVisible(const std::string &id, bool visible)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, visible, &found]()
+                       {
+            auto action = actions.find(id);
+            if (action != actions.end()) {
+                action->second->setVisible(visible);
+                found = true;
+                return;
+            }
+            for (auto &item : pending_menu_items) {
+                if (!item.separator && item.path() == id) {
+                    item.visible = visible;
+                    found = true;
+                }
+            } });
+        return found;
+    }
+
+    // Items that aren't created yet, before the tray is set up, count as well.
+    bool hasMenuItem(const std::string &id)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, &found]()
+                       {
+            found = actions.count(id) > 0 ||
+                    std::any_of(pending_menu_items.begin(), pending_menu_items.end(), [&](const MenuItemSpec &item)
+                                { return !item.separator && item.path() == id; }); });
+        return found;
+    }
+
+    // Returns whether a menu item has the given id (a path).
+    bool setMenuItemText(const std::string &id, const std::string &text)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, &text, &found]()
+                       {
+            // menuItems is updated too, so the text survives the tray being set up again.
+            for (auto *items : {&menuItems, &pending_menu_items}) {
+                for (auto &item : *items) {
+                    if (!item.separator && item.path() == id) {
+                        item.text = text;
+                        found = true;
+                    }
+                }
+            }
+            auto action = actions.find(id);
+            if (action == actions.end()) {
+                return;
+            }
+            // A submenu's title is the text of its menu action.
+            action->second->setText(QString::fromStdString(text));
+            if (QCheckBox *box = tristateBox(action->second)) {
+                box->setText(QString::fromStdString(text));
+            }
+            auto bar = progressBars.find(id);
+            if (bar != progressBars.end()) {
+                bar->second->setFormat(QString::fromStdString(text) + QStringLiteral("  %p%"));
+            }
+            scheduleMenuRefresh(); });
+        return found;
+    }
+
+    // Removes a menu item, or a submenu with everything in it. Returns whether a menu item has
+    // the given id (a path).
+    bool removeMenuItem(const std::string &id)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, &found]()
+                       { found = removeMenuItemNow(id); });
+        return found;
+    }
+
+    // Removes the items whose paths start with prefix; a submenu counts as one item.
+    size_t removeMenuItemsByPrefix(const std::string &prefix)
+    {
+        size_t removed = 0;
+        runOnGuiThread([this, &prefix, &removed]()
+                       {
+            std::vector<std::string> paths;
+            for (auto *items : {&menuItems, &pending_menu_items}) {
+                for (const auto &item : *items) {
+                    if (!item.separator && item.path().rfind(prefix, 0) == 0) {
+                        paths.push_back(item.path());
+                    }
+                }
+            }
+            // Items in a removed submenu are gone by the time their own path comes up.
+            for (const auto &path : paths) {
+                removed += removeMenuItemNow(path) ? 1 : 0;
+            } });
+        return removed;
+    }
+
+    // Moves a menu item, or a submenu with everything in it, to the given position among the
+    // entries of its menu, separators included; positions past the last entry move it to the end.
+    // Keyboard navigation follows the order of a menu's actions, so it follows the move too.
+    // Returns whether a menu item has the given id (a path).
+    bool moveMenuItem(const std::string &id, size_t index)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, index, &found]()
+                       {
+            const std::string prefix = id + "/";
+            auto below = [&](const MenuItemSpec &item)
+            {
+                const std::string path = item.path();
+                return path == id || path.rfind(prefix, 0) == 0;
+            };
+            std::string parent;
+            // menuItems is updated too, so the order survives the tray being set up again.
+            for (auto *items : {&menuItems, &pending_menu_items}) {
+                auto item = std::find_if(items->begin(), items->end(), [&](const MenuItemSpec &item)
+                                         { return !item.separator && item.path() == id; });
+                if (item == items->end()) {
+                    continue;
+                }
+                found = true;
+                parent = item->parent;
+                std::vector<MenuItemSpec> moved;
+                for (const MenuItemSpec &entry : *items) {
+                    if (below(entry)) {
+                        moved.push_back(entry);
+                    }
+                }
+                items->erase(std::remove_if(items->begin(), items->end(), below), items->end());
+                // The items in a submenu come after it, wherever the submenu itself is.
+                auto before = items->end();
+                size_t position = 0;
+                for (auto entry = items->begin(); entry != items->end(); ++entry) {
+                    if (entry->parent == parent && position++ == index) {
+                        before = entry;
+                        break;
+                    }
+                }
+                items->insert(before, moved.begin(), moved.end());
+            }
+            auto action = actions.find(id);
+            if (action == actions.end()) {
+                return;
+            }
+            QMenu *target = menu;
+            if (!parent.empty()) {
+                auto submenu = submenus.find(parent);
+                if (submenu == submenus.end()) {
+                    return;
+                }
+                target = submenu->second;
+            }
+            target->removeAction(action->second);
+            const QList<QAction *> entries = target->actions();
+            target->insertAction(index < static_cast<size_t>(entries.size()) ? entries[index] : nullptr, action->second);
+            scheduleMenuRefresh(); });
+        return found;
+    }
+
+    // Returns whether a progress item has the given id.
+    bool setMenuItemProgress(const std::string &id, int value)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, value, &found]()
+                       {
+            auto bar = progressBars.find(id);
+            if (bar != progressBars.end()) {
+                bar->second->setValue(value);
+                found = true;
+                return;
+            }
+            for (auto &item : pending_menu_items) {
+                if (item.progress && item.path() == id) {
+                    item.progressValue = value;
+                    found = true;
+                }
+            } });
+        return found;
+    }
+
+    void persistCheckable(const std::string &id)
+    {
+        runOnGuiThread([this, &id]()
+                       {
+            if (!persistedChecks.insert(id).second) { return; }
+            auto action = actions.find(id);
+            if (action != actions.end()) { persistCheckState(action->second, id); } });
+    }
+
+    int toggleMenuItemChecked(const std::string &id)
+    {
+        int state = -1;
+        runOnGuiThread([this, &id, &state]()
+                       {
+            auto action = actions.find(id);
+            if (action != actions.end()) {
+                if (QCheckBox *box = tristateBox(action->second)) {
+                    box->setCheckState(box->checkState() == Qt::Checked ? Qt::Unchecked : Qt::Checked);
+                    state = box->checkState() == Qt::Checked ? 1 : 0;
+                    return;
+                }
+                action->second->setCheckable(true);
+                action->second->setChecked(!action->second->isChecked());
+                state = action->second->isChecked() ? 1 : 0;
+                return;
+            }
+            for (auto &item : pending_menu_items) {
+                if (item.path() == id) {
+                    item.checkable = true;
+                    item.checked = item.tristate ? item.checkState != CheckStateChecked : !item.checked;
+                    item.checkState = item.checked ? CheckStateChecked : CheckStateUnchecked;
+                    state = item.checked ? 1 : 0;
+                }
+            } });
+        return state;
+    }
+
+    // Returns whether a menu item has the given id.
+    bool setMenuItemCheckState(const std::string &id, CheckState checkState)
+    {
+        bool found = false;
+        runOnGuiThread([this, &id, checkState, &found]()
+                       {
+            auto action = actions.find(id);
+            if (action != actions.end()) {
+                if (QCheckBox *box = tristateBox(action->second)) {
+                    box->setCheckState(qtCheckState(checkState));
+                } else {
+                    action->second->setCheckable(true);
+                    action->second->setChecked(checkState == CheckStateChecked);
+                }
+                found = true;
+                return;
+            }
+            for (auto &item : pending_menu_items) {
+                if (item.path() == id) {
+                    item.checkable = true;
+                    item.checked = checkState == CheckStateChecked;
+                    item.checkState = checkState;
+                    found = true;
+                }
+            } });
+        return found;
+    }
+
+    void setBusy(bool enabled)
+    {
+        runOnGuiThread([this, enabled]()
+                       {
+            busy = enabled;
+            updateBusyAnimation(); });
+    }
+
+    // Returns false if another process already holds `key`, after passing it `args` (the
+    // arguments separated by '\x1f'). Otherwise this process becomes the primary instance and
+    // listens for later launches, from the moment the tray is set up if it isn't yet.
+    bool acquireSingleInstance(const std::string &key, const std::string &args)
+    {
+        bool primary = false;
+        runOnGuiThread([&]()
+                       {
+            const QString name = QString::fromStdString(key);
+            QLocalSocket socket;
+            socket.connectToServer(name);
+            if (socket.waitForConnected(instanceConnectTimeoutMs)) {
+                socket.write(QByteArray::fromStdString(args));
+                socket.waitForBytesWritten(instanceConnectTimeoutMs);
+                socket.disconnectFromServer();
+                return;
+            }
+            primary = true;
+            instanceKey = key;
+            if (tray) { startInstanceServer(); } });
+        return primary;
+    }
+
+    // Shows QSystemTrayIcon's own message balloon. Must run on the Qt thread, with a tray.
+    void showBalloon(const std::string &title, const std::string &body, int timeoutMs, NotificationIcon icon,
+                     const QByteArray &iconData, const std::string &iconFormat)
+    {
+        if (icon == NotificationIconCustom)
+        {
+            QPixmap pixmap;
+            if (pixmap.loadFromData(iconData, iconFormat.empty() ? nullptr : iconFormat.c_str()))
+            {
+                tray->showMessage(QString::fromStdString(title), QString::fromStdString(body), QIcon(pixmap), timeoutMs);
+                return;
+            }
+            setError("Failed to decode the notification icon");
+        }
+        const QSystemTrayIcon::MessageIcon severity = icon == NotificationIconWarning    ? QSystemTrayIcon::Warning
+                                                      : icon == NotificationIconCritical ? QSystemTrayIcon::Critical
+                                                                                         : QSystemTrayIcon::Information;
+        tray->showMessage(QString::fromStdString(title), QString::fromStdString(body), severity, timeoutMs);
+    }
+
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+    // Shows the notification through the freedesktop notification service, replacing the one
+    // this tray showed before. Returns false, for the balloon to be shown instead, unless the
+    // service is running on the session bus. Must run on the Qt thread.
+    bool sendDbusNotification(const std::string &title, const std::string &body, int timeoutMs, NotificationIcon icon,
+                              const QByteArray &iconData, const std::string &iconFormat,
+                              const std::vector<std::pair<std::string, std::string>> &actions)
+    {
+        const QString service = QStringLiteral("org.freedesktop.Notifications");
+        const QString path = QStringLiteral("/org/freedesktop/Notifications");
+        QDBusConnection bus = QDBusConnection::sessionBus();
+        if (!bus.isConnected() || !bus.interface() || !bus.interface()->isServiceRegistered(service))
+        {
+            return false;
+        }
+        if (!notificationSignals)
+        {
+            qDBusRegisterMetaType<NotificationImage>();
+            notificationSignals = new NotificationSignals(app);
+            notificationSignals->onActionInvoked = [this](uint serverId, const QString &key)
+            {
+                if (serverId == 0 || serverId != dbusNotificationId)
+                {
+                    return;
+                }
+                AppEvent event{NotificationClicked, nullptr};
+                if (key.startsWith(QStringLiteral("action:")))
+                {
+                    event.type_ = NotificationActionInvoked;
+                    event.menu_id_str = strdup(key.mid(7).toStdString().c_str());
+                }
+                event.notification_id = lastNotificationId;
+                pushEvent(event);
+                resolveNotification(NotificationResultClicked);
+            };
+            notificationSignals->onClosed = [this](uint serverId, uint reason)
+            {
+                if (serverId == 0 || serverId != dbusNotificationId)
+                {
+                    return;
+                }
+                dbusNotificationId = 0;
+                // Reason 1 is an expired notification; the others are closed by the user or the app.
+                resolveNotification(reason == 1 ? NotificationResultTimedOut : NotificationResultDismissed);
+            };
+            bus.connect(service, path, service, QStringLiteral("ActionInvoked"), notificationSignals,
+                        SLOT(actionInvoked(uint, QString)));
+            bus.connect(service, path, service, QStringLiteral("NotificationClosed"), notificationSignals,
+                        SLOT(notificationClosed(uint, uint)));
+        }
+
+        // Clicking the notification itself invokes the "default" action; the buttons' ids are
+        // prefixed, so none of them is taken for it.
+        QStringList actionList{QStringLiteral("default"), QString()};
+        for (const auto &action : actions)
+        {
+            actionList << QStringLiteral("action:") + QString::fromStdString(action.first)
+                       << QString::fromStdString(action.second);
+        }
+        QVariantMap hints;
+        hints[QStringLiteral("urgency")] = QVariant::fromValue<uchar>(icon == NotificationIconCritical ? 2 : 1);
+        QString appIcon;
+        if (icon == NotificationIconCustom)
+        {
+            QImage image;
+            if (image.loadFromData(iconData, iconFormat.empty() ? nullptr : iconFormat.c_str()))
+            {
+                image = image.convertToFormat(QImage::Format_RGBA8888);
+                NotificationImage data;
+                data.width = image.width();
+                data.height = image.height();
+                data.rowStride = static_cast<int>(image.bytesPerLine());
+                data.data = QByteArray(reinterpret_cast<const char *>(image.constBits()), image.sizeInBytes());
+                hints[QStringLiteral("image-data")] = QVariant::fromValue(data);
+            }
+            else
+            {
+                setError("Failed to decode the notification icon");
+                icon = NotificationIconInformation;
+            }
+        }
+        if (icon != NotificationIconCustom)
+        {
+            appIcon = icon == NotificationIconWarning    ? QStringLiteral("dialog-warning")
+                      : icon == NotificationIconCritical ? QStringLiteral("dialog-error")
+                                                         : QStringLiteral("dialog-information");
+        }
+
+        QDBusMessage call = QDBusMessage::createMethodCall(service, path, service, QStringLiteral("Notify"));
+        call << QGuiApplication::applicationDisplayName() << dbusNotificationId << appIcon
+             << QString::fromStdString(title) << QString::fromStdString(body) << actionList << hints
+             << (timeoutMs > 0 ? timeoutMs : -1);
+        // The reply carries the service's id for the notification, which its signals refer to.
+        const unsigned int localId = lastNotificationId;
+        auto *watcher = new QDBusPendingCallWatcher(bus.asyncCall(call), notificationSignals);
+        QObject::connect(watcher, &QDBusPendingCallWatcher::finished, [this, localId](QDBusPendingCallWatcher *watcher)
+                         {
+            QDBusPendingReply<uint> reply = *watcher;
+            watcher->deleteLater();
+            if (reply.isError()) {
+                setError("The notification service failed to show the notification: " +
+                         reply.error().message().toStdString());
+            } else if (localId == lastNotificationId) {
+                dbusNotificationId = reply.value();
+            } });
+        return true;
+    }
+#endif
+
+    // `callback`, if set, is called exactly once with the outcome, unless 0 is returned.
+    unsigned int showNotification(const std::string &title, const std::string &body, int timeoutMs,
+                                  NotificationIcon icon, const QByteArray &iconData, const std::string &iconFormat,
+                                  const std::vector<std::pair<std::string, std::string>> &actions,
+                                  NotificationCallback callback, void *userData)
+    {
+        unsigned int id = 0;
+        runOnGuiThread([&]()
+                       {
+            if (!tray) {
+                setError("There is no tray icon to show the notification from");
+                return;
+            }
+            // The new message replaces the previous one.
+            resolveNotification(NotificationResultDismissed);
+            id = ++lastNotificationId;
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+            // The balloon is the fallback for sessions without a notification service.
+            if (!sendDbusNotification(title, body, timeoutMs, icon, iconData, iconFormat, actions))
+#endif
+            {
+                showBalloon(title, body, timeoutMs, icon, iconData, iconFormat);
+            }
+            if (callback) {
+                notificationCallback = callback;
+                notificationUserData = userData;
+                if (!notificationTimer) {
+                    notificationTimer = new QTimer(app);
+                    notificationTimer->setSingleShot(true);
+                    QObject::connect(notificationTimer, &QTimer::timeout, [this]()
+                                     { resolveNotification(NotificationResultTimedOut); });
+                }
+                notificationTimer->start(timeoutMs > 0 ? timeoutMs : defaultNotificationTimeoutMs);
+            } });
+        return id;
+    }
+
+    void requestAttention(int count)
+    {
+        runOnGuiThread([this, count]()
+                       { startAttentionBlink(count); });
+    }
+
+    void setBusyFrames(std::vector<QByteArray> frames, const std::string &format)
+    {
+        runOnGuiThread([this, &frames, &format]()
+                       {
+            busyFrameData = std::move(frames);
+            busyFrameFormat = format;
+            if (busy) { updateBusyAnimation(); } });
+    }
+
+    // An empty family or a non-positive size keeps the default of the menu's style.
+    void setMenuFont(const std::string &family, double pointSize)
+    {
+        runOnGuiThread([this, family, pointSize]()
+                       {
+            menuFont = QApplication::font("QMenu");
+            if (!family.empty()) {
+                // An unavailable family is substituted by Qt's font matching, with the style's
+                // default family as the last resort.
+                menuFont.setFamilies({QString::fromStdString(family), menuFont.family()});
+            }
+            if (pointSize > 0) { menuFont.setPointSizeF(pointSize); }
+            hasMenuFont = true;
+            applyMenuFont(); });
+    }
+
+    void setTearOffEnabled(bool enabled)
+    {
+        runOnGuiThread([this, enabled]()
+                       {
+            tearOffEnabled = enabled;
+            if (menu) { menu->setTearOffEnabled(enabled); }
+            for (auto &submenu : submenus) { submenu.second->setTearOffEnabled(enabled); } });
+    }
+
+    void setButtonBehavior(MouseButton button, ButtonBehavior behavior, const std::string &itemId)
+    {
+        runOnGuiThread([this, button, behavior, itemId]()
+                       {
+            buttonBehaviors[button] = behavior;
+            buttonItems[button] = itemId;
+            applyContextMenu(); });
+    }
+
+    // Platforms show an attached context menu on their own, so it's only attached while the
+    // right button is meant to open it.
+    void applyContextMenu()
+    {
+        if (tray)
+        {
+            tray->setContextMenu(buttonBehaviors[MouseButtonRight] == ButtonOpenMenu ? menu : nullptr);
+        }
+    }
+
+    // StatusNotifierItem hosts (e.g. KDE Plasma, notably on Wayland) may keep showing the menu
+    // as first exported over DBus when items are added to it later. Re-attaching the menu once
+    // the current batch of changes is done makes Qt export it again.
+    void scheduleMenuRefresh()
+    {
+#if defined(Q_OS_UNIX) && !defined(Q_OS_MACOS)
+        if (menuRefreshPending || !tray)
+        {
+            return;
+        }
+        menuRefreshPending = true;
+        QTimer::singleShot(0, tray, [this]()
+                           {
+            menuRefreshPending = false;
+            if (tray && tray->contextMenu())
+            {
+                tray->setContextMenu(nullptr);
+                applyContextMenu();
+            } });
+#endif
+    }
+
+    // Runs the behavior set for `button` after a click on the icon. Must run on the Qt thread.
+    void handleButton(MouseButton button)
+    {
+        const QPoint pos = QCursor::pos();
+        if (button == MouseButtonLeft && leftClickMenu)
+        {
+            leftClickMenu->popup(pos);
+            return;
+        }
+        switch (buttonBehaviors[button])
+        {
+        case ButtonEmitClick:
+            if (button == MouseButtonLeft)
+            {
+                pushEvent({TrayClicked, nullptr, pos.x(), pos.y()});
+            }
+            else
+            {
+                AppEvent event{TrayButtonClicked, nullptr, pos.x(), pos.y()};
+                event.button = button;
+                pushEvent(event);
+            }
+            break;
+        case ButtonOpenMenu:
+            // The attached menu already opens on its own for the right button.
+            if (button != MouseButtonRight && menu) { menu->popup(pos); }
+            break;
+        case ButtonTriggerItem:
+        {
+            auto action = actions.find(buttonItems[button]);
+            if (action != actions.end()) { action->second->trigger(); }
+            break;
+        }
+        default:
+            break;
+        }
+    }
+
+    // A hidden QSystemTrayIcon isn't registered with the systray host at all.
+    void setIconShown(bool shown)
+    {
+        runOnGuiThread([this, shown]()
+                       {
+            iconShown = shown;
+            if (tray) { tray->setVisible(shown); } });
+    }
+
+    void setAccessibleName(const std::string &name)
+    {
+        runOnGuiThread([this, name]()
+                       {
+            accessibleName = name;
+            applyAccessibleName(); });
+    }
+
+    void setPreferredIconSize(int size)
+    {
+        runOnGuiThread([this, size]()
+                       {
+            preferredIconSize = size;
+            if (app) { applyAppIcon(); } });
+    }
+
+    bool isSystemTrayAvailable()
+    {
+        bool available = false;
+        runOnGuiThread([this, &available]()
+                       {
+            if (app) { available = QSystemTrayIcon::isSystemTrayAvailable(); } });
+        return available;
+    }
+
+    size_t getScreens(ScreenInfo *out, size_t capacity)
+    {
+        size_t count = 0;
+        runOnGuiThread([this, out, capacity, &count]()
+                       {
+            if (!app) { return; }
+            const QList<QScreen *> screens = QGuiApplication::screens();
+            count = static_cast<size_t>(screens.size());
+            for (size_t i = 0; i < count && i < capacity; ++i) {
+                const QScreen *screen = screens[i];
+                const QRect geometry = screen->geometry();
+                const QRect available = screen->availableGeometry();
+                out[i] = ScreenInfo{geometry.x(), geometry.y(), geometry.width(), geometry.height(),
+                                    available.x(), available.y(), available.width(), available.height(),
+                                    screen->logicalDotsPerInch(), screen->devicePixelRatio(),
+                                    screen == QGuiApplication::primaryScreen()};
+            } });
+        return count;
+    }
+
+    // Returns a copy of the last recorded failure, or null if there is none.
+    char *lastErrorCopy()
+    {
+        std::lock_guard<std::mutex> lock(errorMutex);
+        return lastError.empty() ? nullptr : strdup(lastError.c_str());
+    }
+
+    bool openPath(const std::string &path)
+    {
+        bool opened = false;
+        runOnGuiThread([this, &path, &opened]()
+                       {
+            if (!app) {
+                setError("The event loop isn't running");
+                return;
+            }
+            opened = QDesktopServices::openUrl(QUrl::fromLocalFile(QString::fromStdString(path)));
+            if (!opened) { setError("No handler could open " + path); } });
+        return opened;
+    }
+
+    // Returns 1 if the user chose Yes, 0 if they chose No or closed the dialog, and -1 if the
+    // event loop isn't running.
+    int confirm(const std::string &title, const std::string &text)
+    {
+        int answer = -1;
+        runOnGuiThread([this, &title, &text, &answer]()
+                       {
+            if (!app) {
+                setError("The event loop isn't running");
+                return;
+            }
+            // A tray app usually has no other window, so closing the dialog would quit it.
+            const bool quitOnLastWindowClosed = QGuiApplication::quitOnLastWindowClosed();
+            QGuiApplication::setQuitOnLastWindowClosed(false);
+            QMessageBox box(QMessageBox::Question, QString::fromStdString(title), QString::fromStdString(text),
+                            QMessageBox::Yes | QMessageBox::No);
+            answer = box.exec() == QMessageBox::Yes ? 1 : 0;
+            QGuiApplication::setQuitOnLastWindowClosed(quitOnLastWindowClosed); });
+        return answer;
+    }
+
+    QSystemTrayIcon *trayIcon()
+    {
+        QSystemTrayIcon *icon = nullptr;
+        runOnGuiThread([this, &icon]()
+                       { icon = tray; });
+        return icon;
+    }
+
+    void requestQuitSafe()
+    {
+        if (app)
+        {
+            QTimer::singleShot(0, app, &QApplication::quit);
+        }
+    }
+
+private:
+    // Creates an empty context menu that reports being shown and closed.
+    QMenu *createMenu()
+    {
+        QMenu *created = new QMenu();
+        created->setTearOffEnabled(tearOffEnabled);
+        QObject::connect(created, &QMenu::aboutToShow, [this, created]()
+                         {
+            pushEvent({MenuAboutToShow, nullptr});
+            // Showing the menu resets its active action, so it's set once the menu is shown.
+            QTimer::singleShot(0, created, [this]()
+                               { applyActiveItem(); }); });
+        QObject::connect(created, &QMenu::aboutToHide, [this]()
+                         { pushEvent({MenuClosed, nullptr}); });
+        return created;
+    }
+
+    // Creates the tray icon and its menu. Must run on the Qt thread once the QApplication exists.
+    void setupTray()
+    {
+        menu = createMenu();
+        tray = new QSystemTrayIcon(displayedTrayIcon());
+        applyContextMenu();
+        applyAccessibleName();
+        applyMenuFont();
+
+        QObject::connect(tray, &QSystemTrayIcon::activated, [this](QSystemTrayIcon::ActivationReason reason)
+                         {
+            if (attention) { finishAnimation(); }
+            if (reason == QSystemTrayIcon::Trigger) { handleButton(MouseButtonLeft); }
+            else if (reason == QSystemTrayIcon::Context) { handleButton(MouseButtonRight); }
+            else if (reason == QSystemTrayIcon::MiddleClick) { handleButton(MouseButtonMiddle); }
+            else if (reason == QSystemTrayIcon::DoubleClick) {
+                const QPoint pos = QCursor::pos();
+                pushEvent({TrayDoubleClicked, nullptr, pos.x(), pos.y()}); // Use the new enum value directly
+            } });
+        // Qt doesn't say which message was clicked, but each tray shows one at a time, with a
+        // new message replacing the previous one, so the click belongs to the latest message.
+        QObject::connect(tray, &QSystemTrayIcon::messageClicked, [this]()
+                         {
+            AppEvent event{NotificationClicked, nullptr};
+            event.notification_id = lastNotificationId;
+            pushEvent(event);
+            resolveNotification(NotificationResultClicked); });
+        tray->setVisible(iconShown);
+#if QT_VERSION >= QT_VERSION_CHECK(6, 5, 0)
+        QObject::connect(QGuiApplication::styleHints(), &QStyleHints::colorSchemeChanged, tray, [this]()
+                         {
+            AppEvent event{ColorSchemeChanged, nullptr};
+            event.dark = isDarkColorScheme();
+            pushEvent(event);
+            if (themedIcon) { applyAppIcon(); } });
+#endif
+
+        for (const auto &item : pending_menu_items)
+        {
+            insertMenuItem(item);
+        }
+        pending_menu_items.clear();
+        buildLeftClickMenu();
+
+        if (busy)
+        {
+            updateBusyAnimation();
+        }
+        watchTrayAvailability();
+        if (!instanceKey.empty())
+        {
+            startInstanceServer();
+        }
+    }
+
+    // Sets up this member's tray on the host's running QApplication, on the Qt thread.
+    void attach(QApplication *hostApp)
+    {
+        beginTransition();
+        app = hostApp;
+        quitConnection = QObject::connect(app, &QCoreApplication::aboutToQuit, [this]()
+                                          { pushEvent({Quit, nullptr}); });
+        applyAppIcon();
+        setupTray();
+        endTransition(true);
+    }
+
+    // Disconnects this member from the host's QApplication. Its Qt objects are only destroyed
+    // while the event loop runs, since this runs on the Qt thread only then.
+    void detach(bool destroy)
+    {
+        resolveNotification(NotificationResultDismissed);
+        if (!app)
+        {
+            return;
+        }
+        QObject::disconnect(quitConnection);
+        if (destroy)
+        {
+            delete notificationTimer;
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+            delete notificationSignals;
+#endif
+            delete instanceServer;
+            delete availabilityTimer;
+            delete animationTimer;
+            delete tray;
+            delete menu;
+            delete leftClickMenu;
+        }
+        notificationTimer = nullptr;
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+        notificationSignals = nullptr;
+        dbusNotificationId = 0;
+#endif
+        instanceServer = nullptr;
+        availabilityTimer = nullptr;
+        animationTimer = nullptr;
+        tray = nullptr;
+        menu = nullptr;
+        leftClickMenu = nullptr;
+        menuRefreshPending = false;
+        actions.clear();
+        submenus.clear();
+        progressBars.clear();
+        menuItems.clear();
+        app = nullptr;
+    }
+
+    // Turns the live menu back into pending items, with the current state of each item, so
+    // it is rebuilt when the tray is set up again.
+    void keepMenuItems()
+    {
+        for (MenuItemSpec &item : menuItems)
+        {
+            auto action = actions.find(item.path());
+            if (action != actions.end())
+            {
+                item.enabled = action->second->isEnabled();
+                item.visible = action->second->isVisible();
+                item.checkable = action->second->isCheckable();
+                item.checked = action->second->isChecked();
+                if (QCheckBox *box = tristateBox(action->second))
+                {
+                    const Qt::CheckState state = box->checkState();
+                    item.checkState = state == Qt::Checked            ? CheckStateChecked
+                                      : state == Qt::PartiallyChecked ? CheckStatePartiallyChecked
+                                                                      : CheckStateUnchecked;
+                }
+            }
+            auto bar = progressBars.find(item.path());
+            if (bar != progressBars.end())
+            {
+                item.progressValue = bar->second->value();
+            }
+        }
+        pending_menu_items.insert(pending_menu_items.begin(), menuItems.begin(), menuItems.end());
+        menuItems.clear();
+    }
+
+    // Called on the Qt thread before it sets up or tears down this wrapper's Qt objects. Calls
+    // from other threads are queued from now on, by runOnGuiThread, until endTransition, and
+    // those running directly on their threads are waited for, so none of them races the setup.
+    void beginTransition()
+    {
+        {
+            std::lock_guard<std::mutex> lock(loopMutex);
+            running = false;
+            transitioning = true;
+            loopThread = QThread::currentThread();
+        }
+        std::lock_guard<std::recursive_mutex> direct(directMutex);
+    }
+
+    // Ends a transition once the event loop is (or is no longer) running, then runs the calls
+    // queued meanwhile, on the Qt thread.
+    void endTransition(bool nowRunning)
+    {
+        std::deque<std::function<void()>> tasks;
+        {
+            std::lock_guard<std::mutex> lock(loopMutex);
+            transitioning = false;
+            running = nowRunning;
+            if (!nowRunning)
+            {
+                loopThread = nullptr;
+            }
+            tasks.swap(transitionTasks);
+        }
+        std::lock_guard<std::recursive_mutex> direct(directMutex);
+        for (const auto &task : tasks)
+        {
+            task();
+        }
+    }
+
+    // Reports the outcome of the notification with a callback, if there is one.
+    void resolveNotification(NotificationResult result)
+    {
+        NotificationCallback callback = notificationCallback;
+        if (!callback)
+        {
+            return;
+        }
+        notificationCallback = nullptr;
+        if (notificationTimer)
+        {
+            notificationTimer->stop();
+        }
+        callback(notificationUserData, result);
+    }
+
+    // Qt's own default for QSystemTrayIcon::showMessage.
+    static constexpr int defaultNotificationTimeoutMs = 10000;
+
+    // Listens for later launches of the app, which connect, send their arguments and
+    // disconnect (see acquireSingleInstance).
+    void startInstanceServer()
+    {
+        if (instanceServer)
+        {
+            return;
+        }
+        const QString name = QString::fromStdString(instanceKey);
+        // No primary answered, so a leftover socket file is from a crashed instance.
+        QLocalServer::removeServer(name);
+        instanceServer = new QLocalServer(app);
+        QObject::connect(instanceServer, &QLocalServer::newConnection, [this]()
+                         {
+            while (QLocalSocket *socket = instanceServer->nextPendingConnection()) {
+                auto received = std::make_shared<QByteArray>();
+                QObject::connect(socket, &QLocalSocket::readyRead, [socket, received]()
+                                 { received->append(socket->readAll()); });
+                QObject::connect(socket, &QLocalSocket::disconnected, [this, socket, received]()
+                                 {
+                    received->append(socket->readAll());
+                    AppEvent event{SecondInstanceLaunched, nullptr};
+                    event.args_str = strdup(received->toStdString().c_str());
+                    pushEvent(event);
+                    socket->deleteLater(); });
+            } });
+        if (!instanceServer->listen(name))
+        {
+            setError("Failed to listen for other instances: " + instanceServer->errorString().toStdString());
+        }
+    }
+
+    static constexpr int instanceConnectTimeoutMs = 500;
+
+    // Qt has no signal for the systray host going away and coming back (e.g. a panel restart),
+    // so availability is polled. A TrayReconnected event is queued when the host reappears, and
+    // VisibilityChanged events bracket the time the icon has no host to be shown in. This is the
+    // best available signal: Qt can't tell whether a panel auto-hides the icon.
+    void watchTrayAvailability()
+    {
+        trayAvailable = QSystemTrayIcon::isSystemTrayAvailable();
+        availabilityTimer = new QTimer(app);
+        QObject::connect(availabilityTimer, &QTimer::timeout, [this]()
+                         { checkTrayAvailability(); });
+        availabilityTimer->start(trayAvailabilityIntervalMs);
+    }
+
+    void checkTrayAvailability()
+    {
+        if (!availabilityTimer)
+        {
+            return;
+        }
+        bool available = QSystemTrayIcon::isSystemTrayAvailable();
+        if (available != trayAvailable)
+        {
+            AppEvent event{VisibilityChanged, nullptr};
+            event.visible = available;
+            pushEvent(event);
+        }
+        if (available && !trayAvailable)
+        {
+            pushEvent({TrayReconnected, nullptr});
+        }
+        trayAvailable = available;
+    }
+
+    static constexpr int trayAvailabilityIntervalMs = 1000;
+
+    // Polls for a systray host missing at startup, doubling the delay between checks, until it
+    // appears or trayWaitMs have passed. The icons then get TrayReconnected events right away
+    // rather than at the next availability check; without a host, the event loop exits with -1
+    // as if none had been available at startup.
+    void waitForTrayHost(int elapsedMs, int delayMs)
+    {
+        QTimer::singleShot(delayMs, app, [this, elapsedMs, delayMs]()
+                           {
+            const int elapsed = elapsedMs + delayMs;
+            if (QSystemTrayIcon::isSystemTrayAvailable()) {
+                checkTrayAvailability();
+                for (QtAppWrapper *member : trays) {
+                    member->checkTrayAvailability();
+                }
+            } else if (elapsed >= trayWaitMs) {
+                setError("No system tray became available within " + std::to_string(trayWaitMs) + " ms");
+                app->exit(-1);
+            } else {
+                waitForTrayHost(elapsed, std::min({delayMs * 2, trayWaitMaxDelayMs, trayWaitMs - elapsed}));
+            } });
+    }
+
+    static constexpr int trayWaitInitialDelayMs = 100;
+    static constexpr int trayWaitMaxDelayMs = 2000;
+
+    // Decodes image data, pre-scaled to the preferred icon size if one is set. Vector formats
+    // are rendered directly at that size.
+    QPixmap loadPixmap(const QByteArray &data, const std::string &format) const
+    {
+        QBuffer buffer;
+        buffer.setData(data);
+        QImageReader reader(&buffer, format.c_str());
+        if (preferredIconSize > 0 && reader.size().isValid())
+        {
+            reader.setScaledSize(reader.size().scaled(preferredIconSize, preferredIconSize, Qt::KeepAspectRatio));
+        }
+        return QPixmap::fromImage(reader.read());
+    }
+
+    // Removes a menu item and everything below it. Must run on the GUI thread.
+    bool removeMenuItemNow(const std::string &id)
+    {
+        bool found = false;
+        const std::string prefix = id + "/";
+        auto below = [&](const std::string &path) { return path == id || path.rfind(prefix, 0) == 0; };
+        for (auto *items : {&menuItems, &pending_menu_items})
+        {
+            found = found || std::any_of(items->begin(), items->end(), [&](const MenuItemSpec &item)
+                                         { return !item.separator && item.path() == id; });
+            items->erase(std::remove_if(items->begin(), items->end(), [&](const MenuItemSpec &item)
+                                        { return below(item.path()); }),
+                         items->end());
+        }
+        auto action = actions.find(id);
+        if (action == actions.end())
+        {
+            return found;
+        }
+        // Deleting a submenu deletes the actions in it; other actions are owned by their menu.
+        auto submenu = submenus.find(id);
+        if (submenu != submenus.end())
+        {
+            delete submenu->second;
+        }
+        else
+        {
+            delete action->second;
+        }
+        auto forget = [&](auto &map)
+        {
+            for (auto entry = map.begin(); entry != map.end();)
+            {
+                entry = below(entry->first) ? map.erase(entry) : std::next(entry);
+            }
+        };
+        forget(actions);
+        forget(submenus);
+        forget(progressBars);
+        scheduleMenuRefresh();
+        return found;
+    }
+
+    // Restores the saved check state of a persisted item, then saves it whenever it changes.
+    // Actions only exist while the QApplication does, which provides the settings' names.
+    void persistCheckState(QAction *action, const std::string &id)
+    {
+        const QString key = QStringLiteral("checkable/") + QString::fromStdString(id);
+        QSettings settings;
+        action->setCheckable(true);
+        if (settings.contains(key))
+        {
+            action->setChecked(settings.value(key).toBool());
+        }
+        QObject::connect(action, &QAction::toggled, [key](bool checked)
+                         { QSettings().setValue(key, checked); });
+    }
+
+    // Submenus are separate popup windows, which don't inherit the font of their parent menu.
+    void applyMenuFont()
+    {
+        if (!hasMenuFont)
+        {
+            return;
+        }
+        if (menu)
+        {
+            menu->setFont(menuFont);
+        }
+        for (auto &submenu : submenus)
+        {
+            submenu.second->setFont(menuFont);
+        }
+        // An action's font replaces the menu's, so emphasized items are rebuilt on the new one.
+        for (auto &action : actions)
+        {
+            const QFont font = action.second->font();
+            if (font.bold() || font.italic())
+            {
+                action.second->setFont(emphasizedFont(font.bold(), font.italic()));
+            }
+        }
+    }
+
+    static QAction::MenuRole qtMenuRole(MenuRole role)
+    {
+        switch (role)
+        {
+        case MenuRoleNone:
+            return QAction::NoRole;
+        case MenuRoleAbout:
+            return QAction::AboutRole;
+        case MenuRolePreferences:
+            return QAction::PreferencesRole;
+        case MenuRoleQuit:
+            return QAction::QuitRole;
+        default:
+            return QAction::TextHeuristicRole;
+        }
+    }
+
+    static Qt::ShortcutContext qtShortcutContext(ShortcutContext context)
+    {
+        switch (context)
+        {
+        case ShortcutContextWidget:
+            return Qt::WidgetShortcut;
+        case ShortcutContextApplication:
+            return Qt::ApplicationShortcut;
+        default:
+            return Qt::WindowShortcut;
+        }
+    }
+
+    // Qt 6.5 reports the platform's color scheme; otherwise, or if the platform doesn't say, a
+    // palette with light text on a darker window background counts as dark.
+    static bool isDarkColorScheme()
+    {
+#if QT_VERSION >= QT_VERSION_CHECK(6, 5, 0)
+        const Qt::ColorScheme scheme = QGuiApplication::styleHints()->colorScheme();
+        if (scheme != Qt::ColorScheme::Unknown)
+        {
+            return scheme == Qt::ColorScheme::Dark;
+        }
+#endif
+        const QPalette palette = QGuiApplication::palette();
+        return palette.color(QPalette::WindowText).lightness() > palette.color(QPalette::Window).lightness();
+    }
+
+    static Qt::CheckState qtCheckState(CheckState state)
+    {
+        switch (state)
+        {
+        case CheckStatePartiallyChecked:
+            return Qt::PartiallyChecked;
+        case CheckStateChecked:
+            return Qt::Checked;
+        default:
+            return Qt::Unchecked;
+        }
+    }
+
+    // The check box of an item added with the tristate option, or null for other items.
+    static QCheckBox *tristateBox(QAction *action)
+    {
+        auto *widgetAction = qobject_cast<QWidgetAction *>(action);
+        return widgetAction ? qobject_cast<QCheckBox *>(widgetAction->defaultWidget()) : nullptr;
+    }
+
+    // The menu font with bold and/or italics applied, for emphasized items.
+    QFont emphasizedFont(bool bold, bool italic) const
+    {
+        QFont font = hasMenuFont ? menuFont : QApplication::font("QMenu");
+        font.setBold(bold);
+        font.setItalic(italic);
+        return font;
+    }
+
+    // QSystemTrayIcon isn't a widget and has no accessibility API of its own. Screen readers
+    // announce the tray icon by its tooltip (the notification area on Windows, the
+    // StatusNotifierItem title on Linux), so the name is applied there and to the context menu.
+    void applyAccessibleName()
+    {
+        if (accessibleName.empty())
+        {
+            return;
+        }
+        const QString name = QString::fromStdString(accessibleName);
+        if (tray)
+        {
+            tray->setToolTip(name);
+        }
+        if (menu)
+        {
+            menu->setAccessibleName(name);
+        }
+    }
+
+    // Rebuilds the regular icon from the icon data and applies it to the application and tray.
+    // The application (window and taskbar) icon falls back to the tray icon if it isn't set.
+    void applyAppIcon()
+    {
+        if (themedIcon)
+        {
+            iconVariants = isDarkColorScheme() ? darkIconVariants : lightIconVariants;
+        }
+        appIcon = QIcon();
+        for (const auto &variant : iconVariants)
+        {
+            // QIcon picks the pixmap closest to the size it is drawn at in device pixels, so each
+            // variant is decoded at its nominal size rather than the preferred icon size.
+            QBuffer buffer;
+            buffer.setData(variant.second);
+            QImageReader reader(&buffer, iconFormat.c_str());
+            if (variant.first > 0 && reader.size().isValid())
+            {
+                reader.setScaledSize(reader.size().scaled(variant.first, variant.first, Qt::KeepAspectRatio));
+            }
+            QPixmap pixmap = QPixmap::fromImage(reader.read());
+            if (!pixmap.isNull())
+            {
+                appIcon.addPixmap(pixmap);
+            }
+            else
+            {
+                setError("Failed to decode the " + std::to_string(variant.first) + "px tray icon as " + iconFormat);
+            }
+        }
+        if (!iconData.isEmpty())
+        {
+            QPixmap pixmap = loadPixmap(iconData, iconFormat);
+            if (!pixmap.isNull())
+            {
+                appIcon = QIcon(pixmap);
+            }
+            else
+            {
+                setError("Failed to decode the tray icon as " + iconFormat);
+            }
+        }
+        if (!iconImage.isNull())
+        {
+            QImage image = iconImage;
+            if (preferredIconSize > 0)
+            {
+                image = image.scaled(preferredIconSize, preferredIconSize, Qt::KeepAspectRatio, Qt::SmoothTransformation);
+            }
+            appIcon = QIcon(QPixmap::fromImage(image));
+        }
+        if (appIcon.isNull() && !fallbackIconData.isEmpty())
+        {
+            QPixmap pixmap = loadPixmap(fallbackIconData, fallbackIconFormat);
+            if (!pixmap.isNull())
+            {
+                appIcon = QIcon(pixmap);
+            }
+        }
+        if (!windowIconData.isEmpty())
+        {
+            // Windows pick the size they need from the icon, so the tray size hint isn't applied.
+            QPixmap pixmap;
+            pixmap.loadFromData(windowIconData, windowIconFormat.c_str());
+            if (!pixmap.isNull())
+            {
+                app->setWindowIcon(QIcon(pixmap));
+            }
+            else
+            {
+                setError("Failed to decode the application icon as " + windowIconFormat);
+            }
+        }
+        else if (!appIcon.isNull())
+        {
+            app->setWindowIcon(appIcon);
+        }
+        if (tray && animationFrames.empty())
+        {
+            tray->setIcon(displayedTrayIcon());
+        }
+    }
+
+    // The icon shown in the tray: appIcon, marked as a mask for template icons. Windows keep
+    // the unmarked appIcon.
+    QIcon displayedTrayIcon() const
+    {
+        QIcon icon = appIcon;
+        icon.setIsMask(iconIsTemplate);
+        return icon;
+    }
+
+    // Starts or stops the busy spinner to match `busy`, using the custom frames if any decode.
+    void updateBusyAnimation()
+    {
+        if (!tray || attention)
+        {
+            // A running attention blink takes precedence; finishAnimation resumes the spinner.
+            return;
+        }
+        if (!busy)
+        {
+            stopAnimation();
+            return;
+        }
+        std::vector<QIcon> frames;
+        for (const QByteArray &data : busyFrameData)
+        {
+            QPixmap pixmap = loadPixmap(data, busyFrameFormat);
+            if (!pixmap.isNull())
+            {
+                frames.emplace_back(pixmap);
+            }
+            else
+            {
+                setError("Failed to decode a busy animation frame as " + busyFrameFormat);
+            }
+        }
+        startAnimation(frames.empty() ? makeSpinnerFrames() : std::move(frames), true, animationIntervalMs);
+    }
+
+    // Blinks the icon `count` times, or until the tray is activated if `count` is negative.
+    void startAttentionBlink(int count)
+    {
+        if (!tray)
+        {
+            return;
+        }
+        if (count == 0)
+        {
+            if (attention)
+            {
+                finishAnimation();
+            }
+            return;
+        }
+        QPixmap blank(appIcon.actualSize(QSize(64, 64)));
+        blank.fill(Qt::transparent);
+        std::vector<QIcon> frames;
+        for (int blink = 0; blink < (count < 0 ? 1 : count); ++blink)
+        {
+            frames.emplace_back(blank);
+            frames.push_back(displayedTrayIcon());
+        }
+        attention = true;
+        startAnimation(std::move(frames), count < 0, attentionIntervalMs);
+    }
+
+    // Cycles the tray icon through `frames`. A looping animation runs until stopped; otherwise
+    // finishAnimation is called after the last frame.
+    void startAnimation(std::vector<QIcon> frames, bool loop, int intervalMs)
+    {
+        if (!animationTimer)
+        {
+            animationTimer = new QTimer(app);
+            QObject::connect(animationTimer, &QTimer::timeout, [this]()
+                             { advanceAnimation(); });
+        }
+        animationFrames = std::move(frames);
+        animationFrame = 0;
+        animationLoops = loop;
+        advanceAnimation();
+        animationTimer->start(intervalMs);
+    }
+
+    void advanceAnimation()
+    {
+        if (animationFrame >= animationFrames.size())
+        {
+            if (!animationLoops || animationFrames.empty())
+            {
+                finishAnimation();
+                return;
+            }
+            animationFrame = 0;
+        }
+        tray->setIcon(animationFrames[animationFrame++]);
+    }
+
+    // Ends the current animation, resuming the busy spinner if it is still wanted.
+    void finishAnimation()
+    {
+        attention = false;
+        if (busy)
+        {
+            updateBusyAnimation();
+        }
+        else
+        {
+            stopAnimation();
+        }
+    }
+
+    // Stops any running animation and restores the regular icon.
+    void stopAnimation()
+    {
+        if (animationTimer)
+        {
+            animationTimer->stop();
+        }
+        animationFrames.clear();
+        tray->setIcon(displayedTrayIcon());
+    }
+
+    static constexpr int animationIntervalMs = 100;
+    static constexpr int attentionIntervalMs = 500;
+
+    // Queues a copy of an event built outside the backend, which owns none of its strings.
+    void injectEvent(AppEvent event)
+    {
+        event.menu_id_str = event.menu_id_str ? strdup(event.menu_id_str) : nullptr;
+        event.args_str = event.args_str ? strdup(event.args_str) : nullptr;
+        pushEvent(event);
+    }
+
+    // Queues an event for pollEvent, applying the configured capacity and overflow policy, or
+    // hands it to the event callback if one is set.
+    void pushEvent(AppEvent event)
+    {
+        if (!(eventMask & (1u << event.type_)))
+        {
+            dropEvent(event);
+            return;
+        }
+        if (eventsPaused && isUserInput(event.type_))
+        {
+            if (pausePolicy == PauseBuffer)
+            {
+                pausedEvents.push_back(event);
+            }
+            else
+            {
+                dropEvent(event);
+            }
+            return;
+        }
+        if (eventCallback)
+        {
+            eventCallback(eventUserData, event);
+            return;
+        }
+        std::lock_guard<std::mutex> lock(queueMutex);
+        if (overflowPolicy != Unbounded && event_queue.size() >= queueCapacity)
+        {
+            if (overflowPolicy == DropNewest || queueCapacity == 0)
+            {
+                dropEvent(event);
+                return;
+            }
+            while (event_queue.size() >= queueCapacity)
+            {
+                dropEvent(event_queue.front());
+                event_queue.pop_front();
+            }
+        }
+        event_queue.push_back(event);
+        queuedEvents.store(event_queue.size(), std::memory_order_release);
+    }
+
+    // Records a failure for last_error. Callable from any thread.
+    void setError(const std::string &message)
+    {
+        std::lock_guard<std::mutex> lock(errorMutex);
+        lastError = message;
+    }
+
+    // Whether the event is a reaction to the user, held back while events are paused.
+    static bool isUserInput(AppEventType type)
+    {
+        return type == TrayClicked || type == TrayDoubleClicked || type == TrayButtonClicked || type == MenuItemHovered ||
+               type == MenuItemClicked || type == NotificationClicked || type == NotificationActionInvoked;
+    }
+
+    // Releases the data owned by an event that will never be polled.
+    static void dropEvent(const AppEvent &event)
+    {
+        free((void *)event.menu_id_str);
+        free((void *)event.args_str);
+    }
+
+    // Posts `f` to the Qt thread. Does nothing unless the event loop is running.
+    template <typename F>
+    void postToGuiThread(F &&f)
+    {
+        std::lock_guard<std::mutex> lock(loopMutex);
+        if (running)
+        {
+            QMetaObject::invokeMethod(app, std::forward<F>(f), Qt::QueuedConnection);
+        }
+    }
+
+    // Runs `f` on the Qt thread and waits for it to finish. When the event loop isn't running,
+    // `f` runs directly on the calling thread instead, one call at a time. While the Qt thread
+    // sets up or tears down the tray (see beginTransition), `f` is queued to run on it right
+    // after, so calls made around start and stop apply in order instead of racing it.
+    template <typename F>
+    void runOnGuiThread(F &&f)
+    {
+        std::unique_lock<std::mutex> lock(loopMutex);
+        if (loopThread && QThread::currentThread() == loopThread)
+        {
+            lock.unlock();
+            f();
+            return;
+        }
+        if (!running && !transitioning)
+        {
+            std::lock_guard<std::recursive_mutex> direct(directMutex);
+            lock.unlock();
+            f();
+            return;
+        }
+        std::promise<void> done;
+        std::future<void> finished = done.get_future();
+        auto task = [&f, &done]()
+        {
+            f();
+            done.set_value();
+        };
+        if (transitioning)
+        {
+            transitionTasks.push_back(task);
+        }
+        else
+        {
+            QMetaObject::invokeMethod(app, task, Qt::QueuedConnection);
+        }
+        lock.unlock();
+        finished.wait();
+    }
+
+    std::string appId;
+    std::string organizationName;
+    QByteArray iconData;
+    std::string iconFormat;
+    std::vector<std::pair<int, QByteArray>> iconVariants; // Replaces iconData when not empty
+    QImage iconImage; // Replaces iconData and iconVariants when not null
+    QByteArray windowIconData; // Empty uses the tray icon
+    std::string windowIconFormat;
+    QByteArray fallbackIconData; // Used when the tray icon is unset or can't be decoded
+    std::string fallbackIconFormat;
+    QIcon appIcon;
+    bool iconIsTemplate = false; // Whether the tray shows appIcon as a template (mask) image
+    bool themedIcon = false; // Whether iconVariants is picked from the sets below
+    std::vector<std::pair<int, QByteArray>> lightIconVariants;
+    std::vector<std::pair<int, QByteArray>> darkIconVariants;
+    int preferredIconSize = 0; // 0 keeps the icon's own size
+    bool busy = false;
+    std::vector<QByteArray> busyFrameData;
+    std::string busyFrameFormat;
+    QTimer *animationTimer = nullptr;
+    QTimer *availabilityTimer = nullptr;
+    std::vector<QIcon> animationFrames;
+    size_t animationFrame = 0;
+    bool animationLoops = false;
+    bool attention = false;
+    unsigned int lastNotificationId = 0;
+    NotificationCallback notificationCallback = nullptr;
+    void *notificationUserData = nullptr;
+    QTimer *notificationTimer = nullptr;
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+    NotificationSignals *notificationSignals = nullptr;
+    unsigned int dbusNotificationId = 0; // The notification service's id for the latest notification, or 0
+#endif
+    bool shouldInitTray = false;
+    bool trayAvailable = false;
+    std::string styleName;
+    std::string displayName; // Empty leaves Qt's default, the application name
+    std::string accessibleName;
+    QFont menuFont;
+    bool hasMenuFont = false;
+    bool tearOffEnabled = false;
+    bool useDarkPalette = false;
+    bool iconShown = true;
+    // Indexed by MouseButton.
+    ButtonBehavior buttonBehaviors[3] = {ButtonEmitClick, ButtonOpenMenu, ButtonIgnore};
+    std::string buttonItems[3];
+    bool highDpiDisabledByEnv = false; // Whether setHighDpi set QT_ENABLE_HIGHDPI_SCALING
+    bool quitOnInterrupt = false;
+    int trayWaitMs = 0; // How long run() waits for a missing systray host, 0 to fail right away
+    std::deque<AppEvent> event_queue;
+    std::mutex queueMutex;
+    // Mirrors event_queue.size() so that idle polls can skip the lock.
+    std::atomic<size_t> queuedEvents{0};
+    size_t queueCapacity = 0;
+    OverflowPolicy overflowPolicy = Unbounded;
+    EventCallback eventCallback = nullptr;
+    // Only used on the Qt thread, like pushEvent.
+    unsigned int eventMask = ~0u;
+    bool eventsPaused = false;
+    PausePolicy pausePolicy = PauseDiscard;
+    std::deque<AppEvent> pausedEvents;
+    std::mutex errorMutex;
+    std::string lastError;
+    void *eventUserData = nullptr;
+    std::vector<MenuItemSpec> pending_menu_items;
+    std::vector<MenuItemSpec> menuItems; // The items of the live menu, in the order they were added
+    std::vector<MenuItemSpec> replacementMenuItems;
+    bool replacingMenu = false;
+    bool menuRefreshPending = false;
+    std::string activeItem; // Highlighted when the context menu opens, if not empty
+    QMenu *leftClickMenu = nullptr;
+    std::vector<MenuItemSpec> leftClickMenuItems;
+    std::vector<MenuItemSpec> leftClickMenuReplacement;
+    bool collectingLeftClickMenu = false;
+    std::unordered_map<std::string, QAction *> actions;
+    std::unordered_map<std::string, QMenu *> submenus;
+    std::unordered_map<std::string, QProgressBar *> progressBars; // Owned by their actions
+    std::unordered_set<std::string> persistedChecks; // Ids of the items whose check state is saved
+
+    QMenu *menu = nullptr;
+    QSystemTrayIcon *tray = nullptr;
+    QApplication *app = nullptr;
+    std::mutex loopMutex;
+    bool running = false;
+    bool transitioning = false; // The Qt thread is setting up or tearing down the tray
+    QThread *loopThread = nullptr; // The thread running the event loop, while there is one
+    std::deque<std::function<void()>> transitionTasks; // Calls queued during a transition
+    std::recursive_mutex directMutex; // Held by calls running off the Qt thread while stopped
+    // Member trays sharing this wrapper's QApplication (see addTray).
+    std::vector<QtAppWrapper *> trays;
+    QMetaObject::Connection quitConnection;
+    std::string instanceKey;
+    QLocalServer *instanceServer = nullptr;
+};
+
+extern "C"
+{
+
+    QtAppHandle *create_qt_app() { return new QtAppHandle{new QtAppWrapper()}; }
+    void set_app_id(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setAppId(id);
+        }
+    }
+    void set_organization_name(QtAppHandle *handle, const char *name)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setOrganizationName(name);
+        }
+    }
+    void set_app_icon_from_data(QtAppHandle *handle, const unsigned char *data, size_t size, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setAppIcon(data, size, format);
+        }
+    }
+    void set_tray_icon_from_data(QtAppHandle *handle, const unsigned char *data, size_t size, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setTrayIcon(data, size, format);
+        }
+    }
+    void set_tray_icon_template(QtAppHandle *handle, const unsigned char *data, size_t size, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setTrayIcon(data, size, format, true);
+        }
+    }
+    void set_application_icon_from_data(QtAppHandle *handle, const unsigned char *data, size_t size,
+                                        const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setApplicationIcon(data, size, format);
+        }
+    }
+    void set_fallback_icon_from_data(QtAppHandle *handle, const unsigned char *data, size_t size,
+                                     const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setFallbackIcon(data, size, format);
+        }
+    }
+    bool init_tray(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->initTray();
+        }
+        return false;
+    }
+    bool init_app(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->initApp();
+        }
+        return false;
+    }
+    void add_app_tray(QtAppHandle *handle, QtAppHandle *tray)
+    {
+        if (handle && handle->impl && tray && tray->impl)
+        {
+            handle->impl->addTray(tray->impl);
+        }
+    }
+    void remove_app_tray(QtAppHandle *handle, QtAppHandle *tray)
+    {
+        if (handle && handle->impl && tray && tray->impl)
+        {
+            handle->impl->removeTray(tray->impl);
+        }
+    }
+    int run_qt_app(QtAppHandle *handle, int argc, char *argv[])
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->run(argc, argv);
+        }
+        return -1;
+    }
+    AppEvent poll_event(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->pollEvent();
+        }
+        return {None, nullptr};
+    } // Use the new enum value directly
+    void request_quit_qt_app_safe(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->requestQuitSafe();
+        }
+    }
+    void cleanup_qt_app(QtAppHandle *handle)
+    {
+        if (handle)
+        {
+            delete handle->impl;
+            delete handle;
+        }
+    }
+    void add_tray_menu_item(QtAppHandle *handle, const char *text, const char *id, const MenuItemOptions *options)
+    {
+        if (handle && handle->impl)
+        {
+            MenuItemSpec item{text, id};
+            if (options)
+            {
+                item.enabled = options->enabled;
+                item.visible = options->visible;
+                item.url = options->url ? options->url : "";
+                item.parent = options->parent ? options->parent : "";
+                item.submenu = options->submenu;
+                item.accessibleDescription = options->accessible_description ? options->accessible_description : "";
+                item.checkable = options->checkable;
+                item.checked = options->checked;
+                item.separator = options->separator;
+                item.bold = options->bold;
+                item.italic = options->italic;
+                item.progress = options->progress;
+                item.role = options->role;
+                item.shortcut = options->shortcut ? options->shortcut : "";
+                item.shortcutContext = options->shortcut_context;
+                item.tristate = options->tristate;
+                item.checkState = options->checked ? CheckStateChecked : CheckStateUnchecked;
+                if (options->icon_data && options->icon_size > 0)
+                {
+                    item.iconData = QByteArray(reinterpret_cast<const char *>(options->icon_data), options->icon_size);
+                    item.iconFormat = options->icon_format ? options->icon_format : "";
+                }
+            }
+            handle->impl->addTrayMenuItem(item);
+        }
+    }
+    void begin_tray_menu_replace(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->beginMenuReplace();
+        }
+    }
+    void finish_tray_menu_replace(QtAppHandle *handle, bool apply)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->finishMenuReplace(apply);
+        }
+    }
+    void begin_left_click_menu(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->beginLeftClickMenu();
+        }
+    }
+    void finish_left_click_menu(QtAppHandle *handle, bool apply)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->finishLeftClickMenu(apply);
         }
-        return -1;
     }
-    AppEvent poll_event(QtAppHandle *handle)
+    void popup_tray_menu(QtAppHandle *handle, int x, int y)
     {
         if (handle && handle->impl)
         {
-            return handle->impl->pollEvent();
+            handle->impl->popupMenu(QPoint(x, y));
         }
-        return {None, nullptr};
-    } // Use the new enum value directly
-    void request_quit_qt_app_safe(QtAppHandle *handle)
+    }
+    void popup_tray_menu_at_cursor(QtAppHandle *handle)
     {
         if (handle && handle->impl)
         {
-            handle->impl->requestQuitSafe();
+            handle->impl->popupMenuAtCursor();
         }
     }
-    void cleanup_qt_app(QtAppHandle *handle)
+    void flush_qt_app(QtAppHandle *handle)
     {
-        if (handle)
+        if (handle && handle->impl)
         {
-            delete handle->impl;
-            delete handle;
+            handle->impl->flush();
+        }
+    }
+    char *get_active_menu_item(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->activeMenuItem();
+        }
+        return nullptr;
+    }
+    bool set_active_menu_item(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl && id)
+        {
+            return handle->impl->setActiveMenuItem(id);
+        }
+        return false;
+    }
+    bool is_menu_open(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->isMenuOpen();
+        }
+        return false;
+    }
+    void set_event_queue_capacity(QtAppHandle *handle, size_t capacity, OverflowPolicy policy)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setEventQueueCapacity(capacity, policy);
+        }
+    }
+    void inject_event(QtAppHandle *handle, const AppEvent *event)
+    {
+        if (handle && handle->impl && event)
+        {
+            handle->impl->injectEvent(*event);
+        }
+    }
+    void set_event_callback(QtAppHandle *handle, EventCallback callback, void *user_data)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setEventCallback(callback, user_data);
+        }
+    }
+    void set_display_name(QtAppHandle *handle, const char *name)
+    {
+        if (handle && handle->impl && name)
+        {
+            handle->impl->setDisplayName(name);
+        }
+    }
+    void set_quit_on_interrupt(QtAppHandle *handle, bool enabled)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setQuitOnInterrupt(enabled);
+        }
+    }
+    void set_tray_wait_timeout(QtAppHandle *handle, int timeout_ms)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setTrayWaitTimeout(timeout_ms);
+        }
+    }
+    void set_high_dpi(QtAppHandle *handle, bool enabled)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setHighDpi(enabled);
+        }
+    }
+    char *last_error(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->lastErrorCopy();
+        }
+        return nullptr;
+    }
+    void refresh_tray(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->refresh();
+        }
+    }
+    void set_event_mask(QtAppHandle *handle, unsigned int mask)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setEventMask(mask);
+        }
+    }
+    void set_events_paused(QtAppHandle *handle, bool paused)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setEventsPaused(paused);
+        }
+    }
+    void set_pause_policy(QtAppHandle *handle, PausePolicy policy)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setPausePolicy(policy);
+        }
+    }
+    void set_app_style(QtAppHandle *handle, const char *style)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setStyle(style);
+        }
+    }
+    void set_dark_palette(QtAppHandle *handle, bool enabled)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setDarkPalette(enabled);
+        }
+    }
+    void set_menu_items_enabled(QtAppHandle *handle, const char *const *ids, const bool *enabled, size_t count, bool *found)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setMenuItemsEnabled(ids, enabled, count, found);
+        }
+    }
+    bool set_menu_item_visible(QtAppHandle *handle, const char *id, bool visible)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->setMenuItemVisible(id, visible);
+        }
+        return false;
+    }
+    bool set_menu_item_text(QtAppHandle *handle, const char *id, const char *text)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->setMenuItemText(id, text);
+        }
+        return false;
+    }
+    bool has_menu_item(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->hasMenuItem(id);
+        }
+        return false;
+    }
+    bool remove_menu_item(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->removeMenuItem(id);
+        }
+        return false;
+    }
+    bool move_menu_item(QtAppHandle *handle, const char *id, size_t index)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->moveMenuItem(id, index);
+        }
+        return false;
+    }
+    size_t remove_menu_items_by_prefix(QtAppHandle *handle, const char *prefix)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->removeMenuItemsByPrefix(prefix);
+        }
+        return 0;
+    }
+    int toggle_menu_item_checked(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->toggleMenuItemChecked(id);
+        }
+        return -1;
+    }
+    bool set_menu_item_check_state(QtAppHandle *handle, const char *id, CheckState state)
+    {
+        if (handle && handle->impl && id)
+        {
+            return handle->impl->setMenuItemCheckState(id, state);
+        }
+        return false;
+    }
+    void persist_menu_item_checked(QtAppHandle *handle, const char *id)
+    {
+        if (handle && handle->impl && id)
+        {
+            handle->impl->persistCheckable(id);
+        }
+    }
+    bool set_menu_item_progress(QtAppHandle *handle, const char *id, int value)
+    {
+        if (handle && handle->impl && id)
+        {
+            return handle->impl->setMenuItemProgress(id, std::clamp(value, 0, 100));
+        }
+        return false;
+    }
+    void set_busy(QtAppHandle *handle, bool busy)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setBusy(busy);
+        }
+    }
+    void set_busy_frames(QtAppHandle *handle, const unsigned char *const *frames, const size_t *sizes, size_t count, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            std::vector<QByteArray> data;
+            for (size_t i = 0; i < count; ++i)
+            {
+                data.emplace_back(reinterpret_cast<const char *>(frames[i]), sizes[i]);
+            }
+            handle->impl->setBusyFrames(std::move(data), format);
+        }
+    }
+    void set_tray_icon_variants(QtAppHandle *handle, const unsigned char *const *variants, const size_t *sizes,
+                                const unsigned int *pixel_sizes, size_t count, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            std::vector<std::pair<int, QByteArray>> data;
+            for (size_t i = 0; i < count; ++i)
+            {
+                data.emplace_back(static_cast<int>(pixel_sizes[i]),
+                                  QByteArray(reinterpret_cast<const char *>(variants[i]), sizes[i]));
+            }
+            handle->impl->setTrayIconVariants(std::move(data), format);
+        }
+    }
+    void set_tray_icon_rgba(QtAppHandle *handle, const unsigned char *pixels, unsigned int width, unsigned int height)
+    {
+        if (handle && handle->impl && pixels)
+        {
+            // QImage only wraps the pixels, so it is copied before the caller's buffer goes away.
+            QImage image(pixels, static_cast<int>(width), static_cast<int>(height), static_cast<qsizetype>(width) * 4,
+                         QImage::Format_RGBA8888);
+            handle->impl->setTrayIconImage(image.copy());
+        }
+    }
+    void set_tray_icon_themed(QtAppHandle *handle, const unsigned char *const *variants, const size_t *sizes,
+                              const unsigned int *pixel_sizes, const bool *dark, size_t count, const char *format)
+    {
+        if (handle && handle->impl)
+        {
+            std::vector<std::pair<int, QByteArray>> light_data;
+            std::vector<std::pair<int, QByteArray>> dark_data;
+            for (size_t i = 0; i < count; ++i)
+            {
+                (dark[i] ? dark_data : light_data)
+                    .emplace_back(static_cast<int>(pixel_sizes[i]),
+                                  QByteArray(reinterpret_cast<const char *>(variants[i]), sizes[i]));
+            }
+            handle->impl->setTrayIconThemed(std::move(light_data), std::move(dark_data), format);
+        }
+    }
+    void request_attention(QtAppHandle *handle, int count)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->requestAttention(count);
+        }
+    }
+    bool acquire_single_instance(QtAppHandle *handle, const char *key, const char *args)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->acquireSingleInstance(key, args);
+        }
+        return false;
+    }
+    unsigned int show_notification(QtAppHandle *handle, const NotificationSpec *spec, NotificationCallback callback,
+                                   void *user_data)
+    {
+        if (handle && handle->impl && spec)
+        {
+            // Only the notification service shows the buttons; the balloon has none.
+            std::vector<std::pair<std::string, std::string>> actions;
+            for (size_t i = 0; spec->actions && i < spec->action_count; ++i)
+            {
+                actions.emplace_back(spec->actions[i].id, spec->actions[i].label);
+            }
+            const QByteArray iconData = spec->icon_data ? QByteArray(reinterpret_cast<const char *>(spec->icon_data), spec->icon_size) : QByteArray();
+            return handle->impl->showNotification(spec->title, spec->body, spec->timeout_ms, spec->icon, iconData,
+                                                  spec->icon_format ? spec->icon_format : "", actions, callback, user_data);
+        }
+        return 0;
+    }
+    void *get_tray_icon_ptr(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->trayIcon();
+        }
+        return nullptr;
+    }
+    void set_menu_font(QtAppHandle *handle, const char *family, double point_size)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setMenuFont(family ? family : "", point_size);
+        }
+    }
+    void set_tear_off_enabled(QtAppHandle *handle, bool enabled)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setTearOffEnabled(enabled);
+        }
+    }
+    void set_button_behavior(QtAppHandle *handle, MouseButton button, ButtonBehavior behavior, const char *item_id)
+    {
+        if (handle && handle->impl && button >= MouseButtonLeft && button <= MouseButtonMiddle)
+        {
+            handle->impl->setButtonBehavior(button, behavior, item_id ? item_id : "");
+        }
+    }
+    void set_tray_icon_shown(QtAppHandle *handle, bool shown)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setIconShown(shown);
+        }
+    }
+    void set_accessible_name(QtAppHandle *handle, const char *name)
+    {
+        if (handle && handle->impl)
+        {
+            handle->impl->setAccessibleName(name);
         }
     }
-    void add_tray_menu_item(QtAppHandle *handle, const char *text, const char *id)
+    void set_preferred_icon_size(QtAppHandle *handle, int size)
     {
         if (handle && handle->impl)
         {
-            handle->impl->addTrayMenuItem(text, id);
+            handle->impl->setPreferredIconSize(size);
         }
     }
+    bool is_system_tray_available(QtAppHandle *handle)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->isSystemTrayAvailable();
+        }
+        return false;
+    }
+    size_t get_screens(QtAppHandle *handle, ScreenInfo *screens, size_t capacity)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->getScreens(screens, capacity);
+        }
+        return 0;
+    }
+    bool open_path(QtAppHandle *handle, const char *path)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->openPath(path);
+        }
+        return false;
+    }
+    int confirm_dialog(QtAppHandle *handle, const char *title, const char *text)
+    {
+        if (handle && handle->impl)
+        {
+            return handle->impl->confirm(title, text);
+        }
+        return -1;
+    }
+    char *get_platform_name(void)
+    {
+        if (qobject_cast<QGuiApplication *>(QCoreApplication::instance()))
+        {
+            return strdup(QGuiApplication::platformName().toStdString().c_str());
+        }
+        return strdup(requestedPlatforms().front().c_str());
+    }
+    char *read_setting(const char *organization, const char *application, const char *key)
+    {
+        QSettings settings(QString::fromUtf8(organization), QString::fromUtf8(application));
+        const QString name = QString::fromUtf8(key);
+        if (!settings.contains(name))
+        {
+            return nullptr;
+        }
+        return strdup(settings.value(name).toString().toStdString().c_str());
+    }
+    bool write_setting(const char *organization, const char *application, const char *key, const char *value)
+    {
+        QSettings settings(QString::fromUtf8(organization), QString::fromUtf8(application));
+        settings.setValue(QString::fromUtf8(key), QString::fromUtf8(value));
+        settings.sync();
+        return settings.status() == QSettings::NoError;
+    }
     void free_char_ptr(const char *ptr) { free((void *)ptr); }
 
-} // extern "C"
\ No newline at end of file
+} // extern "C"
+
+#ifdef QT6_BIND_DBUS_NOTIFICATIONS
+#include "lib.moc"
+#endif
\ No newline at end of file
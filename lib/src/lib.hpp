@@ -1,6 +1,7 @@
 #pragma once
 
 #include <stddef.h> // For size_t
+#include <stdbool.h> // For bool
 
 #ifdef __cplusplus
 extern "C" {
@@ -15,15 +16,164 @@ typedef enum {
     None,
     TrayClicked,
     TrayDoubleClicked,
-    MenuItemClicked
+    MenuItemClicked,
+    Quit,
+    TrayReconnected,
+    VisibilityChanged,
+    NotificationClicked,
+    SecondInstanceLaunched,
+    MenuAboutToShow,
+    MenuClosed,
+    NotificationActionInvoked,
+    TrayButtonClicked,
+    MenuItemHovered,
+    ColorSchemeChanged
 } AppEventType;
 
+// What happens to new events when the event queue is full
+typedef enum {
+    Unbounded,  // The queue grows without limit
+    DropOldest, // The oldest queued event is discarded to make room
+    DropNewest  // The incoming event is discarded
+} OverflowPolicy;
+
+// What happens to user input events (clicks on the icon, menu items and notifications) while
+// event delivery is paused
+typedef enum {
+    PauseDiscard, // They are dropped
+    PauseBuffer   // They are held back and delivered in order on resume
+} PausePolicy;
+
+// A mouse button clicked on the tray icon
+typedef enum {
+    MouseButtonLeft,
+    MouseButtonRight,
+    MouseButtonMiddle
+} MouseButton;
+
+// What clicking the tray icon with a mouse button does
+typedef enum {
+    ButtonEmitClick,   // TrayClicked for the left button, TrayButtonClicked for the others
+    ButtonOpenMenu,    // Opens the context menu at the cursor
+    ButtonTriggerItem, // Triggers a menu item, as if it was clicked
+    ButtonIgnore       // Nothing
+} ButtonBehavior;
+
+// Where a menu item is placed in the native application menu on macOS; ignored elsewhere
+typedef enum {
+    MenuRoleTextHeuristic, // Placed by matching the text, e.g. "About" or "Quit"
+    MenuRoleNone,          // Never moved
+    MenuRoleAbout,         // The "About" item of the application menu
+    MenuRolePreferences,   // The "Preferences..." item of the application menu
+    MenuRoleQuit           // The "Quit" item of the application menu
+} MenuRole;
+
+// Where a menu item's keyboard shortcut is active
+typedef enum {
+    ShortcutContextWindow,     // While the window holding the item is active (Qt's default)
+    ShortcutContextWidget,     // Only while the menu holding the item has focus
+    ShortcutContextApplication // While any window of the application is active
+} ShortcutContext;
+
+// The check state of a menu item, which can be partial for tristate items
+typedef enum {
+    CheckStateUnchecked,
+    CheckStatePartiallyChecked, // Only shown by tristate items; others show it as unchecked
+    CheckStateChecked
+} CheckState;
+
+// How a notification shown with a callback ended
+typedef enum {
+    NotificationResultClicked,  // The user clicked the notification
+    NotificationResultTimedOut, // The timeout elapsed without a click
+    NotificationResultDismissed // It was replaced by another notification or the tray went away
+} NotificationResult;
+
+// The icon shown on a notification
+typedef enum {
+    NotificationIconInformation, // The platform's information icon (the default)
+    NotificationIconWarning,     // The platform's warning icon
+    NotificationIconCritical,    // The platform's error icon
+    NotificationIconCustom       // The image in the spec's icon_data
+} NotificationIcon;
+
+// Called on the Qt thread with the outcome of a notification
+typedef void (*NotificationCallback)(void* user_data, NotificationResult result);
+
+// A button shown on a notification
+typedef struct {
+    const char* id; // Reported by the NotificationActionInvoked event when the button is clicked
+    const char* label; // The UTF-8 encoded button text
+} NotificationButton;
+
+// The content of a notification shown with show_notification
+typedef struct {
+    const char* title; // The UTF-8 encoded title
+    const char* body; // The UTF-8 encoded message
+    int timeout_ms; // How long to show the notification, or 0 for the default; the platform may ignore this
+    const NotificationButton* actions; // The buttons to show, or NULL
+    size_t action_count;
+    NotificationIcon icon;
+    const unsigned char* icon_data; // The encoded image for NotificationIconCustom, otherwise ignored; copied
+    size_t icon_size; // The size of icon_data in bytes
+    const char* icon_format; // The format of icon_data (e.g., "PNG", "SVG"), or NULL to detect it
+} NotificationSpec;
+
+// Optional properties applied when a menu item is created
+typedef struct {
+    bool enabled; // Whether the item starts enabled
+    bool visible; // Whether the item starts visible
+    const char* url; // Opened with the default handler when the item is clicked, or NULL
+    const char* parent; // Path of the submenu to add the item to (ids joined with '/'), or NULL for the top level
+    bool submenu; // Whether the item is a submenu that other items can be added to, rather than clickable
+    const char* accessible_description; // Announced by screen readers along with the item's text, or NULL
+    bool checkable; // Whether the item shows a check mark that toggles on click
+    bool checked; // Whether a checkable item starts checked
+    bool separator; // Adds a separator line instead of an item; text and id are ignored
+    bool bold; // Shows the item's text in bold
+    bool italic; // Shows the item's text in italics
+    bool progress; // Shows a progress bar labelled with the text; clicking it still emits MenuItemClicked
+    MenuRole role; // Where the item goes in the native application menu on macOS
+    const char* shortcut; // A key sequence in QKeySequence's portable format, e.g. "Ctrl+Q", or NULL
+    ShortcutContext shortcut_context; // Where the shortcut is active
+    const unsigned char* icon_data; // The encoded image shown next to the text, or NULL for none; copied
+    size_t icon_size; // The size of icon_data in bytes
+    const char* icon_format; // The format of icon_data (e.g., "PNG", "SVG"), or NULL to detect it
+    bool tristate; // Shows a check box that can also be partially checked; starts checked if checked is set
+} MenuItemOptions;
+
+// A screen's geometry in device-independent pixels, as reported by QGuiApplication::screens()
+typedef struct {
+    int x; // The screen's position in the virtual desktop
+    int y;
+    int width;
+    int height;
+    int available_x; // The area not covered by panels, docks or taskbars
+    int available_y;
+    int available_width;
+    int available_height;
+    double logical_dpi; // Logical dots per inch, used to scale fonts
+    double device_pixel_ratio; // Physical pixels per device-independent pixel
+    bool primary; // Whether the screen is the primary screen
+} ScreenInfo;
+
 // Struct to hold event data
 typedef struct {
     AppEventType type_; // Renamed from 'type' to 'type_' to avoid C++ keyword collision
-    const char* menu_id_str; // For MenuItemClicked events, now a string
+    const char* menu_id_str; // For MenuItemClicked and MenuItemHovered events, now a string; for NotificationActionInvoked events, the action id
+    int x; // For TrayClicked/TrayDoubleClicked events, the cursor position in screen coordinates
+    int y;
+    bool visible; // For VisibilityChanged events, whether the icon can be shown to the user
+    unsigned int notification_id; // For NotificationClicked/NotificationActionInvoked events, the id returned by show_notification
+    const char* args_str; // For SecondInstanceLaunched events, the arguments separated by '\x1f'; freed with free_char_ptr
+    MouseButton button; // For TrayButtonClicked events, the button that was clicked
+    bool dark; // For ColorSchemeChanged events, whether the new color scheme is dark
 } AppEvent;
 
+// Called with each event instead of queuing it for poll_event. The callback takes ownership of
+// the event's strings, as poll_event's caller would.
+typedef void (*EventCallback)(void* user_data, AppEvent event);
+
 /**
  * @brief Creates a new Qt application handle.
  */
@@ -45,7 +195,7 @@ void set_app_id(QtAppHandle* handle, const char* id);
 void set_organization_name(QtAppHandle* handle, const char* name); // Added this function
 
 /**
- * @brief Sets the application icon from raw binary data.
+ * @brief Sets both the tray icon and the application icon from raw binary data.
  *
  * @param handle The application handle.
  * @param data Pointer to the raw icon data.
@@ -54,16 +204,142 @@ void set_organization_name(QtAppHandle* handle, const char* name); // Added this
  */
 void set_app_icon_from_data(QtAppHandle* handle, const unsigned char* data, size_t size, const char* format);
 
+/**
+ * @brief Sets the image shown in the system tray from raw binary data.
+ *
+ * May be called before or while the event loop runs.
+ *
+ * @param handle The application handle.
+ * @param data Pointer to the raw icon data.
+ * @param size The size of the data in bytes.
+ * @param format The format of the icon data (e.g., "PNG", "JPG", "SVG").
+ */
+void set_tray_icon_from_data(QtAppHandle* handle, const unsigned char* data, size_t size, const char* format);
+
+/**
+ * @brief Sets the tray image like set_tray_icon_from_data, marked as a template (mask) image
+ * with QIcon::setIsMask so the platform may recolor it to match the panel.
+ * Only macOS recolors template images; elsewhere the image is shown as is. Setting the tray
+ * icon any other way clears the mark.
+ * @param handle The application handle.
+ * @param data Pointer to the raw icon data, typically black on a transparent background.
+ * @param size The size of the data in bytes.
+ * @param format The format of the icon data (e.g., "PNG", "SVG").
+ */
+void set_tray_icon_template(QtAppHandle* handle, const unsigned char* data, size_t size, const char* format);
+
+/**
+ * @brief Sets the tray icon from several renditions of the same image, e.g. 16, 32 and 64 pixels.
+ * Qt picks the one that best fits the size the icon is drawn at, taking the screen's device pixel
+ * ratio into account. The data is copied. Replaces an icon set with set_tray_icon_from_data.
+ * @param handle The application handle.
+ * @param variants Pointers to the raw data of each rendition.
+ * @param sizes The size in bytes of each rendition.
+ * @param pixel_sizes The nominal width and height in pixels of each rendition; images are scaled
+ * to fit it. 0 keeps an image's own size.
+ * @param count The number of renditions.
+ * @param format The format of the data (e.g., "PNG", "SVG").
+ */
+void set_tray_icon_variants(QtAppHandle* handle, const unsigned char* const* variants, const size_t* sizes,
+                            const unsigned int* pixel_sizes, size_t count, const char* format);
+
+/**
+ * @brief Sets the tray icon from uncompressed pixels, skipping image decoding.
+ * The pixels are copied. Replaces an icon set with set_tray_icon_from_data or set_tray_icon_variants.
+ * @param handle The application handle.
+ * @param pixels width * height pixels, row by row from the top, as 4 bytes each: red, green, blue
+ * and alpha (not premultiplied).
+ * @param width The width in pixels.
+ * @param height The height in pixels.
+ */
+void set_tray_icon_rgba(QtAppHandle* handle, const unsigned char* pixels, unsigned int width, unsigned int height);
+
+/**
+ * @brief Sets the tray icon from renditions for a light and a dark color scheme, e.g. light and
+ * dark at 1x and 2x. The renditions for the current scheme are used as with
+ * set_tray_icon_variants, and swapped for the others when the scheme changes, which emits a
+ * ColorSchemeChanged event (Qt 6.5 or later). Without Qt's color scheme, a palette with light
+ * text counts as dark. The data is copied. Setting the tray icon any other way replaces both sets.
+ * @param handle The application handle.
+ * @param variants Pointers to the raw data of each rendition.
+ * @param sizes The size in bytes of each rendition.
+ * @param pixel_sizes The nominal width and height in pixels of each rendition, or 0.
+ * @param dark Whether each rendition is for the dark color scheme.
+ * @param count The number of renditions.
+ * @param format The format of the data (e.g., "PNG", "SVG").
+ */
+void set_tray_icon_themed(QtAppHandle* handle, const unsigned char* const* variants, const size_t* sizes,
+                          const unsigned int* pixel_sizes, const bool* dark, size_t count, const char* format);
+
+/**
+ * @brief Sets the application icon used for windows and the taskbar from raw binary data.
+ *
+ * If it isn't set, the tray icon is used. May be called before or while the event loop runs.
+ *
+ * @param handle The application handle.
+ * @param data Pointer to the raw icon data.
+ * @param size The size of the data in bytes.
+ * @param format The format of the icon data (e.g., "PNG", "JPG", "SVG").
+ */
+void set_application_icon_from_data(QtAppHandle* handle, const unsigned char* data, size_t size,
+                                    const char* format);
+
+/**
+ * @brief Sets the icon shown in the tray when no tray icon is set or it can't be decoded.
+ *
+ * May be called before or while the event loop runs.
+ *
+ * @param handle The application handle.
+ * @param data Pointer to the raw icon data.
+ * @param size The size of the data in bytes.
+ * @param format The format of the icon data (e.g., "PNG", "JPG", "SVG").
+ */
+void set_fallback_icon_from_data(QtAppHandle* handle, const unsigned char* data, size_t size,
+                                 const char* format);
+
 /**
  * @brief Initializes the system tray icon with a menu.
  * @param handle The application handle.
+ * @return False if no display is available to show the tray icon on, true otherwise.
  */
-void init_tray(QtAppHandle* handle);
+bool init_tray(QtAppHandle* handle);
+
+/**
+ * @brief Checks whether the application can be initialized, without creating the QApplication.
+ *
+ * Besides checking for a display, this loads the Qt platform plugin on its own on Linux, since
+ * QApplication aborts the process when it can't. On failure, the reason is available from
+ * last_error, e.g. the library the xcb plugin is missing.
+ *
+ * @param handle The application handle.
+ * @return False if no display is available to show tray icons on or the platform plugin can't
+ * be loaded, true otherwise.
+ */
+bool init_app(QtAppHandle* handle);
+
+/**
+ * @brief Adds a tray icon that shares the QApplication and event loop of another handle.
+ * The tray handle keeps its own icon, menu and event queue, but is never run itself: its tray
+ * is created when the application handle's event loop runs (or immediately, if it already does).
+ * @param handle The application handle that runs the event loop.
+ * @param tray The handle of the tray to add.
+ */
+void add_app_tray(QtAppHandle* handle, QtAppHandle* tray);
+
+/**
+ * @brief Removes a tray added with add_app_tray, destroying its tray icon and menu.
+ * Must be called before the tray handle is cleaned up.
+ * @param handle The application handle that runs the event loop.
+ * @param tray The handle of the tray to remove.
+ */
+void remove_app_tray(QtAppHandle* handle, QtAppHandle* tray);
 
 /**
  * @brief Runs the Qt application event loop.
  * This is a blocking call that starts the Qt event loop.
  * It should be called from the thread intended to be the Qt GUI thread.
+ * Once it returns, the QApplication and the trays' Qt objects are destroyed, keeping their
+ * settings and menus, so it may be called again to restart the application.
  * @param handle The application handle.
  * @param argc The number of command-line arguments.
  * @param argv An array of command-line argument strings.
@@ -97,8 +373,519 @@ void cleanup_qt_app(QtAppHandle* handle);
  * @param handle The application handle.
  * @param text The text to display for the menu item.
  * @param id A unique string ID for the menu item, used to identify clicks.
+ * @param options Optional properties of the item, or NULL for an enabled, visible plain item.
+ * A MenuItemClicked event is still emitted for items with a URL.
+ * Items in a submenu are identified by their path, e.g. "file/recent/doc1", in events and in
+ * set_menu_items_enabled. The parent submenu must be added first. Items added while the
+ * event loop runs are added on the Qt thread, and the menu is exported again to
+ * StatusNotifierItem hosts so that they show up there too.
+ */
+void add_tray_menu_item(QtAppHandle* handle, const char* text, const char* id, const MenuItemOptions* options);
+
+/**
+ * @brief Starts collecting a replacement for the whole context menu.
+ * Items added with add_tray_menu_item until finish_tray_menu_replace is called make up the new
+ * menu instead of being added to the current one.
+ * @param handle The application handle.
+ */
+void begin_tray_menu_replace(QtAppHandle* handle);
+
+/**
+ * @brief Builds the collected replacement menu and swaps it in for the current one in one step.
+ * The new menu is built on the Qt thread while the previous one stays in place.
+ * @param handle The application handle.
+ * @param apply Whether to apply the replacement; false discards the collected items.
+ */
+void finish_tray_menu_replace(QtAppHandle* handle, bool apply);
+
+/**
+ * @brief Starts collecting the menu opened by a left click on the tray icon.
+ * Items added with add_tray_menu_item until finish_left_click_menu is called make up that menu
+ * instead of being added to the context menu. Only their text, id, parent, submenu and
+ * separator options are used.
+ * @param handle The application handle.
+ */
+void begin_left_click_menu(QtAppHandle* handle);
+
+/**
+ * @brief Replaces the left-click menu with the collected items.
+ * While the left-click menu has items, a left click opens it instead of running the behavior
+ * set with set_button_behavior; without items, that behavior applies again. Its items emit
+ * MenuItemClicked with their path like context menu items, but can't be changed by id.
+ * @param handle The application handle.
+ * @param apply Whether to apply the collected items; false keeps the current left-click menu.
+ */
+void finish_left_click_menu(QtAppHandle* handle, bool apply);
+
+/**
+ * @brief Shows the tray's context menu at the given screen position.
+ * The request is posted to the Qt thread; it does nothing before the event loop runs.
+ * @param handle The application handle.
+ * @param x The horizontal screen coordinate.
+ * @param y The vertical screen coordinate.
+ */
+void popup_tray_menu(QtAppHandle* handle, int x, int y);
+
+/**
+ * @brief Shows the tray's context menu at the current cursor position.
+ * @param handle The application handle.
+ */
+void popup_tray_menu_at_cursor(QtAppHandle* handle);
+
+/**
+ * @brief Waits until everything posted to the Qt thread so far has run.
+ * Returns immediately before the event loop runs or when called on the Qt thread.
+ * @param handle The application handle.
+ */
+void flush_qt_app(QtAppHandle* handle);
+
+/**
+ * @brief Returns the ID of the currently highlighted menu item.
+ * @param handle The application handle.
+ * @return A newly allocated string that must be released with free_char_ptr, or NULL if the
+ * menu is closed or no item is highlighted.
+ */
+char* get_active_menu_item(QtAppHandle* handle);
+
+/**
+ * @brief Sets the menu item highlighted whenever the context menu opens, and right away if it
+ * is open, so that keyboard navigation starts there.
+ * An item inside a submenu highlights the top-level submenu containing it. May be called
+ * before or after the event loop starts.
+ * @param handle The application handle.
+ * @param id The path of the item.
+ * @return false if there is no item with this path.
+ */
+bool set_active_menu_item(QtAppHandle* handle, const char* id);
+
+/**
+ * @brief Returns whether the context menu or one of its submenus is currently shown as a popup.
+ * @param handle The application handle.
+ * @return true if a menu is visible, false if they are closed or the event loop isn't running.
+ */
+bool is_menu_open(QtAppHandle* handle);
+
+/**
+ * @brief Limits the number of events that can wait in the event queue.
+ * Events already queued beyond the new capacity are discarded according to the policy.
+ * @param handle The application handle.
+ * @param capacity The maximum number of queued events. Ignored for the Unbounded policy.
+ * @param policy What to do with new events once the queue is full.
+ */
+void set_event_queue_capacity(QtAppHandle* handle, size_t capacity, OverflowPolicy policy);
+
+/**
+ * @brief Queues `event` as if the backend had emitted it, for testing and benchmarking.
+ * Its strings are copied. Like other events, it goes to the event callback if one is set,
+ * but on the calling thread, and is held back or dropped while events are paused.
+ * @param handle The application handle.
+ * @param event The event to queue.
+ */
+void inject_event(QtAppHandle* handle, const AppEvent* event);
+
+/**
+ * @brief Delivers events to a callback on the Qt thread instead of queuing them for poll_event.
+ * Events already queued are passed to the new callback first. After this returns, the previous
+ * callback is no longer called, so its user_data may be released.
+ * @param handle The application handle.
+ * @param callback The function to call with each event, or NULL to queue events again.
+ * @param user_data Passed to callback.
+ */
+void set_event_callback(QtAppHandle* handle, EventCallback callback, void* user_data);
+
+/**
+ * @brief Chooses which kinds of events are delivered. Events of other kinds are discarded as
+ * soon as they happen, before pausing, the queue or the event callback see them. All kinds are
+ * delivered by default.
+ * @param handle The application handle.
+ * @param mask Bit n set delivers events whose AppEventType has the value n.
+ */
+void set_event_mask(QtAppHandle* handle, unsigned int mask);
+
+/**
+ * @brief Pauses or resumes the delivery of user input events: clicks on the tray icon, its menu
+ * items and notifications, and menu item hovers. Other events, such as Quit, are still delivered. Items with a URL
+ * still open it while paused.
+ * @param handle The application handle.
+ * @param paused Whether delivery is paused.
+ */
+void set_events_paused(QtAppHandle* handle, bool paused);
+
+/**
+ * @brief Chooses what happens to user input events while delivery is paused. Defaults to
+ * PauseDiscard. Switching to PauseDiscard drops the events held back so far.
+ * @param handle The application handle.
+ * @param policy The policy for paused events.
+ */
+void set_pause_policy(QtAppHandle* handle, PausePolicy policy);
+
+/**
+ * @brief Sets the human-friendly application name, e.g. shown with notifications.
+ * Qt uses the application name (the app id) until this is called. May be called before or
+ * after run_qt_app.
+ * @param handle The application handle.
+ * @param name The UTF-8 encoded display name.
+ */
+void set_display_name(QtAppHandle* handle, const char* name);
+
+/**
+ * @brief Sets the widget style (e.g. "Fusion") used by the tray's menus.
+ * Must be called before run_qt_app; unknown style names are ignored by Qt.
+ * @param handle The application handle.
+ * @param style The name of the style.
+ */
+void set_app_style(QtAppHandle* handle, const char* style);
+
+/**
+ * @brief Enables or disables a built-in dark palette, overriding the system theme.
+ * Must be called before run_qt_app.
+ * @param handle The application handle.
+ * @param enabled Whether the dark palette is applied.
+ */
+void set_dark_palette(QtAppHandle* handle, bool enabled);
+
+/**
+ * @brief Enables or disables high-DPI scaling. Must be called before run_qt_app, since Qt reads
+ * the setting when the application is created.
+ * Qt 6 scales by default; enabling keeps fractional scale factors (e.g. 1.5) unrounded for crisp
+ * rendering, and disabling renders at device pixels (QT_ENABLE_HIGHDPI_SCALING=0). Both are
+ * applied on the calling thread; enabling again unsets the variable if disabling had set it.
+ * @param handle The application handle.
+ * @param enabled Whether high-DPI scaling is used.
+ */
+void set_high_dpi(QtAppHandle* handle, bool enabled);
+
+/**
+ * @brief Makes Ctrl+C quit the event loop gracefully, emitting a Quit event.
+ * Must be called before run_qt_app. While run_qt_app runs, SIGINT (on Windows, the console's
+ * Ctrl+C) is handled by quitting the application; the previous handler is restored when it
+ * returns. By default, SIGINT is left alone.
+ * @param handle The application handle.
+ * @param enabled Whether Ctrl+C quits the event loop.
+ */
+void set_quit_on_interrupt(QtAppHandle* handle, bool enabled);
+
+/**
+ * @brief Sets how long run_qt_app waits for a systray host that isn't up yet, e.g. right after
+ * login. Must be called before run_qt_app. With a timeout, the event loop starts without a host
+ * and checks for it with a growing delay; each tray gets VisibilityChanged and TrayReconnected
+ * events when it appears. If it doesn't appear in time, the event loop exits with -1. By
+ * default (0), run_qt_app returns -1 right away when no systray host is available.
+ * @param handle The application handle.
+ * @param timeout_ms How long to wait in milliseconds, or 0 not to wait.
+ */
+void set_tray_wait_timeout(QtAppHandle* handle, int timeout_ms);
+
+/**
+ * @brief Enables or disables several menu items in one call on the Qt thread.
+ * @param handle The application handle.
+ * @param ids The IDs of the menu items to update.
+ * @param enabled The new enabled state for each ID.
+ * @param count The number of entries in ids, enabled and found.
+ * @param found Receives, for each ID, whether a matching menu item exists.
+ */
+void set_menu_items_enabled(QtAppHandle* handle, const char* const* ids, const bool* enabled, size_t count, bool* found);
+
+/**
+ * @brief Shows or hides a menu item on the Qt thread.
+ * @param handle The application handle.
+ * @param id The path of the menu item, e.g. "file/recent/doc1" for an item in a submenu.
+ * @param visible Whether the item is shown.
+ * @return Whether a menu item has the given ID.
+ */
+bool set_menu_item_visible(QtAppHandle* handle, const char* id, bool visible);
+
+/**
+ * @brief Changes the text of a menu item, or the title of a submenu, on the Qt thread.
+ * @param handle The application handle.
+ * @param id The path of the menu item.
+ * @param text The new text, with '&' marking a mnemonic like when the item was added.
+ * @return Whether a menu item has the given ID.
+ */
+bool set_menu_item_text(QtAppHandle* handle, const char* id, const char* text);
+
+/**
+ * @brief Returns whether the context menu has an item with the given ID, including items added
+ * before the tray is set up.
+ * @param handle The application handle.
+ * @param id The path of the menu item.
+ * @return Whether a menu item has the given ID.
+ */
+bool has_menu_item(QtAppHandle* handle, const char* id);
+
+/**
+ * @brief Removes a menu item on the Qt thread. Removing a submenu removes everything in it.
+ * @param handle The application handle.
+ * @param id The path of the menu item.
+ * @return Whether a menu item had the given ID.
+ */
+bool remove_menu_item(QtAppHandle* handle, const char* id);
+
+/**
+ * @brief Removes every menu item whose path starts with the given prefix, along with the items in
+ * removed submenus, like remove_menu_item does for each of them.
+ * @param handle The application handle.
+ * @param prefix The start of the paths to remove, e.g. "recent:" or "file/recent:".
+ * @return How many menu items were removed, counting a submenu and its contents as one.
+ */
+size_t remove_menu_items_by_prefix(QtAppHandle* handle, const char* prefix);
+
+/**
+ * @brief Moves a menu item, or a submenu with everything in it, within its menu on the Qt thread.
+ * Keyboard navigation of the menu follows the new order.
+ * @param handle The application handle.
+ * @param id The path of the menu item.
+ * @param index The new position among the entries of the item's menu, separators included.
+ * Positions past the last entry move the item to the end.
+ * @return Whether a menu item has the given ID.
+ */
+bool move_menu_item(QtAppHandle* handle, const char* id, size_t index);
+
+/**
+ * @brief Inverts the check state of a menu item on the Qt thread, making it checkable if needed.
+ * @param handle The application handle.
+ * @param id The ID of the menu item.
+ * @return The new check state (1 for checked, 0 for unchecked), or -1 if no menu item has the ID.
+ */
+int toggle_menu_item_checked(QtAppHandle* handle, const char* id);
+
+/**
+ * @brief Sets the check state of a menu item on the Qt thread, making it checkable if needed.
+ * Items that aren't tristate show CheckStatePartiallyChecked as unchecked.
+ * @param handle The application handle.
+ * @param id The ID of the menu item.
+ * @param state The new check state.
+ * @return Whether a menu item had the given ID.
+ */
+bool set_menu_item_check_state(QtAppHandle* handle, const char* id, CheckState state);
+
+/**
+ * @brief Saves a menu item's check state in QSettings whenever it changes, and restores it when
+ * the item is created. The item becomes checkable.
+ * May be called before the item exists; it applies to items added later with the same ID.
+ * @param handle The application handle.
+ * @param id The ID of the menu item.
+ */
+void persist_menu_item_checked(QtAppHandle* handle, const char* id);
+
+/**
+ * @brief Sets the value shown by a progress menu item.
+ * @param handle The application handle.
+ * @param id The ID of the progress item.
+ * @param value The progress from 0 to 100; other values are clamped.
+ * @return false if no progress item has the ID.
+ */
+bool set_menu_item_progress(QtAppHandle* handle, const char* id, int value);
+
+/**
+ * @brief Starts or stops animating the tray icon with the busy spinner.
+ * When stopped, the regular icon is restored.
+ * @param handle The application handle.
+ * @param busy Whether the spinner is shown.
+ */
+void set_busy(QtAppHandle* handle, bool busy);
+
+/**
+ * @brief Replaces the built-in busy spinner with custom frames.
+ * The data is copied. Passing no frames restores the built-in spinner.
+ * @param handle The application handle.
+ * @param frames Pointers to the raw data of each frame.
+ * @param sizes The size in bytes of each frame.
+ * @param count The number of frames.
+ * @param format The format of the frame data (e.g., "PNG", "SVG").
+ */
+void set_busy_frames(QtAppHandle* handle, const unsigned char* const* frames, const size_t* sizes, size_t count, const char* format);
+
+/**
+ * @brief Blinks the tray icon to draw the user's attention.
+ * Requires the tray icon to have been created by run_qt_app.
+ * @param handle The application handle.
+ * @param count The number of blinks, a negative value to blink until the tray is activated,
+ * or 0 to stop blinking.
+ */
+void request_attention(QtAppHandle* handle, int count);
+
+/**
+ * @brief Makes this process the only instance holding a key, using QLocalServer.
+ * If another process holds the key, it is sent args and receives a SecondInstanceLaunched event.
+ * May be called before the event loop starts; the key is then listened on once the tray is set up.
+ * @param handle The application handle.
+ * @param key The name identifying the application, shared by all of its instances.
+ * @param args The arguments of this process, separated by '\x1f'.
+ * @return true if this process is now the primary instance, false if another process holds the key.
+ */
+bool acquire_single_instance(QtAppHandle* handle, const char* key, const char* args);
+
+/**
+ * @brief Shows a notification balloon from the tray icon.
+ * Requires the tray icon to have been created by run_qt_app.
+ * A NotificationClicked event carrying the returned id is queued if the user clicks it.
+ * When built with QT6_BIND_DBUS_NOTIFICATIONS on Linux and other freedesktop systems, the
+ * notification goes through the org.freedesktop.Notifications service if it is running on the
+ * session bus, which shows the spec's buttons and queues a NotificationActionInvoked event when
+ * one is clicked. Otherwise QSystemTrayIcon's balloon is shown, which can't show buttons, so the
+ * spec's actions are ignored.
+ * @param handle The application handle.
+ * @param spec The title, message, timeout, buttons and icon of the notification. A custom icon
+ * that fails to decode is reported by last_error, and the information icon is shown instead.
+ * @param callback Called once on the Qt thread with the outcome of the notification, or NULL.
+ * It is not called if 0 is returned.
+ * @param user_data Passed to callback.
+ * @return An id identifying the notification, or 0 if there is no tray icon to show it from.
+ */
+unsigned int show_notification(QtAppHandle* handle, const NotificationSpec* spec, NotificationCallback callback,
+                               void* user_data);
+
+/**
+ * @brief Returns the underlying QSystemTrayIcon* as an opaque pointer.
+ * The object lives on the Qt thread and must only be used from there.
+ * @param handle The application handle.
+ * @return The QSystemTrayIcon*, or NULL if the tray icon hasn't been created yet.
+ */
+void* get_tray_icon_ptr(QtAppHandle* handle);
+
+/**
+ * @brief Sets the font of the tray's context menu and its submenus.
+ * An unavailable family is substituted by Qt's font matching. The platform style may ignore the
+ * font, e.g. for native menus. May be called before or after the event loop starts.
+ * @param handle The application handle.
+ * @param family The font family, or NULL or empty to keep the default family.
+ * @param point_size The size in points, or 0 to keep the default size.
+ */
+void set_menu_font(QtAppHandle* handle, const char* family, double point_size);
+
+/**
+ * @brief Lets the user tear the context menu and its submenus off into floating windows.
+ * Only menus drawn by Qt support this; native and StatusNotifierItem menus ignore it.
+ * May be called before or after the event loop starts.
+ * @param handle The application handle.
+ * @param enabled Whether the menus show a tear-off handle.
+ */
+void set_tear_off_enabled(QtAppHandle* handle, bool enabled);
+
+/**
+ * @brief Sets what clicking the tray icon with `button` does.
+ * By default, the left button emits TrayClicked, the right button opens the context menu and
+ * the middle button does nothing. The context menu is only attached to the icon while the
+ * right button opens it, since the platform shows an attached menu on its own. Double clicks
+ * always emit TrayDoubleClicked. May be called before or after the event loop starts.
+ * @param handle The application handle.
+ * @param button The mouse button.
+ * @param behavior What a click does.
+ * @param item_id For ButtonTriggerItem, the path of the menu item to trigger; ignored otherwise.
+ */
+void set_button_behavior(QtAppHandle* handle, MouseButton button, ButtonBehavior behavior, const char* item_id);
+
+/**
+ * @brief Shows or hides the tray icon. A hidden icon isn't registered with the systray host.
+ * May be called before the event loop starts, in which case the icon is created hidden.
+ * @param handle The application handle.
+ * @param shown Whether the icon is shown.
+ */
+void set_tray_icon_shown(QtAppHandle* handle, bool shown);
+
+/**
+ * @brief Applies the current icon, tooltip and menu to the tray icon again, forcing the systray
+ * host to redraw them. The icon is decoded again, so a themed icon follows the current color
+ * scheme; a running animation keeps its frame. Does nothing unless the tray is set up.
+ * @param handle The application handle.
+ */
+void refresh_tray(QtAppHandle* handle);
+
+/**
+ * @brief Sets the name screen readers announce for the tray icon.
+ * Qt has no accessibility API for the tray icon itself, so the name is applied as the tray's
+ * tooltip and as the context menu's accessible name. May be called before or after the event
+ * loop starts.
+ * @param handle The application handle.
+ * @param name The UTF-8 encoded name.
+ */
+void set_accessible_name(QtAppHandle* handle, const char* name);
+
+/**
+ * @brief Pre-scales the tray icon to the given size before handing it to the tray.
+ * The platform may still scale the displayed icon.
+ * @param handle The application handle.
+ * @param size The preferred width and height in pixels, or 0 to keep the icon's own size.
+ */
+void set_preferred_icon_size(QtAppHandle* handle, int size);
+
+/**
+ * @brief Returns whether the platform provides a system tray.
+ * Requires the Qt event loop to be running; returns false otherwise.
+ * @param handle The application handle.
+ */
+bool is_system_tray_available(QtAppHandle* handle);
+
+/**
+ * @brief Lists the screens. The primary screen comes first.
+ * Requires the event loop to be running; reports no screens otherwise.
+ * @param handle The application handle.
+ * @param screens Receives up to capacity screens, or NULL if capacity is 0.
+ * @param capacity The number of entries screens can hold.
+ * @return The number of screens, which may exceed capacity.
+ */
+size_t get_screens(QtAppHandle* handle, ScreenInfo* screens, size_t capacity);
+
+/**
+ * @brief Opens a local file or folder with its default handler.
+ * Requires the Qt event loop to be running.
+ * @param handle The application handle.
+ * @param path The UTF-8 encoded path to open.
+ * @return true if the default handler was launched.
+ */
+bool open_path(QtAppHandle* handle, const char* path);
+
+/**
+ * @brief Shows a modal Yes/No QMessageBox and waits until it is answered.
+ * The dialog is shown on the Qt thread; the calling thread is blocked meanwhile.
+ * @param handle The application handle.
+ * @param title The UTF-8 encoded window title.
+ * @param text The UTF-8 encoded question.
+ * @return 1 if the user chose Yes, 0 if they chose No or closed the dialog, and -1 if the
+ * event loop isn't running.
+ */
+int confirm_dialog(QtAppHandle* handle, const char* title, const char* text);
+
+/**
+ * @brief Returns the name of the Qt platform plugin, such as "xcb", "wayland" or "windows".
+ * Needs no handle. Once a QApplication exists, this is the plugin in use; before that, it is
+ * predicted from QT_QPA_PLATFORM and the session, the way Qt picks the plugin.
+ * @return A newly allocated string that must be released with free_char_ptr.
+ */
+char* get_platform_name(void);
+
+/**
+ * @brief Reads a value from the application's QSettings.
+ * Needs no handle, so settings can be read before the application is initialized.
+ * @param organization The organization name the settings are stored under.
+ * @param application The application name the settings are stored under.
+ * @param key The key of the value, with '/' separating groups.
+ * @return A newly allocated string that must be released with free_char_ptr, or NULL if the key
+ * doesn't exist.
+ */
+char* read_setting(const char* organization, const char* application, const char* key);
+
+/**
+ * @brief Writes a value to the application's QSettings and flushes it to permanent storage.
+ * @param organization The organization name the settings are stored under.
+ * @param application The application name the settings are stored under.
+ * @param key The key of the value, with '/' separating groups.
+ * @param value The UTF-8 encoded value.
+ * @return false if the settings couldn't be written.
+ */
+bool write_setting(const char* organization, const char* application, const char* key, const char* value);
+
+/**
+ * @brief Describes the most recent failure on the handle, for diagnostics.
+ * Failures are recorded by calls that would otherwise fail silently, such as an icon that can't
+ * be decoded or a menu item whose parent submenu doesn't exist, including those that happen
+ * later on the Qt thread. The message is kept until the next failure replaces it.
+ * @param handle The application handle.
+ * @return A newly allocated string that must be released with free_char_ptr, or NULL if nothing
+ * has failed.
  */
-void add_tray_menu_item(QtAppHandle* handle, const char* text, const char* id);
+char* last_error(QtAppHandle* handle);
 
 /**
  * @brief Frees a character pointer allocated by the C++ side.
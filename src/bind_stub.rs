@@ -0,0 +1,914 @@
+//! A stand-in for the Qt bindings, used when the `stub` feature is enabled.
+//!
+//! It mirrors the items generated by bindgen from `lib/src/lib.hpp`, so the rest of the crate
+//! compiles unchanged without Qt. Keep it in sync with the header. Nothing is shown, but the
+//! event queue behaves like the Qt backend's, so injected events can be polled.
+//!
+//! The menu is a second implementation of the one in `lib/src/lib.cpp`: `StubState::menu_items`
+//! follows its `pending_menu_items`, and adding, finding, changing, removing and moving items
+//! follow the corresponding `QtAppWrapper` methods, so that tests can check menu handling through
+//! `stub_menu_items` without Qt. Any change to how `lib.cpp` handles menu items has to be made
+//! here too.
+
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(dead_code)]
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    collections::VecDeque,
+    ffi::{c_char, c_int, c_uchar, c_uint, c_void, CStr, CString},
+    ptr,
+    sync::Mutex,
+};
+
+#[repr(C)]
+pub struct QtAppHandle {
+    _unused: [u8; 0],
+}
+
+pub type AppEventType = c_uint;
+pub const AppEventType_None: AppEventType = 0;
+pub const AppEventType_TrayClicked: AppEventType = 1;
+pub const AppEventType_TrayDoubleClicked: AppEventType = 2;
+pub const AppEventType_MenuItemClicked: AppEventType = 3;
+pub const AppEventType_Quit: AppEventType = 4;
+pub const AppEventType_TrayReconnected: AppEventType = 5;
+pub const AppEventType_VisibilityChanged: AppEventType = 6;
+pub const AppEventType_NotificationClicked: AppEventType = 7;
+pub const AppEventType_SecondInstanceLaunched: AppEventType = 8;
+pub const AppEventType_MenuAboutToShow: AppEventType = 9;
+pub const AppEventType_MenuClosed: AppEventType = 10;
+pub const AppEventType_NotificationActionInvoked: AppEventType = 11;
+pub const AppEventType_TrayButtonClicked: AppEventType = 12;
+pub const AppEventType_MenuItemHovered: AppEventType = 13;
+pub const AppEventType_ColorSchemeChanged: AppEventType = 14;
+
+pub type OverflowPolicy = c_uint;
+pub const OverflowPolicy_Unbounded: OverflowPolicy = 0;
+pub const OverflowPolicy_DropOldest: OverflowPolicy = 1;
+pub const OverflowPolicy_DropNewest: OverflowPolicy = 2;
+
+pub type PausePolicy = c_uint;
+pub const PausePolicy_PauseDiscard: PausePolicy = 0;
+pub const PausePolicy_PauseBuffer: PausePolicy = 1;
+
+pub type MouseButton = c_uint;
+pub const MouseButton_MouseButtonLeft: MouseButton = 0;
+pub const MouseButton_MouseButtonRight: MouseButton = 1;
+pub const MouseButton_MouseButtonMiddle: MouseButton = 2;
+
+pub type ButtonBehavior = c_uint;
+pub const ButtonBehavior_ButtonEmitClick: ButtonBehavior = 0;
+pub const ButtonBehavior_ButtonOpenMenu: ButtonBehavior = 1;
+pub const ButtonBehavior_ButtonTriggerItem: ButtonBehavior = 2;
+pub const ButtonBehavior_ButtonIgnore: ButtonBehavior = 3;
+
+pub type MenuRole = c_uint;
+pub const MenuRole_MenuRoleTextHeuristic: MenuRole = 0;
+pub const MenuRole_MenuRoleNone: MenuRole = 1;
+pub const MenuRole_MenuRoleAbout: MenuRole = 2;
+pub const MenuRole_MenuRolePreferences: MenuRole = 3;
+pub const MenuRole_MenuRoleQuit: MenuRole = 4;
+pub type ShortcutContext = c_uint;
+pub const ShortcutContext_ShortcutContextWindow: ShortcutContext = 0;
+pub const ShortcutContext_ShortcutContextWidget: ShortcutContext = 1;
+pub const ShortcutContext_ShortcutContextApplication: ShortcutContext = 2;
+
+pub type CheckState = c_uint;
+pub const CheckState_CheckStateUnchecked: CheckState = 0;
+pub const CheckState_CheckStatePartiallyChecked: CheckState = 1;
+pub const CheckState_CheckStateChecked: CheckState = 2;
+
+pub type NotificationIcon = c_uint;
+pub const NotificationIcon_NotificationIconInformation: NotificationIcon = 0;
+pub const NotificationIcon_NotificationIconWarning: NotificationIcon = 1;
+pub const NotificationIcon_NotificationIconCritical: NotificationIcon = 2;
+pub const NotificationIcon_NotificationIconCustom: NotificationIcon = 3;
+
+pub type NotificationResult = c_uint;
+pub const NotificationResult_NotificationResultClicked: NotificationResult = 0;
+pub const NotificationResult_NotificationResultTimedOut: NotificationResult = 1;
+pub const NotificationResult_NotificationResultDismissed: NotificationResult = 2;
+
+pub type NotificationCallback =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, result: NotificationResult)>;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct NotificationButton {
+    pub id: *const c_char,
+    pub label: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct NotificationSpec {
+    pub title: *const c_char,
+    pub body: *const c_char,
+    pub timeout_ms: c_int,
+    pub actions: *const NotificationButton,
+    pub action_count: usize,
+    pub icon: NotificationIcon,
+    pub icon_data: *const c_uchar,
+    pub icon_size: usize,
+    pub icon_format: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MenuItemOptions {
+    pub enabled: bool,
+    pub visible: bool,
+    pub url: *const c_char,
+    pub parent: *const c_char,
+    pub submenu: bool,
+    pub accessible_description: *const c_char,
+    pub checkable: bool,
+    pub checked: bool,
+    pub separator: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub progress: bool,
+    pub role: MenuRole,
+    pub shortcut: *const c_char,
+    pub shortcut_context: ShortcutContext,
+    pub icon_data: *const c_uchar,
+    pub icon_size: usize,
+    pub icon_format: *const c_char,
+    pub tristate: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScreenInfo {
+    pub x: c_int,
+    pub y: c_int,
+    pub width: c_int,
+    pub height: c_int,
+    pub available_x: c_int,
+    pub available_y: c_int,
+    pub available_width: c_int,
+    pub available_height: c_int,
+    pub logical_dpi: f64,
+    pub device_pixel_ratio: f64,
+    pub primary: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AppEvent {
+    pub type_: AppEventType,
+    pub menu_id_str: *const c_char,
+    pub x: c_int,
+    pub y: c_int,
+    pub visible: bool,
+    pub notification_id: c_uint,
+    pub args_str: *const c_char,
+    pub button: MouseButton,
+    pub dark: bool,
+}
+
+pub type EventCallback = Option<unsafe extern "C" fn(user_data: *mut c_void, event: AppEvent)>;
+
+/// What a handle points to. Events are handled as `pushEvent` in lib.cpp does, and menu items
+/// as its `pending_menu_items`.
+struct StubApp {
+    state: Mutex<StubState>,
+}
+
+/// A menu entry as the stub keeps it, which tests can inspect with `stub_menu_items`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StubMenuItem {
+    pub id: String,
+    pub parent: String,
+    pub text: String,
+    pub separator: bool,
+    pub enabled: bool,
+    pub visible: bool,
+}
+
+impl StubMenuItem {
+    /// The id of the item among all items, as `MenuItemSpec::path` in lib.cpp. Separators have
+    /// an empty id.
+    fn path(&self) -> String {
+        if self.parent.is_empty() {
+            self.id.clone()
+        } else {
+            format!("{}/{}", self.parent, self.id)
+        }
+    }
+
+    fn has_path(&self, path: &str) -> bool {
+        !self.separator && self.path() == path
+    }
+}
+
+struct StubState {
+    events: VecDeque<AppEvent>,
+    capacity: usize,
+    overflow_policy: OverflowPolicy,
+    event_mask: c_uint,
+    paused: bool,
+    pause_policy: PausePolicy,
+    paused_events: VecDeque<AppEvent>,
+    callback: EventCallback,
+    user_data: *mut c_void,
+    menu_items: Vec<StubMenuItem>,
+    // The items collected between begin_tray_menu_replace and finish_tray_menu_replace.
+    replacement_menu_items: Option<Vec<StubMenuItem>>,
+    collecting_left_click_menu: bool,
+}
+
+impl StubState {
+    /// Removes a menu item and everything below it, as `removeMenuItemNow` in lib.cpp.
+    fn remove_menu_item(&mut self, path: &str) -> bool {
+        let found = self.menu_items.iter().any(|item| item.has_path(path));
+        let prefix = format!("{}/", path);
+        self.menu_items.retain(|item| {
+            let item_path = item.path();
+            item_path != path && !item_path.starts_with(&prefix)
+        });
+        found
+    }
+}
+
+impl Drop for StubState {
+    fn drop(&mut self) {
+        for event in self.events.iter().chain(&self.paused_events) {
+            unsafe { drop_event(event) };
+        }
+    }
+}
+
+unsafe fn stub_app<'a>(handle: *mut QtAppHandle) -> Option<&'a StubApp> {
+    (handle as *const StubApp).as_ref()
+}
+
+/// Copies a string the way the backend's `strdup` does, to be released with `free_char_ptr`.
+unsafe fn copy_string(text: *const c_char) -> *const c_char {
+    if text.is_null() {
+        return ptr::null();
+    }
+    CStr::from_ptr(text).to_owned().into_raw()
+}
+
+/// Releases the strings of an event that will never be polled.
+unsafe fn drop_event(event: &AppEvent) {
+    free_char_ptr(event.menu_id_str);
+    free_char_ptr(event.args_str);
+}
+
+fn is_user_input(type_: AppEventType) -> bool {
+    matches!(
+        type_,
+        AppEventType_TrayClicked
+            | AppEventType_TrayDoubleClicked
+            | AppEventType_TrayButtonClicked
+            | AppEventType_MenuItemHovered
+            | AppEventType_MenuItemClicked
+            | AppEventType_NotificationClicked
+            | AppEventType_NotificationActionInvoked
+    )
+}
+
+fn no_event() -> AppEvent {
+    AppEvent {
+        type_: AppEventType_None,
+        menu_id_str: ptr::null(),
+        x: 0,
+        y: 0,
+        visible: false,
+        notification_id: 0,
+        args_str: ptr::null(),
+        button: MouseButton_MouseButtonLeft,
+        dark: false,
+    }
+}
+
+unsafe fn push_event(app: &StubApp, event: AppEvent) {
+    let mut state = app.state.lock().unwrap();
+    if state.event_mask & (1 << event.type_) == 0 {
+        drop_event(&event);
+        return;
+    }
+    if state.paused && is_user_input(event.type_) {
+        if state.pause_policy == PausePolicy_PauseBuffer {
+            state.paused_events.push_back(event);
+        } else {
+            drop_event(&event);
+        }
+        return;
+    }
+    if let Some(callback) = state.callback {
+        // The callback may call back into the stub, as a sink may on the Qt thread.
+        let user_data = state.user_data;
+        drop(state);
+        callback(user_data, event);
+        return;
+    }
+    if state.overflow_policy != OverflowPolicy_Unbounded && state.events.len() >= state.capacity {
+        if state.overflow_policy == OverflowPolicy_DropNewest || state.capacity == 0 {
+            drop_event(&event);
+            return;
+        }
+        while state.events.len() >= state.capacity {
+            if let Some(oldest) = state.events.pop_front() {
+                drop_event(&oldest);
+            }
+        }
+    }
+    state.events.push_back(event);
+}
+
+/// Returns a handle to a new stub application; null marks a closed tray.
+pub unsafe fn create_qt_app() -> *mut QtAppHandle {
+    let app = Box::new(StubApp {
+        state: Mutex::new(StubState {
+            events: VecDeque::new(),
+            capacity: 0,
+            overflow_policy: OverflowPolicy_Unbounded,
+            event_mask: !0,
+            paused: false,
+            pause_policy: PausePolicy_PauseDiscard,
+            paused_events: VecDeque::new(),
+            callback: None,
+            user_data: ptr::null_mut(),
+            menu_items: Vec::new(),
+            replacement_menu_items: None,
+            collecting_left_click_menu: false,
+        }),
+    });
+    Box::into_raw(app) as *mut QtAppHandle
+}
+
+pub unsafe fn set_app_id(_handle: *mut QtAppHandle, _id: *const c_char) {}
+
+pub unsafe fn set_organization_name(_handle: *mut QtAppHandle, _name: *const c_char) {}
+
+pub unsafe fn set_app_icon_from_data(
+    _handle: *mut QtAppHandle,
+    _data: *const c_uchar,
+    _size: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_from_data(
+    _handle: *mut QtAppHandle,
+    _data: *const c_uchar,
+    _size: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_template(
+    _handle: *mut QtAppHandle,
+    _data: *const c_uchar,
+    _size: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_themed(
+    _handle: *mut QtAppHandle,
+    _variants: *const *const c_uchar,
+    _sizes: *const usize,
+    _pixel_sizes: *const c_uint,
+    _dark: *const bool,
+    _count: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_variants(
+    _handle: *mut QtAppHandle,
+    _variants: *const *const c_uchar,
+    _sizes: *const usize,
+    _pixel_sizes: *const c_uint,
+    _count: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_rgba(
+    _handle: *mut QtAppHandle,
+    _pixels: *const c_uchar,
+    _width: c_uint,
+    _height: c_uint,
+) {
+}
+
+pub unsafe fn set_application_icon_from_data(
+    _handle: *mut QtAppHandle,
+    _data: *const c_uchar,
+    _size: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn set_fallback_icon_from_data(
+    _handle: *mut QtAppHandle,
+    _data: *const c_uchar,
+    _size: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn init_tray(_handle: *mut QtAppHandle) -> bool {
+    true
+}
+
+pub unsafe fn init_app(_handle: *mut QtAppHandle) -> bool {
+    true
+}
+
+pub unsafe fn add_app_tray(_handle: *mut QtAppHandle, _tray: *mut QtAppHandle) {}
+
+pub unsafe fn remove_app_tray(_handle: *mut QtAppHandle, _tray: *mut QtAppHandle) {}
+
+pub unsafe fn run_qt_app(
+    _handle: *mut QtAppHandle,
+    _argc: c_int,
+    _argv: *mut *mut c_char,
+) -> c_int {
+    0
+}
+
+pub unsafe fn poll_event(handle: *mut QtAppHandle) -> AppEvent {
+    stub_app(handle)
+        .and_then(|app| app.state.lock().unwrap().events.pop_front())
+        .unwrap_or_else(no_event)
+}
+
+pub unsafe fn request_quit_qt_app_safe(_handle: *mut QtAppHandle) {}
+
+pub unsafe fn cleanup_qt_app(handle: *mut QtAppHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle as *mut StubApp));
+    }
+}
+
+unsafe fn string_or_empty(text: *const c_char) -> String {
+    if text.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(text).to_string_lossy().into_owned()
+}
+
+/// Items of the left-click menu are dropped, since nothing can open it.
+pub unsafe fn add_tray_menu_item(
+    handle: *mut QtAppHandle,
+    text: *const c_char,
+    id: *const c_char,
+    options: *const MenuItemOptions,
+) {
+    let (Some(app), Some(options)) = (stub_app(handle), options.as_ref()) else {
+        return;
+    };
+    let item = StubMenuItem {
+        id: string_or_empty(id),
+        parent: string_or_empty(options.parent),
+        text: string_or_empty(text),
+        separator: options.separator,
+        enabled: options.enabled,
+        visible: options.visible,
+    };
+    let mut state = app.state.lock().unwrap();
+    if state.collecting_left_click_menu {
+        return;
+    }
+    match &mut state.replacement_menu_items {
+        Some(items) => items.push(item),
+        None => state.menu_items.push(item),
+    }
+}
+
+pub unsafe fn begin_tray_menu_replace(handle: *mut QtAppHandle) {
+    if let Some(app) = stub_app(handle) {
+        app.state.lock().unwrap().replacement_menu_items = Some(Vec::new());
+    }
+}
+
+pub unsafe fn finish_tray_menu_replace(handle: *mut QtAppHandle, apply: bool) {
+    let Some(app) = stub_app(handle) else {
+        return;
+    };
+    let mut state = app.state.lock().unwrap();
+    let items = state.replacement_menu_items.take().unwrap_or_default();
+    if apply {
+        state.menu_items = items;
+    }
+}
+
+pub unsafe fn begin_left_click_menu(handle: *mut QtAppHandle) {
+    if let Some(app) = stub_app(handle) {
+        app.state.lock().unwrap().collecting_left_click_menu = true;
+    }
+}
+
+pub unsafe fn finish_left_click_menu(handle: *mut QtAppHandle, _apply: bool) {
+    if let Some(app) = stub_app(handle) {
+        app.state.lock().unwrap().collecting_left_click_menu = false;
+    }
+}
+
+/// Returns the entries of the menu at `parent` (a path, empty for the top level) in order, for
+/// tests to check the menu built by the crate. Not part of the Qt bindings.
+pub unsafe fn stub_menu_items(handle: *mut QtAppHandle, parent: &str) -> Vec<StubMenuItem> {
+    stub_app(handle).map_or_else(Vec::new, |app| {
+        let state = app.state.lock().unwrap();
+        state
+            .menu_items
+            .iter()
+            .filter(|item| item.parent == parent)
+            .cloned()
+            .collect()
+    })
+}
+
+pub unsafe fn popup_tray_menu(_handle: *mut QtAppHandle, _x: c_int, _y: c_int) {}
+
+pub unsafe fn popup_tray_menu_at_cursor(_handle: *mut QtAppHandle) {}
+
+pub unsafe fn flush_qt_app(_handle: *mut QtAppHandle) {}
+
+pub unsafe fn get_active_menu_item(_handle: *mut QtAppHandle) -> *mut c_char {
+    ptr::null_mut()
+}
+
+/// Reports every id as found.
+pub unsafe fn set_active_menu_item(_handle: *mut QtAppHandle, _id: *const c_char) -> bool {
+    true
+}
+
+pub unsafe fn is_menu_open(_handle: *mut QtAppHandle) -> bool {
+    false
+}
+
+pub unsafe fn set_event_queue_capacity(
+    handle: *mut QtAppHandle,
+    capacity: usize,
+    policy: OverflowPolicy,
+) {
+    let Some(app) = stub_app(handle) else {
+        return;
+    };
+    let mut state = app.state.lock().unwrap();
+    state.capacity = capacity;
+    state.overflow_policy = policy;
+    if policy == OverflowPolicy_Unbounded {
+        return;
+    }
+    while state.events.len() > capacity {
+        let dropped = if policy == OverflowPolicy_DropOldest {
+            state.events.pop_front()
+        } else {
+            state.events.pop_back()
+        };
+        if let Some(dropped) = dropped {
+            drop_event(&dropped);
+        }
+    }
+}
+
+pub unsafe fn inject_event(handle: *mut QtAppHandle, event: *const AppEvent) {
+    let (Some(app), Some(event)) = (stub_app(handle), event.as_ref()) else {
+        return;
+    };
+    let mut event = *event;
+    event.menu_id_str = copy_string(event.menu_id_str);
+    event.args_str = copy_string(event.args_str);
+    push_event(app, event);
+}
+
+pub unsafe fn set_event_callback(
+    handle: *mut QtAppHandle,
+    callback: EventCallback,
+    user_data: *mut c_void,
+) {
+    let Some(app) = stub_app(handle) else {
+        return;
+    };
+    let queued = {
+        let mut state = app.state.lock().unwrap();
+        state.callback = callback;
+        state.user_data = user_data;
+        std::mem::take(&mut state.events)
+    };
+    match callback {
+        Some(callback) => {
+            for event in queued {
+                callback(user_data, event);
+            }
+        }
+        None => app.state.lock().unwrap().events = queued,
+    }
+}
+
+pub unsafe fn refresh_tray(_handle: *mut QtAppHandle) {}
+
+pub unsafe fn set_event_mask(handle: *mut QtAppHandle, mask: c_uint) {
+    if let Some(app) = stub_app(handle) {
+        app.state.lock().unwrap().event_mask = mask;
+    }
+}
+
+pub unsafe fn set_events_paused(handle: *mut QtAppHandle, paused: bool) {
+    let Some(app) = stub_app(handle) else {
+        return;
+    };
+    let held = {
+        let mut state = app.state.lock().unwrap();
+        state.paused = paused;
+        if paused {
+            return;
+        }
+        std::mem::take(&mut state.paused_events)
+    };
+    for event in held {
+        push_event(app, event);
+    }
+}
+
+pub unsafe fn set_pause_policy(handle: *mut QtAppHandle, policy: PausePolicy) {
+    let Some(app) = stub_app(handle) else {
+        return;
+    };
+    let mut state = app.state.lock().unwrap();
+    state.pause_policy = policy;
+    if policy == PausePolicy_PauseDiscard {
+        for event in std::mem::take(&mut state.paused_events) {
+            drop_event(&event);
+        }
+    }
+}
+
+pub unsafe fn set_display_name(_handle: *mut QtAppHandle, _name: *const c_char) {}
+
+pub unsafe fn set_app_style(_handle: *mut QtAppHandle, _style: *const c_char) {}
+
+pub unsafe fn set_dark_palette(_handle: *mut QtAppHandle, _enabled: bool) {}
+
+pub unsafe fn set_high_dpi(_handle: *mut QtAppHandle, _enabled: bool) {}
+
+pub unsafe fn set_quit_on_interrupt(_handle: *mut QtAppHandle, _enabled: bool) {}
+
+pub unsafe fn set_tray_wait_timeout(_handle: *mut QtAppHandle, _timeout_ms: c_int) {}
+
+/// Calls `f` with each menu item whose path is `id`, returning whether there were any.
+unsafe fn update_menu_item(
+    handle: *mut QtAppHandle,
+    id: *const c_char,
+    mut f: impl FnMut(&mut StubMenuItem),
+) -> bool {
+    let Some(app) = stub_app(handle) else {
+        return false;
+    };
+    let id = string_or_empty(id);
+    let mut found = false;
+    for item in &mut app.state.lock().unwrap().menu_items {
+        if item.has_path(&id) {
+            f(item);
+            found = true;
+        }
+    }
+    found
+}
+
+pub unsafe fn set_menu_items_enabled(
+    handle: *mut QtAppHandle,
+    ids: *const *const c_char,
+    enabled: *const bool,
+    count: usize,
+    found: *mut bool,
+) {
+    for i in 0..count {
+        let enabled = *enabled.add(i);
+        *found.add(i) = update_menu_item(handle, *ids.add(i), |item| item.enabled = enabled);
+    }
+}
+
+pub unsafe fn set_menu_item_visible(
+    handle: *mut QtAppHandle,
+    id: *const c_char,
+    visible: bool,
+) -> bool {
+    update_menu_item(handle, id, |item| item.visible = visible)
+}
+
+pub unsafe fn set_menu_item_text(
+    handle: *mut QtAppHandle,
+    id: *const c_char,
+    text: *const c_char,
+) -> bool {
+    let text = string_or_empty(text);
+    update_menu_item(handle, id, |item| item.text = text.clone())
+}
+
+pub unsafe fn has_menu_item(handle: *mut QtAppHandle, id: *const c_char) -> bool {
+    update_menu_item(handle, id, |_| {})
+}
+
+pub unsafe fn remove_menu_item(handle: *mut QtAppHandle, id: *const c_char) -> bool {
+    stub_app(handle).is_some_and(|app| {
+        app.state
+            .lock()
+            .unwrap()
+            .remove_menu_item(&string_or_empty(id))
+    })
+}
+
+/// Moves the item and everything below it as `moveMenuItem` in lib.cpp does.
+pub unsafe fn move_menu_item(handle: *mut QtAppHandle, id: *const c_char, index: usize) -> bool {
+    let Some(app) = stub_app(handle) else {
+        return false;
+    };
+    let id = string_or_empty(id);
+    let mut state = app.state.lock().unwrap();
+    let Some(parent) = state
+        .menu_items
+        .iter()
+        .find(|item| item.has_path(&id))
+        .map(|item| item.parent.clone())
+    else {
+        return false;
+    };
+    let prefix = format!("{}/", id);
+    let (moved, mut rest): (Vec<_>, Vec<_>) = std::mem::take(&mut state.menu_items)
+        .into_iter()
+        .partition(|item| {
+            let path = item.path();
+            path == id || path.starts_with(&prefix)
+        });
+    // The items in a submenu come after it, wherever the submenu itself is.
+    let before = rest
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.parent == parent)
+        .nth(index)
+        .map_or(rest.len(), |(position, _)| position);
+    rest.splice(before..before, moved);
+    state.menu_items = rest;
+    true
+}
+
+pub unsafe fn remove_menu_items_by_prefix(
+    handle: *mut QtAppHandle,
+    prefix: *const c_char,
+) -> usize {
+    let Some(app) = stub_app(handle) else {
+        return 0;
+    };
+    let prefix = string_or_empty(prefix);
+    let mut state = app.state.lock().unwrap();
+    let paths: Vec<String> = state
+        .menu_items
+        .iter()
+        .filter(|item| !item.separator)
+        .map(StubMenuItem::path)
+        .filter(|path| path.starts_with(&prefix))
+        .collect();
+    // Items in a removed submenu are gone by the time their own path comes up.
+    paths
+        .iter()
+        .filter(|path| state.remove_menu_item(path))
+        .count()
+}
+
+/// Reports every id as unchecked after the toggle.
+pub unsafe fn toggle_menu_item_checked(_handle: *mut QtAppHandle, _id: *const c_char) -> c_int {
+    0
+}
+
+/// Reports every id as a menu item.
+pub unsafe fn set_menu_item_check_state(
+    _handle: *mut QtAppHandle,
+    _id: *const c_char,
+    _state: CheckState,
+) -> bool {
+    true
+}
+
+pub unsafe fn persist_menu_item_checked(_handle: *mut QtAppHandle, _id: *const c_char) {}
+
+/// Reports every id as a progress item.
+pub unsafe fn set_menu_item_progress(
+    _handle: *mut QtAppHandle,
+    _id: *const c_char,
+    _value: c_int,
+) -> bool {
+    true
+}
+
+pub unsafe fn set_busy(_handle: *mut QtAppHandle, _busy: bool) {}
+
+pub unsafe fn set_busy_frames(
+    _handle: *mut QtAppHandle,
+    _frames: *const *const c_uchar,
+    _sizes: *const usize,
+    _count: usize,
+    _format: *const c_char,
+) {
+}
+
+pub unsafe fn request_attention(_handle: *mut QtAppHandle, _count: c_int) {}
+
+/// Reports every process as the primary instance.
+pub unsafe fn acquire_single_instance(
+    _handle: *mut QtAppHandle,
+    _key: *const c_char,
+    _args: *const c_char,
+) -> bool {
+    true
+}
+
+pub unsafe fn show_notification(
+    _handle: *mut QtAppHandle,
+    _spec: *const NotificationSpec,
+    _callback: NotificationCallback,
+    _user_data: *mut c_void,
+) -> c_uint {
+    0
+}
+
+pub unsafe fn get_tray_icon_ptr(_handle: *mut QtAppHandle) -> *mut c_void {
+    ptr::null_mut()
+}
+
+pub unsafe fn set_menu_font(_handle: *mut QtAppHandle, _family: *const c_char, _point_size: f64) {}
+
+pub unsafe fn set_tear_off_enabled(_handle: *mut QtAppHandle, _enabled: bool) {}
+
+pub unsafe fn set_button_behavior(
+    _handle: *mut QtAppHandle,
+    _button: MouseButton,
+    _behavior: ButtonBehavior,
+    _item_id: *const c_char,
+) {
+}
+
+pub unsafe fn set_tray_icon_shown(_handle: *mut QtAppHandle, _shown: bool) {}
+
+pub unsafe fn set_accessible_name(_handle: *mut QtAppHandle, _name: *const c_char) {}
+
+pub unsafe fn set_preferred_icon_size(_handle: *mut QtAppHandle, _size: c_int) {}
+
+pub unsafe fn is_system_tray_available(_handle: *mut QtAppHandle) -> bool {
+    false
+}
+
+pub unsafe fn get_screens(
+    _handle: *mut QtAppHandle,
+    _screens: *mut ScreenInfo,
+    _capacity: usize,
+) -> usize {
+    0
+}
+
+pub unsafe fn open_path(_handle: *mut QtAppHandle, _path: *const c_char) -> bool {
+    false
+}
+
+pub unsafe fn confirm_dialog(
+    _handle: *mut QtAppHandle,
+    _title: *const c_char,
+    _text: *const c_char,
+) -> c_int {
+    -1
+}
+
+/// Reports the stub as the offscreen platform.
+pub unsafe fn get_platform_name() -> *mut c_char {
+    c"offscreen".to_owned().into_raw()
+}
+
+pub unsafe fn read_setting(
+    _organization: *const c_char,
+    _application: *const c_char,
+    _key: *const c_char,
+) -> *mut c_char {
+    ptr::null_mut()
+}
+
+/// Reports every write as failed, since nothing is stored.
+pub unsafe fn write_setting(
+    _organization: *const c_char,
+    _application: *const c_char,
+    _key: *const c_char,
+    _value: *const c_char,
+) -> bool {
+    false
+}
+
+pub unsafe fn last_error(_handle: *mut QtAppHandle) -> *mut c_char {
+    ptr::null_mut()
+}
+
+/// Releases a string allocated by the stub, which uses Rust's allocator instead of `malloc`.
+pub unsafe fn free_char_ptr(ptr: *const c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr as *mut c_char));
+    }
+}
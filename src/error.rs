@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,4 +9,22 @@ pub enum SystemTrayError {
     Ffi(#[from] std::ffi::NulError),
     #[error("Failed to poll event: {0}")]
     PollEventError(String),
-}
\ No newline at end of file
+    #[error("This setting must be applied before the system tray is started")]
+    AlreadyStarted,
+    #[error("Unknown menu item id: {0}")]
+    UnknownMenuItem(String),
+    #[error("Unknown menu item ids: {}", .0.join(", "))]
+    UnknownMenuItems(Vec<String>),
+    #[error("Failed to open path: {}", .0.display())]
+    OpenPath(PathBuf),
+    #[error("Failed to initialize the system tray: {0}")]
+    Init(String),
+    #[error("The system tray is not running")]
+    NotRunning,
+    #[error("Failed to parse event: {0}")]
+    ParseEvent(String),
+    #[error("Failed to write the setting: {0}")]
+    Settings(String),
+    #[error("The system tray has been closed")]
+    Closed,
+}
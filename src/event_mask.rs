@@ -0,0 +1,108 @@
+//! The `EventMask` set of event kinds, for `SystemTray::set_event_mask`.
+
+use crate::bind;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+/// A set of event kinds, choosing which events `SystemTray::set_event_mask` lets through.
+///
+/// Masks are combined with `|`, like bitflags. Each constant is named after the `Event` variant
+/// it stands for; `MENU_ITEM_CLICKED` also covers `Event::MenuItemClickedWithData`.
+///
+/// # Examples
+///
+/// ```
+/// use system_tray::EventMask;
+///
+/// let mask = EventMask::MENU_ITEM_CLICKED | EventMask::QUIT;
+/// assert!(mask.contains(EventMask::QUIT));
+/// assert!(!mask.contains(EventMask::MENU_ITEM_HOVERED));
+/// assert_eq!(EventMask::ALL & !EventMask::MENU_ITEM_HOVERED & mask, mask);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventMask(u32);
+
+impl EventMask {
+    /// No events at all.
+    pub const NONE: Self = Self(0);
+    /// Every kind of event. This is the default.
+    pub const ALL: Self = Self(!0);
+    /// `Event::TrayClicked`.
+    pub const TRAY_CLICKED: Self = Self::raw(bind::AppEventType_TrayClicked);
+    /// `Event::TrayDoubleClicked`.
+    pub const TRAY_DOUBLE_CLICKED: Self = Self::raw(bind::AppEventType_TrayDoubleClicked);
+    /// `Event::TrayButtonClicked`.
+    pub const TRAY_BUTTON_CLICKED: Self = Self::raw(bind::AppEventType_TrayButtonClicked);
+    /// `Event::MenuItemClicked` and `Event::MenuItemClickedWithData`.
+    pub const MENU_ITEM_CLICKED: Self = Self::raw(bind::AppEventType_MenuItemClicked);
+    /// `Event::MenuItemHovered`.
+    pub const MENU_ITEM_HOVERED: Self = Self::raw(bind::AppEventType_MenuItemHovered);
+    /// `Event::MenuAboutToShow`.
+    pub const MENU_ABOUT_TO_SHOW: Self = Self::raw(bind::AppEventType_MenuAboutToShow);
+    /// `Event::MenuClosed`.
+    pub const MENU_CLOSED: Self = Self::raw(bind::AppEventType_MenuClosed);
+    /// `Event::Quit`.
+    pub const QUIT: Self = Self::raw(bind::AppEventType_Quit);
+    /// `Event::TrayReconnected`.
+    pub const TRAY_RECONNECTED: Self = Self::raw(bind::AppEventType_TrayReconnected);
+    /// `Event::VisibilityChanged`.
+    pub const VISIBILITY_CHANGED: Self = Self::raw(bind::AppEventType_VisibilityChanged);
+    /// `Event::NotificationClicked`.
+    pub const NOTIFICATION_CLICKED: Self = Self::raw(bind::AppEventType_NotificationClicked);
+    /// `Event::NotificationAction`.
+    pub const NOTIFICATION_ACTION: Self = Self::raw(bind::AppEventType_NotificationActionInvoked);
+    /// `Event::SecondInstanceLaunched`.
+    pub const SECOND_INSTANCE_LAUNCHED: Self = Self::raw(bind::AppEventType_SecondInstanceLaunched);
+    /// `Event::ColorSchemeChanged`.
+    pub const COLOR_SCHEME_CHANGED: Self = Self::raw(bind::AppEventType_ColorSchemeChanged);
+
+    /// The mask of the backend's event type, whose value is the bit's position.
+    const fn raw(event_type: bind::AppEventType) -> Self {
+        Self(1 << event_type)
+    }
+
+    /// Returns whether every kind in `other` is also in `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the mask in the form passed to the backend.
+    pub(crate) fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for EventMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for EventMask {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for EventMask {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl Not for EventMask {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
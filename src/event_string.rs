@@ -0,0 +1,182 @@
+//! The stable string representation of `Event`, for logging and IPC.
+//!
+//! An event is written as its kind followed by its fields, separated by colons, e.g.
+//! `tray:double:10:20` or `menu:file/open`. Text fields are percent-encoded (`%` as `%25`, `:`
+//! as `%3A`, and control characters likewise), so ids containing colons still round-trip, and
+//! payloads are written as lowercase hex.
+
+use crate::{Error, Event, MouseButton};
+use std::{fmt, str::FromStr};
+
+impl fmt::Display for Event {
+    /// Writes the event in its stable string form, which `str::parse` reads back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Event;
+    ///
+    /// let event = Event::MenuItemClicked("open:recent".into());
+    /// assert_eq!(event.to_string(), "menu:open%3Arecent");
+    /// assert_eq!("menu:open%3Arecent".parse::<Event>().unwrap(), event);
+    /// assert_eq!(
+    ///     Event::TrayDoubleClicked { x: 10, y: 20 }.to_string(),
+    ///     "tray:double:10:20"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::None => f.write_str("none"),
+            Event::TrayClicked { x, y } => write!(f, "tray:click:{}:{}", x, y),
+            Event::TrayDoubleClicked { x, y } => write!(f, "tray:double:{}:{}", x, y),
+            Event::TrayButtonClicked { button, x, y } => {
+                let button = match button {
+                    MouseButton::Left => "left",
+                    MouseButton::Right => "right",
+                    MouseButton::Middle => "middle",
+                };
+                write!(f, "tray:button:{}:{}:{}", button, x, y)
+            }
+            Event::MenuItemClicked(id) => write!(f, "menu:{}", escape(id)),
+            Event::MenuItemClickedWithData { id, data } => {
+                write!(f, "menu:{}:", escape(id))?;
+                data.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+            Event::Quit => f.write_str("quit"),
+            Event::TrayReconnected => f.write_str("tray:reconnected"),
+            Event::VisibilityChanged { visible } => write!(f, "tray:visible:{}", visible),
+            Event::NotificationClicked { id } => write!(f, "notification:{}", id),
+            Event::SecondInstanceLaunched { args } => {
+                f.write_str("instance")?;
+                args.iter()
+                    .try_for_each(|arg| write!(f, ":{}", escape(arg)))
+            }
+            Event::MenuAboutToShow => f.write_str("menu-open"),
+            Event::MenuItemHovered(id) => write!(f, "menu-hover:{}", escape(id)),
+            Event::MenuClosed => f.write_str("menu-closed"),
+            Event::NotificationAction {
+                notification_id,
+                action_id,
+            } => write!(f, "notification:{}:{}", notification_id, escape(action_id)),
+            Event::ColorSchemeChanged { dark } => write!(f, "color-scheme:dark:{}", dark),
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = Error;
+
+    /// Parses the string form written by `Display`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::ParseEvent` if `s` isn't a valid event string.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::ParseEvent(s.to_string());
+        let mut fields = s.split(':');
+        let kind = fields.next().unwrap_or_default();
+        let fields: Vec<&str> = fields.collect();
+        let event = match (kind, fields.as_slice()) {
+            ("none", []) => Event::None,
+            ("tray", ["click", x, y]) => Event::TrayClicked {
+                x: parse_field(x, s)?,
+                y: parse_field(y, s)?,
+            },
+            ("tray", ["double", x, y]) => Event::TrayDoubleClicked {
+                x: parse_field(x, s)?,
+                y: parse_field(y, s)?,
+            },
+            ("tray", ["button", button, x, y]) => Event::TrayButtonClicked {
+                button: match *button {
+                    "left" => MouseButton::Left,
+                    "right" => MouseButton::Right,
+                    "middle" => MouseButton::Middle,
+                    _ => return Err(invalid()),
+                },
+                x: parse_field(x, s)?,
+                y: parse_field(y, s)?,
+            },
+            ("tray", ["reconnected"]) => Event::TrayReconnected,
+            ("tray", ["visible", visible]) => Event::VisibilityChanged {
+                visible: parse_field(visible, s)?,
+            },
+            ("menu", [id]) => Event::MenuItemClicked(unescape(id).ok_or_else(invalid)?.into()),
+            ("menu", [id, data]) => Event::MenuItemClickedWithData {
+                id: unescape(id).ok_or_else(invalid)?.into(),
+                data: decode_hex(data).ok_or_else(invalid)?,
+            },
+            ("quit", []) => Event::Quit,
+            ("notification", [id]) => Event::NotificationClicked {
+                id: parse_field(id, s)?,
+            },
+            ("notification", [id, action_id]) => Event::NotificationAction {
+                notification_id: parse_field(id, s)?,
+                action_id: unescape(action_id).ok_or_else(invalid)?,
+            },
+            ("instance", args) => Event::SecondInstanceLaunched {
+                args: args
+                    .iter()
+                    .map(|arg| unescape(arg))
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+            },
+            ("menu-open", []) => Event::MenuAboutToShow,
+            ("menu-hover", [id]) => {
+                Event::MenuItemHovered(unescape(id).ok_or_else(invalid)?.into())
+            }
+            ("menu-closed", []) => Event::MenuClosed,
+            ("color-scheme", ["dark", dark]) => Event::ColorSchemeChanged {
+                dark: parse_field(dark, s)?,
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(event)
+    }
+}
+
+/// Parses a number or boolean field of the event string `s`.
+fn parse_field<T: FromStr>(field: &str, s: &str) -> Result<T, Error> {
+    field.parse().map_err(|_| Error::ParseEvent(s.to_string()))
+}
+
+/// Percent-encodes the characters that would break up or garble a field.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '%' || c == ':' || c.is_ascii_control() {
+            escaped.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Reverses `escape`, returning `None` for malformed escapes.
+fn unescape(field: &str) -> Option<String> {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = field.get(i + 1..i + 3)?;
+            unescaped.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(unescaped).ok()
+}
+
+/// Reads the lowercase or uppercase hex written for payloads.
+fn decode_hex(field: &str) -> Option<Vec<u8>> {
+    if !field.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..field.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(field.get(i..i + 2)?, 16).ok())
+        .collect()
+}
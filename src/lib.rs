@@ -2,15 +2,44 @@
 //! It allows you to create a system tray icon, add menu items to it, set its icon,
 //! and handle events such as clicks and menu item selections.
 
+#[cfg_attr(feature = "stub", path = "bind_stub.rs")]
 mod bind;
 mod error;
+mod event_mask;
+mod event_string;
+mod menu_id;
+#[cfg(feature = "async")]
+mod notification;
+#[cfg(feature = "async")]
+mod stop;
+#[cfg(feature = "testing")]
+mod testing;
 
 pub use error::SystemTrayError as Error;
+pub use event_mask::EventMask;
+pub use menu_id::MenuId;
+#[cfg(feature = "async")]
+pub use notification::NotificationOutcome;
 use std::{
-    ffi::{c_char, CString},
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    path::Path,
+    ptr,
+    sync::{Arc, Mutex, MutexGuard, RwLock, Weak},
     thread::JoinHandle,
+    time::{Duration, Instant},
 };
+#[cfg(feature = "testing")]
+pub use testing::RecordingSink;
+
+/// The icon shown by trays that haven't been given one, so they don't look broken.
+const FALLBACK_ICON: &[u8] = include_bytes!("../icon.svg");
+
+/// The pause between polls suggested by `SystemTray::recommended_poll_interval`.
+const RECOMMENDED_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Separates the arguments passed between instances by `SystemTray::acquire_single_instance`.
+const ARGS_SEPARATOR: char = '\x1f';
 
 /// A transparent wrapper around a raw `bind::QtAppHandle` pointer.
 ///
@@ -38,208 +67,4921 @@ impl SafeQtAppHandle {
     }
 }
 
+/// Copies a string allocated by the C++ side into an owned `String` and frees the original.
+///
+/// # Safety
+///
+/// `ptr` must be null or a pointer returned by the C++ side that hasn't been freed yet.
+unsafe fn take_backend_string(ptr: *mut c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let string = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    bind::free_char_ptr(ptr);
+    Some(string)
+}
+
+/// Synthesizes double clicks from consecutive single clicks for platforms whose tray host never
+/// delivers a native double click (e.g. some Wayland compositors).
+struct DoubleClickEmulation {
+    enabled: bool,
+    interval: Duration,
+    last_click: Option<Instant>,
+}
+
+impl DoubleClickEmulation {
+    /// The default window in which two clicks count as a double click, matching Qt's default
+    /// double click interval.
+    const DEFAULT_INTERVAL: Duration = Duration::from_millis(400);
+
+    fn new() -> Self {
+        Self {
+            enabled: false,
+            interval: Self::DEFAULT_INTERVAL,
+            last_click: None,
+        }
+    }
+
+    /// Translates a single click into either `Event::TrayClicked` or, if the previous click was
+    /// recent enough, a synthesized `Event::TrayDoubleClicked`.
+    fn on_click(&mut self, x: i32, y: i32) -> Event {
+        if !self.enabled {
+            return Event::TrayClicked { x, y };
+        }
+        let now = Instant::now();
+        match self.last_click.take() {
+            Some(last) if now.duration_since(last) <= self.interval => {
+                Event::TrayDoubleClicked { x, y }
+            }
+            _ => {
+                self.last_click = Some(now);
+                Event::TrayClicked { x, y }
+            }
+        }
+    }
+
+    /// Forgets the pending click when a native double click arrives, so it isn't reported twice.
+    fn on_double_click(&mut self, x: i32, y: i32) -> Event {
+        self.last_click = None;
+        Event::TrayDoubleClicked { x, y }
+    }
+}
+
 /// Represents the various events that can be received from the system tray.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Event {
     /// No event occurred.
     None,
     /// The system tray icon was clicked.
-    TrayClicked,
-    /// The system tray icon was double-clicked.
-    TrayDoubleClicked,
+    ///
+    /// `x` and `y` are the screen coordinates of the cursor at the time of the click, which is
+    /// handy for positioning popups. Platforms that don't expose the global cursor position
+    /// (e.g. some Wayland compositors) may report a stale position or `(0, 0)`.
+    TrayClicked {
+        /// The horizontal screen coordinate of the click.
+        x: i32,
+        /// The vertical screen coordinate of the click.
+        y: i32,
+    },
+    /// The system tray icon was double-clicked, at the given screen coordinates (see
+    /// `Event::TrayClicked`).
+    TrayDoubleClicked {
+        /// The horizontal screen coordinate of the click.
+        x: i32,
+        /// The vertical screen coordinate of the click.
+        y: i32,
+    },
+    /// The system tray icon was clicked with the right or middle button, at the given screen
+    /// coordinates (see `Event::TrayClicked`).
+    ///
+    /// Only emitted for buttons set to `ButtonBehavior::EmitClick` with
+    /// `SystemTray::set_button_event`; left clicks are reported as `Event::TrayClicked`.
+    TrayButtonClicked {
+        /// The button that was clicked.
+        button: MouseButton,
+        /// The horizontal screen coordinate of the click.
+        x: i32,
+        /// The vertical screen coordinate of the click.
+        y: i32,
+    },
     /// A menu item in the system tray was clicked, identified by its ID.
-    MenuItemClicked(String),
+    MenuItemClicked(MenuId),
+    /// A menu item carrying a data payload (see `Menu::with_data`) was clicked.
+    MenuItemClickedWithData {
+        /// The ID of the clicked menu item.
+        id: MenuId,
+        /// The payload that was attached to the menu item.
+        data: Vec<u8>,
+    },
+    /// The mouse or keyboard moved onto a menu item, identified by its ID, e.g. to update a
+    /// status area or load a preview while the user goes through the menu.
+    ///
+    /// This is emitted from `QAction::hovered`, for submenus too, each time an item becomes
+    /// highlighted, so moving through the menu emits one event per item passed over.
+    MenuItemHovered(MenuId),
+    /// The Qt application is about to quit, either because `stop` was called or because the
+    /// desktop session is ending (e.g. on logout).
+    ///
+    /// This is emitted from `QCoreApplication::aboutToQuit`, right before the Qt event loop
+    /// returns, so the window to react is short: save state promptly. On session logout the
+    /// session manager usually grants only a few seconds before the process is killed. Plain
+    /// `SIGTERM` is not translated by Qt and terminates the process without this event.
+    Quit,
+    /// The systray host reappeared after being lost, e.g. because the desktop panel restarted.
+    ///
+    /// Qt re-registers the icon on its own, but state such as the icon or tooltip may have been
+    /// lost and should be re-applied. The host is checked about once per second. It's also
+    /// emitted when a host that wasn't up at startup appears (see
+    /// `TrayApp::set_tray_wait_timeout`).
+    TrayReconnected,
+    /// The tray icon became visible or invisible to the user.
+    ///
+    /// Qt doesn't report whether a panel actually shows the icon (e.g. when it auto-hides), so
+    /// this is best effort: it tracks whether a systray host is available to show the icon at
+    /// all, checked about once per second. `visible` becomes false when the host goes away and
+    /// true again when it reappears (alongside `Event::TrayReconnected`).
+    VisibilityChanged {
+        /// Whether the icon can currently be shown to the user.
+        visible: bool,
+    },
+    /// A notification shown with `SystemTray::notify` was clicked before it disappeared.
+    NotificationClicked {
+        /// The id returned by `SystemTray::notify`.
+        id: u32,
+    },
+    /// Another instance of the app was launched while this one holds the single-instance key
+    /// (see `SystemTray::acquire_single_instance`).
+    SecondInstanceLaunched {
+        /// The command-line arguments of the new instance, including the program name.
+        args: Vec<String>,
+    },
+    /// The tray's context menu is about to be shown, e.g. to refresh its content.
+    MenuAboutToShow,
+    /// The tray's context menu was closed, with or without an item being clicked. Together
+    /// with `Event::MenuAboutToShow` it brackets the time the menu is visible. When the menu
+    /// closes because an item was clicked, this arrives before the click's event.
+    MenuClosed,
+    /// A button of a notification shown with `SystemTray::show_notification` was clicked.
+    NotificationAction {
+        /// The id returned by `SystemTray::show_notification`.
+        notification_id: u32,
+        /// The id passed to `Notification::action` for the button.
+        action_id: String,
+    },
+    /// The desktop switched between a light and a dark color scheme.
+    ///
+    /// This is emitted from `QStyleHints::colorSchemeChanged`, which needs Qt 6.5 or later;
+    /// with older Qt it never arrives. An icon set with `SystemTray::set_icon_themed` is
+    /// switched on its own before the event is queued.
+    ColorSchemeChanged {
+        /// Whether the new color scheme is dark.
+        dark: bool,
+    },
+}
+
+/// Receives events pushed by the Qt thread, as an alternative to polling.
+///
+/// See `SystemTray::set_event_sink`. Since any type implementing the trait will do, events can
+/// be forwarded to whatever channel or async runtime the application uses.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::sync::{mpsc, Arc, Mutex};
+/// use system_tray::{Event, EventSink, SystemTray};
+///
+/// struct ChannelSink(Mutex<mpsc::Sender<Event>>);
+///
+/// impl EventSink for ChannelSink {
+///     fn handle(&self, event: Event) {
+///         let _ = self.0.lock().unwrap().send(event);
+///     }
+/// }
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let tray = SystemTray::new("MyOrg", "MyApp");
+/// tray.set_event_sink(Arc::new(ChannelSink(Mutex::new(sender))))?;
+/// tray.start();
+/// for event in receiver {
+///     println!("{:?}", event);
+/// }
+/// # Ok::<(), system_tray::Error>(())
+/// ```
+pub trait EventSink {
+    /// Called on the Qt thread with each event.
+    ///
+    /// The Qt event loop is blocked until this returns, so it should be quick. Calling back
+    /// into the `SystemTray` from here can deadlock with other threads using it; forward the
+    /// event to another thread instead.
+    fn handle(&self, event: Event);
+}
+
+/// Determines what happens to new events once the event queue is full.
+///
+/// See `SystemTray::set_event_queue_capacity`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued event to make room for the new one.
+    DropOldest,
+    /// Discard the new event, keeping the queued ones.
+    DropNewest,
+    /// Never discard events; the queue grows without limit. This is the default.
+    #[default]
+    Unbounded,
+}
+
+impl OverflowPolicy {
+    fn to_raw(self) -> bind::OverflowPolicy {
+        match self {
+            Self::DropOldest => bind::OverflowPolicy_DropOldest,
+            Self::DropNewest => bind::OverflowPolicy_DropNewest,
+            Self::Unbounded => bind::OverflowPolicy_Unbounded,
+        }
+    }
+}
+
+/// A mouse button clicked on the tray icon.
+///
+/// See `SystemTray::set_button_event`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MouseButton {
+    /// The primary button, usually the left one.
+    Left,
+    /// The secondary button, usually the right one.
+    Right,
+    /// The middle button or wheel.
+    Middle,
+}
+
+impl MouseButton {
+    fn to_raw(self) -> bind::MouseButton {
+        match self {
+            Self::Left => bind::MouseButton_MouseButtonLeft,
+            Self::Right => bind::MouseButton_MouseButtonRight,
+            Self::Middle => bind::MouseButton_MouseButtonMiddle,
+        }
+    }
+
+    fn from_raw(raw: bind::MouseButton) -> Self {
+        match raw {
+            bind::MouseButton_MouseButtonRight => Self::Right,
+            bind::MouseButton_MouseButtonMiddle => Self::Middle,
+            _ => Self::Left,
+        }
+    }
+}
+
+/// What clicking the tray icon with a mouse button does.
+///
+/// See `SystemTray::set_button_event`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ButtonBehavior {
+    /// Emit `Event::TrayClicked` for the left button, or `Event::TrayButtonClicked` for the
+    /// others.
+    EmitClick,
+    /// Open the context menu at the cursor.
+    OpenMenu,
+    /// Trigger the menu item with this id (a path, see `Menu::item`), as if it was clicked.
+    /// Nothing happens if there is no such item or it is disabled.
+    TriggerItem(String),
+    /// Do nothing.
+    Ignore,
+}
+
+/// The windowing system backend Qt uses, as returned by `backend`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Backend {
+    /// X11, where the tray is either an XEmbed icon or a StatusNotifierItem over DBus.
+    Xcb,
+    /// A Wayland session, where the tray is a StatusNotifierItem over DBus.
+    Wayland,
+    /// The Windows notification area.
+    Windows,
+    /// The macOS menu bar.
+    Cocoa,
+    /// No display at all, e.g. `QT_QPA_PLATFORM=offscreen` in CI, or the `stub` backend.
+    Offscreen,
+    /// Any other Qt platform plugin, by name.
+    Other(String),
+}
+
+/// Returns the windowing system backend Qt uses for the tray, so that apps can work around
+/// known per-backend quirks.
+///
+/// Once a `TrayApp` has started, this is the platform plugin actually in use. Before that, it
+/// is predicted the way Qt picks the plugin: from `QT_QPA_PLATFORM` if set, otherwise from the
+/// operating system and, on Linux, whether `WAYLAND_DISPLAY` is set.
+///
+/// # Examples
+///
+/// ```
+/// use system_tray::Backend;
+///
+/// if system_tray::backend() == Backend::Wayland {
+///     // Wayland compositors may never report double clicks.
+/// }
+/// ```
+pub fn backend() -> Backend {
+    let name = unsafe { take_backend_string(bind::get_platform_name()) }.unwrap_or_default();
+    match name.as_str() {
+        "xcb" => Backend::Xcb,
+        "wayland" | "wayland-egl" => Backend::Wayland,
+        "windows" => Backend::Windows,
+        "cocoa" => Backend::Cocoa,
+        "offscreen" => Backend::Offscreen,
+        _ => Backend::Other(name),
+    }
+}
+
+/// A rectangle in device-independent pixels, in virtual desktop coordinates.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Rect {
+    /// The horizontal coordinate of the left edge.
+    pub x: i32,
+    /// The vertical coordinate of the top edge.
+    pub y: i32,
+    /// The width.
+    pub width: i32,
+    /// The height.
+    pub height: i32,
+}
+
+impl Rect {
+    /// Returns whether the point `(x, y)` lies inside the rectangle.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+/// A screen attached to the system, as returned by `SystemTray::screens`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenInfo {
+    /// The screen's full area.
+    pub geometry: Rect,
+    /// The part of the screen not covered by panels, docks or taskbars. Popups placed inside it
+    /// stay fully visible.
+    pub available_geometry: Rect,
+    /// Logical dots per inch, which Qt uses to scale fonts.
+    pub dpi: f64,
+    /// The number of physical pixels per device-independent pixel, e.g. 2.0 on a HiDPI screen.
+    pub scale_factor: f64,
+    /// Whether this is the primary screen.
+    pub primary: bool,
+}
+
+impl ScreenInfo {
+    fn from_raw(raw: &bind::ScreenInfo) -> Self {
+        ScreenInfo {
+            geometry: Rect {
+                x: raw.x,
+                y: raw.y,
+                width: raw.width,
+                height: raw.height,
+            },
+            available_geometry: Rect {
+                x: raw.available_x,
+                y: raw.available_y,
+                width: raw.available_width,
+                height: raw.available_height,
+            },
+            dpi: raw.logical_dpi,
+            scale_factor: raw.device_pixel_ratio,
+            primary: raw.primary,
+        }
+    }
+}
+
+/// Determines what happens to user input events while event delivery is paused.
+///
+/// See `SystemTray::pause_events`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum PausePolicy {
+    /// Drop the events, as if the user hadn't clicked. This is the default.
+    #[default]
+    Discard,
+    /// Hold the events back and deliver them in order once delivery resumes.
+    Buffer,
+}
+
+impl PausePolicy {
+    fn to_raw(self) -> bind::PausePolicy {
+        match self {
+            Self::Discard => bind::PausePolicy_PauseDiscard,
+            Self::Buffer => bind::PausePolicy_PauseBuffer,
+        }
+    }
+}
+
+/// What `SystemTray::stop_with` stops.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum StopScope {
+    /// Remove only this tray's icon and menu, leaving the shared Qt application and its other
+    /// trays running.
+    ThisTray,
+    /// Quit the whole Qt application, like `SystemTray::stop`. This is the default.
+    #[default]
+    Application,
+}
+
+/// Stops the Qt event loop when dropped, for tying a tray's lifetime to a scope.
+///
+/// Returned by `SystemTray::start_scoped` and `TrayApp::start_scoped`. Dropping the guard is
+/// the same as calling `stop`; the trays themselves stay usable and can be started again.
+#[must_use = "the event loop stops as soon as the guard is dropped"]
+pub struct StartedTray {
+    app: TrayApp,
+}
+
+impl Drop for StartedTray {
+    /// Stops the event loop, waiting for the Qt thread to finish.
+    fn drop(&mut self) {
+        self.app.stop();
+    }
+}
+
+/// The Qt application shared by one or more tray icons.
+///
+/// Qt allows a single `QApplication` per process, running one event loop for all of its tray
+/// icons. A `TrayApp` represents it explicitly: tray icons are spawned from it with `tray`, and
+/// it owns the event loop and its lifecycle. `SystemTray::new` creates a private `TrayApp` for a
+/// single tray icon.
+///
+/// Clones refer to the same application. The event loop is stopped once the last clone, and
+/// every tray spawned from it, has been dropped.
+#[derive(Clone)]
+pub struct TrayApp {
+    handle: Arc<Mutex<SafeQtAppHandle>>,
+    instance: Arc<Mutex<Option<JoinHandle<()>>>>,
+    exit_code: Arc<Mutex<Option<i32>>>,
+    organization: String,
+    app_id: String,
+    display_name: Arc<Mutex<Option<String>>>,
+    initialized: Arc<Mutex<bool>>,
+    trays: Arc<Mutex<TrayRegistry>>,
+}
+
+/// Identifies a tray icon among those of its `TrayApp`, e.g. in the events returned by
+/// `TrayApp::poll_event`. See `SystemTray::id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrayId(u64);
+
+/// The trays spawned from a `TrayApp`, polled by `TrayApp::poll_event`.
+///
+/// Only weak references are kept, so the trays are still freed when the last clone is dropped.
+/// `SystemTray`'s `Drop` locks the registry while checking whether it's the last clone, so the
+/// references a poll upgrades can't make it skip the cleanup.
+#[derive(Default)]
+struct TrayRegistry {
+    next_id: u64,
+    // The index of the tray to poll first, so that a busy tray doesn't starve the others.
+    next_poll: usize,
+    trays: Vec<RegisteredTray>,
+}
+
+struct RegisteredTray {
+    id: TrayId,
+    handle: Weak<Mutex<SafeQtAppHandle>>,
+    payloads: Weak<Mutex<HashMap<String, Vec<u8>>>>,
+    double_click: Weak<Mutex<DoubleClickEmulation>>,
 }
 
 /// Represents the system tray icon and its associated application.
 ///
-/// This struct manages the underlying Qt application instance and its lifecycle.
+/// Each `SystemTray` has its own icon, menu and event queue, and runs on the event loop of the
+/// `TrayApp` it was spawned from.
+///
+/// Its methods may be called from any thread, before or after `start`. Until the event loop
+/// runs, changes such as menu items and icons are stored and applied when the tray is set up;
+/// afterwards, they are applied on the Qt thread in the order they were made. Calls made while
+/// the event loop is starting or stopping wait for it to finish doing so, so they never race
+/// the setup or teardown of the tray.
 #[derive(Clone)]
 pub struct SystemTray {
     handle: Arc<Mutex<SafeQtAppHandle>>,
-    instance: Arc<Mutex<Option<JoinHandle<()>>>>,
+    app: TrayApp,
+    id: TrayId,
+    payloads: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    double_click: Arc<Mutex<DoubleClickEmulation>>,
+    event_sink: Arc<Mutex<Option<Box<SinkContext>>>>,
+    // Reused for the strings of frequent updates such as `set_progress`, so that they don't
+    // allocate once it has grown large enough.
+    c_buffer: Arc<Mutex<Vec<u8>>>,
+    // Held shared by dialogs, which run without the handle locked, and exclusively by `close`
+    // so that it doesn't free the handle under them.
+    dialogs: Arc<RwLock<()>>,
+}
+
+/// Where a menu item is placed in the native application menu on macOS.
+///
+/// See `Menu::role`. Other platforms have no application menu and ignore the role.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum MenuRole {
+    /// Let Qt guess from the item's text, e.g. moving "About" or "Quit" items. This is the
+    /// default.
+    #[default]
+    TextHeuristic,
+    /// Keep the item where it is, whatever its text.
+    None,
+    /// The "About" item of the application menu.
+    About,
+    /// The "Preferences..." item of the application menu.
+    Preferences,
+    /// The "Quit" item of the application menu.
+    Quit,
+}
+
+impl MenuRole {
+    fn to_raw(self) -> bind::MenuRole {
+        match self {
+            Self::TextHeuristic => bind::MenuRole_MenuRoleTextHeuristic,
+            Self::None => bind::MenuRole_MenuRoleNone,
+            Self::About => bind::MenuRole_MenuRoleAbout,
+            Self::Preferences => bind::MenuRole_MenuRolePreferences,
+            Self::Quit => bind::MenuRole_MenuRoleQuit,
+        }
+    }
+}
+
+/// Where the keyboard shortcut of a menu item is active, see `Menu::shortcut_context`.
+///
+/// Shortcuts are never global hotkeys: they only fire while a window of the application is
+/// active, such as the open tray menu or a dialog shown with `SystemTray::confirm`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ShortcutContext {
+    /// Only while the menu holding the item has keyboard focus.
+    Widget,
+    /// While the window holding the item is active. This is Qt's default.
+    #[default]
+    Window,
+    /// While any window of the application is active. A tray app usually has no window of
+    /// its own, so this is needed for shortcuts to fire outside the menu.
+    Application,
+}
+
+impl ShortcutContext {
+    fn to_raw(self) -> bind::ShortcutContext {
+        match self {
+            Self::Widget => bind::ShortcutContext_ShortcutContextWidget,
+            Self::Window => bind::ShortcutContext_ShortcutContextWindow,
+            Self::Application => bind::ShortcutContext_ShortcutContextApplication,
+        }
+    }
+}
+
+/// The check state of a menu item, see `SystemTray::set_check_state`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CheckState {
+    /// No check mark. This is the default.
+    #[default]
+    Unchecked,
+    /// Partly checked, e.g. for an item standing for several settings of which only some are
+    /// on. Only items made tristate with `Menu::tristate` show it; others show it as unchecked.
+    PartiallyChecked,
+    /// A check mark.
+    Checked,
+}
+
+impl CheckState {
+    fn to_raw(self) -> bind::CheckState {
+        match self {
+            Self::Unchecked => bind::CheckState_CheckStateUnchecked,
+            Self::PartiallyChecked => bind::CheckState_CheckStatePartiallyChecked,
+            Self::Checked => bind::CheckState_CheckStateChecked,
+        }
+    }
 }
 
 /// Represents a menu item that can be added to the system tray context menu.
+///
+/// A `Menu` is plain data, so it can be cloned and reused as a template for several trays or
+/// compared in tests.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Menu {
     text: String,
-    id: String,
+    id: MenuId,
+    data: Option<Vec<u8>>,
+    url: Option<String>,
+    enabled: bool,
+    visible: bool,
+    children: Vec<MenuEntry>,
+    accessible_description: Option<String>,
+    checked: Option<bool>,
+    tristate: bool,
+    mnemonic: bool,
+    bold: bool,
+    italic: bool,
+    role: MenuRole,
+    shortcut: Option<String>,
+    shortcut_context: ShortcutContext,
+    // Boxed to keep `MenuEntry::Item` small.
+    icon: Option<Box<(Vec<u8>, String)>>,
 }
 
 impl Menu {
     /// Creates a new `Menu` item with the given `text` and unique `id`.
     ///
     /// The `id` is used to identify which menu item was clicked when an `Event::MenuItemClicked`
-    /// is received.
-    pub fn new(text: String, id: String) -> Self {
-        Self { text, id }
+    /// is received. It is a `MenuId` rather than a string, so passing the text and the id the
+    /// wrong way round doesn't compile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::{Menu, MenuId};
+    ///
+    /// let open = Menu::new("Open", "open".into());
+    /// let quit = Menu::new(String::from("Quit"), MenuId::from("quit"));
+    /// ```
+    pub fn new(text: impl Into<String>, id: MenuId) -> Self {
+        Self {
+            text: text.into(),
+            id,
+            data: None,
+            url: None,
+            enabled: true,
+            visible: true,
+            children: Vec::new(),
+            accessible_description: None,
+            checked: None,
+            tristate: false,
+            mnemonic: false,
+            bold: false,
+            italic: false,
+            role: MenuRole::default(),
+            shortcut: None,
+            shortcut_context: ShortcutContext::default(),
+            icon: None,
+        }
     }
-}
 
-impl SystemTray {
-    /// Creates a new `SystemTray` instance.
+    /// Marks this menu item as initially disabled.
     ///
-    /// This initializes the underlying Qt application.
+    /// The item is created disabled, avoiding a flash of the enabled state and a second call to
+    /// `SystemTray::set_enabled`.
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    /// Marks this menu item as initially hidden.
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+
+    /// Enables or disables mnemonics in this item's text.
     ///
-    /// # Arguments
+    /// By default, the text is shown as is: an `&` appears as a literal ampersand. With
+    /// mnemonics enabled, `&` marks the next character as the item's mnemonic instead, shown
+    /// underlined (depending on the platform, only while Alt is held) and selecting the item
+    /// when pressed while the menu is open. `&&` then stands for a literal ampersand.
     ///
-    /// * `organization` - The organization name for the application.
-    /// * `app_id` - A unique application identifier.
+    /// # Examples
     ///
-    /// # Panics
+    /// ```
+    /// use system_tray::Menu;
     ///
-    /// This method panics if the `organization` or `app_id` strings contain null bytes.
-    pub fn new(organization: &str, app_id: &str) -> Self {
-        let c_org = CString::new(organization).map_err(Error::Ffi).unwrap();
-        let c_id = CString::new(app_id).map_err(Error::Ffi).unwrap();
-        let handle = unsafe { bind::create_qt_app() };
-        let safe_handle = unsafe { SafeQtAppHandle::new(handle) };
-        unsafe {
-            bind::set_organization_name(safe_handle.as_ptr(), c_org.as_ptr());
-            bind::set_app_id(safe_handle.as_ptr(), c_id.as_ptr());
-            bind::init_tray(safe_handle.as_ptr());
-        }
-        Self {
-            handle: Arc::new(Mutex::new(safe_handle)),
-            instance: Arc::new(Mutex::new(None)),
-        }
+    /// // Shown as "Save & Quit".
+    /// let save = Menu::new("Save & Quit", "save".into());
+    /// // Shown as "Open" with an underlined "O", and "Tom && Jerry" as "Tom & Jerry".
+    /// let open = Menu::new("&Open", "open".into()).mnemonic(true);
+    /// let show = Menu::new("Tom && Jerry", "show".into()).mnemonic(true);
+    /// ```
+    pub fn mnemonic(mut self, enabled: bool) -> Self {
+        self.mnemonic = enabled;
+        self
     }
 
-    /// Adds a menu item to the system tray's context menu.
+    /// Shows this item's text in bold, e.g. to highlight a feature.
     ///
-    /// This method consumes `self` and returns a new `SystemTray` instance, allowing for
-    /// method chaining.
+    /// This can be combined with `italic` and works for checkable items and submenus alike. The
+    /// platform style may limit how the emphasis is rendered; native menus on some platforms
+    /// ignore per-item fonts.
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Shows this item's text in italics.
     ///
-    /// # Arguments
+    /// See `bold` for how emphasis is rendered across platforms.
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    /// Sets where this item is placed in the native application menu on macOS.
     ///
-    /// * `menu` - The `Menu` item to add.
+    /// On macOS, Qt moves items with the `About`, `Preferences` or `Quit` role to the
+    /// matching entries of the application menu, as users expect. Other platforms ignore the
+    /// role. Clicking a moved item still emits `Event::MenuItemClicked` with its id.
     ///
-    /// # Panics
+    /// # Examples
     ///
-    /// This method panics if the `menu.text` or `menu.id` strings contain null bytes.
-    pub fn menu(self, menu: Menu) -> Self {
-        let c_text = CString::new(menu.text).map_err(Error::Ffi).unwrap();
-        let c_id = CString::new(menu.id).map_err(Error::Ffi).unwrap();
-        unsafe {
-            bind::add_tray_menu_item(
-                self.handle.lock().unwrap().as_ptr(),
-                c_text.as_ptr(),
-                c_id.as_ptr(),
-            );
-        }
+    /// ```
+    /// use system_tray::{Menu, MenuRole};
+    ///
+    /// let quit = Menu::new("Quit", "quit".into()).role(MenuRole::Quit);
+    /// // Keep "About this folder" in the tray menu.
+    /// let about = Menu::new("About this folder", "about".into()).role(MenuRole::None);
+    /// ```
+    pub fn role(mut self, role: MenuRole) -> Self {
+        self.role = role;
         self
     }
 
-    /// Sets the icon for the system tray.
+    /// Sets a keyboard shortcut that triggers this item, shown next to its text.
     ///
-    /// This method consumes `self` and returns a new `SystemTray` instance, allowing for
-    /// method chaining.
+    /// `keys` uses the portable format of `QKeySequence`, e.g. `"Ctrl+Q"` or `"Ctrl+Shift+S"`;
+    /// `Ctrl` stands for the Command key on macOS. Triggering the shortcut emits
+    /// `Event::MenuItemClicked` like a click. Where the shortcut is active is set with
+    /// `shortcut_context`.
     ///
-    /// # Arguments
+    /// # Examples
     ///
-    /// * `icon_data` - A static slice of bytes representing the icon data.
-    /// * `icon_format` - The format of the icon data (e.g., "png", "ico").
+    /// ```
+    /// use system_tray::{Menu, ShortcutContext};
     ///
-    /// # Panics
+    /// let quit = Menu::new("Quit", "quit".into())
+    ///     .shortcut("Ctrl+Q")
+    ///     .shortcut_context(ShortcutContext::Application);
+    /// ```
+    pub fn shortcut(mut self, keys: &str) -> Self {
+        self.shortcut = Some(keys.to_string());
+        self
+    }
+
+    /// Sets where the keyboard shortcut set with `shortcut` is active, mapping to
+    /// `QAction::setShortcutContext`.
     ///
-    /// This method panics if the `icon_format` string contains null bytes.
-    pub fn icon(self, icon_data: &'static [u8], icon_format: &str) -> Self {
-        let c_format = CString::new(icon_format).map_err(Error::Ffi).unwrap();
-        unsafe {
-            bind::set_app_icon_from_data(
-                self.handle.lock().unwrap().as_ptr(),
-                icon_data.as_ptr(),
-                icon_data.len(),
-                c_format.as_ptr(),
-            );
-        }
+    /// The default, `ShortcutContext::Window`, only fires while the menu's window is active,
+    /// i.e. while the menu is open. Use `ShortcutContext::Application` to have the shortcut
+    /// fire in any window of the application as well, such as a dialog of the app.
+    pub fn shortcut_context(mut self, context: ShortcutContext) -> Self {
+        self.shortcut_context = context;
         self
     }
 
-    /// Starts the Qt event loop in a new thread.
+    /// Shows an icon next to this menu item's text.
     ///
-    /// This is a non-blocking operation. Events can be polled using `poll_event`.
-    pub fn start(&self) {
-        let handle = {
-            let handle_guard = self.handle.lock().unwrap();
-            *handle_guard
-        };
-        let join_handle = std::thread::spawn(move || {
-            let mut argv: Vec<*mut c_char> = Vec::new(); // Currently unused in the bind, but required by Qt signature
-            let result = unsafe { bind::run_qt_app(handle.as_ptr(), 0, argv.as_mut_ptr()) };
-            if result != 0 {
-                eprintln!("Qt application exited with code: {}", result);
-            }
-        });
-        *self.instance.lock().unwrap() = Some(join_handle);
+    /// On a submenu (a `Menu` with children added with `item`), this is the icon of the entry
+    /// that opens it, set on the `QMenu`'s `menuAction()`. The data is copied; an image that
+    /// can't be decoded is reported through [`SystemTray::last_backend_error`] and the item is
+    /// shown without an icon. Some platforms, such as macOS, hide menu icons by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `icon_data` - The encoded image.
+    /// * `icon_format` - The format of the icon data (e.g., "png", "svg").
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Menu;
+    ///
+    /// let recent = Menu::new("Recent files", "recent".into())
+    ///     .with_icon(include_bytes!("../icon.svg"), "svg")
+    ///     .item(("notes.txt", "notes"));
+    /// ```
+    pub fn with_icon(mut self, icon_data: &[u8], icon_format: &str) -> Self {
+        self.icon = Some(Box::new((icon_data.to_vec(), icon_format.to_string())));
+        self
     }
 
-    /// Requests the Qt application to quit and waits for the Qt event loop thread to finish.
+    /// Makes this menu item checkable, starting checked if `checked` is true.
     ///
-    /// This method is blocking until the Qt thread has terminated.
-    pub fn stop(&self) {
-        {
-            let handle = self.handle.lock().unwrap();
-            unsafe {
-                bind::request_quit_qt_app_safe(handle.as_ptr());
-            }
-        }
-        if let Some(join_handle) = self.instance.lock().unwrap().take() {
-            join_handle.join().unwrap_or_else(|e| {
-                eprintln!("Failed to join Qt thread: {:?}", e);
-            });
-        }
+    /// Qt flips the check mark when the item is clicked; the new state can also be set with
+    /// `SystemTray::toggle_checked`.
+    pub fn checkable(mut self, checked: bool) -> Self {
+        self.checked = Some(checked);
+        self
     }
 
-    /// Polls for a new event from the system tray.
+    /// Makes this menu item a check box that can also be partially checked, e.g. for a parent
+    /// toggle whose children are only partly on.
     ///
-    /// This method is non-blocking and returns an `Event` immediately.
+    /// A `QAction` can only be checked or not, so the item is shown as a check box widget
+    /// (`QCheckBox` with `setTristate`), whose style may differ slightly from other items. It
+    /// starts unchecked, or checked after `checkable(true)`. A click checks an unchecked or
+    /// partially checked item and unchecks a checked one, emitting `Event::MenuItemClicked`;
+    /// only `SystemTray::set_check_state` makes it partial. The menu stays open after a click.
+    /// Submenus can't be tristate.
     ///
-    /// # Returns
+    /// # Examples
     ///
-    /// A `Result` containing an `Event` or a `SystemTrayError` if an unknown event type is received.
-    pub fn poll_event(&self) -> Result<Event, Error> {
-        let handle = self.handle.lock().unwrap();
-        let event = unsafe { bind::poll_event(handle.as_ptr()) };
+    /// ```
+    /// use system_tray::{CheckState, Menu, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp")
+    ///     .menu(Menu::new("All notifications", "notify".into()).tristate())
+    ///     .menu(Menu::new("Mail", "mail".into()).checkable(true))
+    ///     .menu(Menu::new("Chat", "chat".into()).checkable(false));
+    /// tray.set_check_state("notify", CheckState::PartiallyChecked)?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn tristate(mut self) -> Self {
+        self.checked.get_or_insert(false);
+        self.tristate = true;
+        self
+    }
 
-        match event.type_ {
-            bind::AppEventType_None => Ok(Event::None),
-            bind::AppEventType_TrayClicked => Ok(Event::TrayClicked),
-            bind::AppEventType_TrayDoubleClicked => Ok(Event::TrayDoubleClicked),
-            bind::AppEventType_MenuItemClicked => {
-                // IMPORTANT: CString::from_raw takes ownership of the pointer.
-                // It will call free() when `c_str` is dropped.
-                // Therefore, we MUST NOT call bind::free_char_ptr here.
-                let c_str = unsafe { CString::from_raw(event.menu_id_str as *mut c_char) };
-                let rust_str = c_str.to_string_lossy().into_owned();
-                Ok(Event::MenuItemClicked(rust_str))
-            }
-            _ => Err(Error::PollEventError(format!(
-                "Unknown event type value: {}",
-                event.type_
-            ))),
-        }
+    /// Sets a description that screen readers announce along with this item's text.
+    ///
+    /// Use it when the text alone doesn't explain what the item does, e.g. for short or
+    /// icon-like labels.
+    pub fn accessible_description(mut self, description: impl Into<String>) -> Self {
+        self.accessible_description = Some(description.into());
+        self
     }
-}
 
-impl Default for SystemTray {
-    /// Creates a default `SystemTray` instance with "MyOrganization" and "MyApp" as identifiers.
-    fn default() -> Self {
-        Self::new("MyOrganization", "MyApp")
+    /// Adds `child` to this item, turning it into a submenu.
+    ///
+    /// Items inside a submenu are identified by their path from the top-level menu: the ids
+    /// joined with `/`. Clicking `doc1` below `file` > `recent` reports
+    /// `Event::MenuItemClicked("file/recent/doc1")`, and the same path is used by methods such
+    /// as `SystemTray::set_enabled`. Ids shouldn't contain `/` themselves. A submenu isn't
+    /// clickable, so its own data and URL are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Menu;
+    ///
+    /// let file = Menu::new("File", "file".into())
+    ///     .item(("Open", "open"))
+    ///     .item(Menu::new("Recent", "recent".into()).item(("doc1.txt", "doc1")));
+    /// ```
+    pub fn item(mut self, child: impl Into<Menu>) -> Self {
+        self.children.push(MenuEntry::Item(child.into()));
+        self
     }
-}
 
-impl Drop for SystemTray {
-    /// Cleans up the Qt application resources when the `SystemTray` instance is dropped.
+    /// Adds a separator line after the items added so far, turning this item into a submenu.
     ///
-    /// This ensures that the Qt application is properly shut down and memory is freed.
-    fn drop(&mut self) {
-        self.stop();
-        let handle = self.handle.lock().unwrap();
-        if !handle.as_ptr().is_null() {
-            unsafe {
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Menu;
+    ///
+    /// let file = Menu::new("File", "file".into())
+    ///     .item(("Open", "open"))
+    ///     .separator()
+    ///     .item(("Close", "close"));
+    /// ```
+    pub fn separator(mut self) -> Self {
+        self.children.push(MenuEntry::Separator);
+        self
+    }
+
+    /// Creates a `Menu` item that opens `url` with the default handler (usually the browser)
+    /// when clicked.
+    ///
+    /// The URL is opened directly on the Qt thread via `QDesktopServices::openUrl`. An
+    /// `Event::MenuItemClicked` is still emitted, with the id `"url:"` followed by the URL, for
+    /// apps that want to observe the click.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Menu;
+    ///
+    /// let website = Menu::open_url("Open website", "https://example.com");
+    /// ```
+    pub fn open_url(text: &str, url: &str) -> Self {
+        let mut menu = Self::new(text, format!("url:{url}").into());
+        menu.url = Some(url.to_string());
+        menu
+    }
+
+    /// Attaches an opaque data payload to this menu item.
+    ///
+    /// The payload is kept on the Rust side, keyed by the item's `id`. When the item is clicked,
+    /// `Event::MenuItemClickedWithData` is emitted instead of `Event::MenuItemClicked`.
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+impl From<(&str, &str)> for Menu {
+    /// Creates a `Menu` from a `(text, id)` pair.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::Menu;
+    ///
+    /// let menu: Menu = ("Open", "open").into();
+    /// ```
+    fn from((text, id): (&str, &str)) -> Self {
+        Self::new(text, id.into())
+    }
+}
+
+/// An entry of the context menu passed to `SystemTray::replace_menu`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuEntry {
+    /// A menu item, which may be a submenu.
+    Item(Menu),
+    /// A separator line between items.
+    Separator,
+}
+
+impl From<Menu> for MenuEntry {
+    fn from(menu: Menu) -> Self {
+        Self::Item(menu)
+    }
+}
+
+impl From<(&str, &str)> for MenuEntry {
+    /// Creates a `MenuEntry::Item` from a `(text, id)` pair.
+    fn from(pair: (&str, &str)) -> Self {
+        Self::Item(pair.into())
+    }
+}
+
+/// A node of the menu tree passed to `SystemTray::set_menu_tree`.
+///
+/// Unlike `Menu`, which only nests items, a tree can also place separators inside submenus,
+/// and a submenu may be empty. Items are identified by their path as described in
+/// `Menu::item`, so ids shouldn't contain `/`.
+///
+/// # Examples
+///
+/// ```
+/// use system_tray::MenuNode;
+///
+/// let tree = vec![
+///     MenuNode::Submenu {
+///         text: "File".into(),
+///         id: "file".into(),
+///         children: vec![
+///             MenuNode::Item { text: "Open".into(), id: "open".into() },
+///             MenuNode::Separator,
+///             MenuNode::Item { text: "Close".into(), id: "close".into() },
+///         ],
+///     },
+///     MenuNode::Item { text: "Quit".into(), id: "quit".into() },
+/// ];
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuNode {
+    /// A clickable item, reported as `Event::MenuItemClicked` with its path.
+    Item { text: String, id: MenuId },
+    /// A separator line between items.
+    Separator,
+    /// A submenu holding `children`. The submenu itself isn't clickable.
+    Submenu {
+        text: String,
+        id: MenuId,
+        children: Vec<MenuNode>,
+    },
+}
+
+/// A notification with optional action buttons, shown with `SystemTray::show_notification`.
+///
+/// Like `Menu`, a `Notification` is plain data built by chaining methods, so it can be reused.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use system_tray::Notification;
+///
+/// let update = Notification::new()
+///     .title("Update available")
+///     .body("Version 2.0 is ready to install")
+///     .action("Install", "install")
+///     .action("Later", "later")
+///     .timeout(Duration::from_secs(30));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Notification {
+    title: String,
+    body: String,
+    timeout: Duration,
+    /// `(id, label)` pairs, in the order the buttons are shown.
+    actions: Vec<(String, String)>,
+    icon: NotificationIcon,
+}
+
+impl Notification {
+    /// Creates an empty notification that uses the platform's default timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the notification's title.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the notification's message.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets how long the notification is shown. Some platforms ignore this and use their own
+    /// duration. `Duration::ZERO`, the default, uses the platform's default timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a button showing `label`. Clicking it emits an `Event::NotificationAction` carrying
+    /// `id`.
+    ///
+    /// Not every notification system can show buttons. Where they are unsupported, including
+    /// Qt's own balloon messages, the notification is shown without them.
+    pub fn action(mut self, label: impl Into<String>, id: impl Into<String>) -> Self {
+        self.actions.push((id.into(), label.into()));
+        self
+    }
+
+    /// Sets the icon shown on the notification: one of the platform's severity icons, or a
+    /// custom image such as a brand logo, independent of the tray icon.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::{Notification, NotificationIcon};
+    ///
+    /// let failed = Notification::new()
+    ///     .title("Backup failed")
+    ///     .icon(NotificationIcon::Critical);
+    /// let branded = Notification::new()
+    ///     .title("Welcome")
+    ///     .icon(NotificationIcon::custom(include_bytes!("../icon.svg"), "svg"));
+    /// ```
+    pub fn icon(mut self, icon: NotificationIcon) -> Self {
+        self.icon = icon;
+        self
+    }
+}
+
+/// The icon shown on a notification, see `Notification::icon`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum NotificationIcon {
+    /// The platform's information icon. This is the default.
+    #[default]
+    Information,
+    /// The platform's warning icon.
+    Warning,
+    /// The platform's error icon.
+    Critical,
+    /// An encoded image and its format (e.g., "png", "svg"), as for `SystemTray::set_tray_icon`.
+    /// If it fails to decode, the information icon is shown and the error is reported by
+    /// `SystemTray::last_backend_error`.
+    Custom(Vec<u8>, String),
+}
+
+impl NotificationIcon {
+    /// Creates a `Custom` icon from the encoded image `data` in `format`.
+    pub fn custom(data: &[u8], format: &str) -> Self {
+        Self::Custom(data.to_vec(), format.to_string())
+    }
+
+    fn to_raw(&self) -> bind::NotificationIcon {
+        match self {
+            Self::Information => bind::NotificationIcon_NotificationIconInformation,
+            Self::Warning => bind::NotificationIcon_NotificationIconWarning,
+            Self::Critical => bind::NotificationIcon_NotificationIconCritical,
+            Self::Custom(..) => bind::NotificationIcon_NotificationIconCustom,
+        }
+    }
+}
+
+/// Adds `menu` and its children below the submenu at `parent` (a path), or at the top level.
+///
+/// Payloads of the added items are inserted into `payloads`, keyed by their path.
+fn add_menu(
+    handle: &SafeQtAppHandle,
+    payloads: &mut HashMap<String, Vec<u8>>,
+    mut menu: Menu,
+    parent: Option<&str>,
+) -> Result<(), Error> {
+    let children = std::mem::take(&mut menu.children);
+    let path = add_menu_item(handle, payloads, menu, parent, !children.is_empty())?;
+    for child in children {
+        match child {
+            MenuEntry::Item(child) => add_menu(handle, payloads, child, Some(&path))?,
+            MenuEntry::Separator => add_separator(handle, Some(&path))?,
+        }
+    }
+    Ok(())
+}
+
+/// Prepares the text of a menu item for Qt, which treats `&` as a mnemonic marker: it's escaped
+/// to show as is, unless `mnemonic` keeps the markers.
+fn menu_text(text: &str, mnemonic: bool) -> String {
+    if mnemonic {
+        text.to_string()
+    } else {
+        text.replace('&', "&&")
+    }
+}
+
+/// Adds `menu` itself, without its children, as an item or an empty submenu, and returns its
+/// path.
+fn add_menu_item(
+    handle: &SafeQtAppHandle,
+    payloads: &mut HashMap<String, Vec<u8>>,
+    menu: Menu,
+    parent: Option<&str>,
+    submenu: bool,
+) -> Result<String, Error> {
+    let path = menu_id::join_path(parent, &menu.id);
+    let c_text = CString::new(menu_text(&menu.text, menu.mnemonic))?;
+    let c_id = CString::new(menu.id.into_string())?;
+    let c_url = menu.url.map(CString::new).transpose()?;
+    let c_parent = parent.map(CString::new).transpose()?;
+    let c_description = menu.accessible_description.map(CString::new).transpose()?;
+    let c_shortcut = menu.shortcut.map(CString::new).transpose()?;
+    let c_icon_format = menu
+        .icon
+        .as_ref()
+        .map(|icon| CString::new(icon.1.as_str()))
+        .transpose()?;
+    let options = bind::MenuItemOptions {
+        enabled: menu.enabled,
+        visible: menu.visible,
+        url: c_url.as_ref().map_or(ptr::null(), |url| url.as_ptr()),
+        parent: c_parent
+            .as_ref()
+            .map_or(ptr::null(), |parent| parent.as_ptr()),
+        submenu,
+        accessible_description: c_description
+            .as_ref()
+            .map_or(ptr::null(), |description| description.as_ptr()),
+        checkable: menu.checked.is_some(),
+        checked: menu.checked.unwrap_or(false),
+        separator: false,
+        bold: menu.bold,
+        italic: menu.italic,
+        progress: false,
+        role: menu.role.to_raw(),
+        shortcut: c_shortcut
+            .as_ref()
+            .map_or(ptr::null(), |shortcut| shortcut.as_ptr()),
+        shortcut_context: menu.shortcut_context.to_raw(),
+        icon_data: menu
+            .icon
+            .as_ref()
+            .map_or(ptr::null(), |icon| icon.0.as_ptr()),
+        icon_size: menu.icon.as_ref().map_or(0, |icon| icon.0.len()),
+        icon_format: c_icon_format
+            .as_ref()
+            .map_or(ptr::null(), |format| format.as_ptr()),
+        tristate: menu.tristate && !submenu,
+    };
+    if let (Some(data), false) = (menu.data, submenu) {
+        payloads.insert(path.clone(), data);
+    }
+    unsafe {
+        bind::add_tray_menu_item(handle.as_ptr(), c_text.as_ptr(), c_id.as_ptr(), &options);
+    }
+    Ok(path)
+}
+
+/// Adds `node` and everything below it to the submenu at `parent` (a path), or at the top
+/// level.
+fn add_menu_node(
+    handle: &SafeQtAppHandle,
+    payloads: &mut HashMap<String, Vec<u8>>,
+    node: MenuNode,
+    parent: Option<&str>,
+) -> Result<(), Error> {
+    match node {
+        MenuNode::Item { text, id } => add_menu(handle, payloads, Menu::new(text, id), parent),
+        MenuNode::Separator => add_separator(handle, parent),
+        MenuNode::Submenu { text, id, children } => {
+            let path = add_menu_item(handle, payloads, Menu::new(text, id), parent, true)?;
+            children
+                .into_iter()
+                .try_for_each(|child| add_menu_node(handle, payloads, child, Some(&path)))
+        }
+    }
+}
+
+/// Adds a separator line to the submenu at `parent` (a path), or at the top level.
+fn add_separator(handle: &SafeQtAppHandle, parent: Option<&str>) -> Result<(), Error> {
+    let c_parent = parent.map(CString::new).transpose()?;
+    let options = bind::MenuItemOptions {
+        enabled: true,
+        visible: true,
+        url: ptr::null(),
+        parent: c_parent
+            .as_ref()
+            .map_or(ptr::null(), |parent| parent.as_ptr()),
+        submenu: false,
+        accessible_description: ptr::null(),
+        checkable: false,
+        checked: false,
+        separator: true,
+        bold: false,
+        italic: false,
+        progress: false,
+        role: bind::MenuRole_MenuRoleTextHeuristic,
+        shortcut: ptr::null(),
+        shortcut_context: bind::ShortcutContext_ShortcutContextWindow,
+        icon_data: ptr::null(),
+        icon_size: 0,
+        icon_format: ptr::null(),
+        tristate: false,
+    };
+    let empty = c"";
+    unsafe {
+        bind::add_tray_menu_item(handle.as_ptr(), empty.as_ptr(), empty.as_ptr(), &options);
+    }
+    Ok(())
+}
+
+impl TrayApp {
+    /// Creates a new `TrayApp` without any tray icons.
+    ///
+    /// This allocates the backend's bookkeeping for the application, but no `QApplication` yet,
+    /// so it needs no display and can't fail; the fallible setup happens in `init`, which
+    /// `start` calls automatically if it hasn't been called yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `organization` - The organization name for the application.
+    /// * `app_id` - A unique application identifier.
+    pub fn new(organization: &str, app_id: &str) -> Self {
+        let handle = unsafe { bind::create_qt_app() };
+        let safe_handle = unsafe { SafeQtAppHandle::new(handle) };
+        Self {
+            handle: Arc::new(Mutex::new(safe_handle)),
+            instance: Arc::new(Mutex::new(None)),
+            exit_code: Arc::new(Mutex::new(None)),
+            organization: organization.to_string(),
+            app_id: app_id.to_string(),
+            display_name: Arc::new(Mutex::new(None)),
+            initialized: Arc::new(Mutex::new(false)),
+            trays: Arc::new(Mutex::new(TrayRegistry::default())),
+        }
+    }
+
+    /// Spawns a new tray icon running on this application's event loop.
+    ///
+    /// Trays may be spawned before or after `start`; a tray spawned while the event loop runs
+    /// appears right away.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::TrayApp;
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// let status = app.tray().menu(("Status", "status"));
+    /// let sync = app.tray().menu(("Sync now", "sync"));
+    /// app.start();
+    /// ```
+    pub fn tray(&self) -> SystemTray {
+        let handle = unsafe { bind::create_qt_app() };
+        let safe_handle = unsafe { SafeQtAppHandle::new(handle) };
+        unsafe {
+            bind::set_fallback_icon_from_data(
+                safe_handle.as_ptr(),
+                FALLBACK_ICON.as_ptr(),
+                FALLBACK_ICON.len(),
+                c"svg".as_ptr(),
+            );
+            bind::add_app_tray(self.handle.lock().unwrap().as_ptr(), safe_handle.as_ptr());
+        }
+        let mut registry = self.trays.lock().unwrap();
+        let id = TrayId(registry.next_id);
+        registry.next_id += 1;
+        let tray = SystemTray {
+            handle: Arc::new(Mutex::new(safe_handle)),
+            app: self.clone(),
+            id,
+            payloads: Arc::new(Mutex::new(HashMap::new())),
+            double_click: Arc::new(Mutex::new(DoubleClickEmulation::new())),
+            event_sink: Arc::new(Mutex::new(None)),
+            c_buffer: Arc::new(Mutex::new(Vec::new())),
+            dialogs: Arc::new(RwLock::new(())),
+        };
+        registry.trays.push(RegisteredTray {
+            id,
+            handle: Arc::downgrade(&tray.handle),
+            payloads: Arc::downgrade(&tray.payloads),
+            double_click: Arc::downgrade(&tray.double_click),
+        });
+        tray
+    }
+
+    /// Polls every tray of this application for a new event, returning the first one found
+    /// along with the id of the tray it belongs to.
+    ///
+    /// This lets one loop serve several tray icons. Each tray keeps its own queue; they are
+    /// polled in turn, starting after the tray whose event was returned last, so a busy tray
+    /// doesn't hold back the others. Events of one tray arrive in order, but events of
+    /// different trays may be returned in a different order than they happened. Like
+    /// `SystemTray::poll_event`, this never blocks. Closed and dropped trays are skipped, as
+    /// are trays with an event sink.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the id of a tray and its event, `None` if no tray has an event
+    /// queued, or the error returned by `SystemTray::poll_event`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, TrayApp};
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// let mail = app.tray().menu(("Check mail", "check"));
+    /// let sync = app.tray().menu(("Sync now", "sync"));
+    /// app.start();
+    /// loop {
+    ///     match app.poll_event()? {
+    ///         Some((tray, Event::MenuItemClicked(id))) if tray == mail.id() => {
+    ///             println!("mail: {}", id)
+    ///         }
+    ///         Some((tray, event)) if tray == sync.id() => println!("sync: {:?}", event),
+    ///         Some(_) => {}
+    ///         None => std::thread::sleep(mail.recommended_poll_interval()),
+    ///     }
+    /// #   break;
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn poll_event(&self) -> Result<Option<(TrayId, Event)>, Error> {
+        let mut registry = self.trays.lock().unwrap();
+        registry.trays.retain(|tray| tray.handle.strong_count() > 0);
+        let count = registry.trays.len();
+        for offset in 0..count {
+            let index = (registry.next_poll + offset) % count;
+            let tray = &registry.trays[index];
+            let (Some(handle), Some(payloads), Some(double_click)) = (
+                tray.handle.upgrade(),
+                tray.payloads.upgrade(),
+                tray.double_click.upgrade(),
+            ) else {
+                continue;
+            };
+            let id = tray.id;
+            loop {
+                let event = {
+                    let handle = handle.lock().unwrap();
+                    if handle.as_ptr().is_null() {
+                        break;
+                    }
+                    unsafe { bind::poll_event(handle.as_ptr()) }
+                };
+                let queued = event.type_ != bind::AppEventType_None;
+                match decode_event(event, &payloads, &double_click)? {
+                    // A skipped event, so the tray's next one is polled.
+                    Event::None if queued => continue,
+                    Event::None => break,
+                    event => {
+                        registry.next_poll = index + 1;
+                        return Ok(Some((id, event)));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Initializes the underlying Qt application with the configuration given to `new`.
+    ///
+    /// Calling this before `start` lets failures be handled where they happen instead of
+    /// surfacing later as a non-zero `last_exit_code`. Calling it again after it succeeded does
+    /// nothing.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Ffi` if the organization name or app id contains null bytes.
+    /// * `Error::Init` if no display is available to show tray icons on, or if the Qt platform
+    ///   plugin can't be loaded. Qt would abort the process in the latter case, so on Linux the
+    ///   plugin is loaded ahead of the `QApplication`, and the error tells why it failed (e.g.
+    ///   "Could not load the Qt platform plugin "xcb": ... libxcb-cursor.so.0: cannot open
+    ///   shared object file ...").
+    pub fn init(&self) -> Result<(), Error> {
+        let mut initialized = self.initialized.lock().unwrap();
+        if *initialized {
+            return Ok(());
+        }
+        let c_org = CString::new(self.organization.as_str())?;
+        let c_id = CString::new(self.app_id.as_str())?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_organization_name(handle.as_ptr(), c_org.as_ptr());
+            bind::set_app_id(handle.as_ptr(), c_id.as_ptr());
+            if !bind::init_app(handle.as_ptr()) {
+                let reason = take_backend_string(bind::last_error(handle.as_ptr()))
+                    .unwrap_or_else(|| "no display is available".to_string());
+                return Err(Error::Init(reason));
+            }
+        }
+        *initialized = true;
+        Ok(())
+    }
+
+    /// Sets the human-friendly name of the application, as opposed to its id.
+    ///
+    /// Qt distinguishes the application name, which the crate sets to the app id passed to
+    /// `new`, from the display name shown to users. Some environments show the display name
+    /// along with notifications (e.g. as the sender on Linux desktops) and in tray attributions,
+    /// so this avoids showing an id such as "com.example.sync". This calls
+    /// `QGuiApplication::setApplicationDisplayName` and may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if `name` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::TrayApp;
+    ///
+    /// let app = TrayApp::new("MyOrg", "com.example.sync");
+    /// app.set_display_name("Example Sync")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_display_name(&self, name: &str) -> Result<(), Error> {
+        let c_name = CString::new(name)?;
+        {
+            let handle = self.handle.lock().unwrap();
+            unsafe {
+                bind::set_display_name(handle.as_ptr(), c_name.as_ptr());
+            }
+        }
+        *self.display_name.lock().unwrap() = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Returns the name shown to users: the one set with `set_display_name`, or the app id
+    /// like Qt does until then.
+    pub fn display_name(&self) -> String {
+        self.display_name
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| self.app_id.clone())
+    }
+
+    /// Sets the widget style used for the menus of all trays, regardless of the system theme.
+    ///
+    /// This calls `QApplication::setStyle` when the event loop starts, so it must be called
+    /// before `start`. Unknown style names (see `QStyleFactory::keys`) are ignored by Qt.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - The name of the style, e.g. "Fusion".
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running, or `Error::Ffi` if `style`
+    /// contains null bytes.
+    pub fn set_style(&self, style: &str) -> Result<(), Error> {
+        self.ensure_not_started()?;
+        let c_style = CString::new(style)?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_app_style(handle.as_ptr(), c_style.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Enables or disables a built-in dark palette for the menus of all trays, regardless of
+    /// the system theme.
+    ///
+    /// The palette is applied when the event loop starts, so this must be called before `start`.
+    /// It pairs well with the "Fusion" style (see `set_style`), which honors the palette on all
+    /// platforms.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    pub fn set_dark_palette(&self, enabled: bool) -> Result<(), Error> {
+        self.ensure_not_started()?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_dark_palette(handle.as_ptr(), enabled);
+        }
+        Ok(())
+    }
+
+    /// Enables or disables high-DPI scaling for all trays.
+    ///
+    /// Qt reads this when it creates the `QApplication`, which happens in `start`, so it must be
+    /// called before `start`; `new` doesn't create the application yet. Qt 6 scales for HiDPI
+    /// screens by default (`Qt::AA_EnableHighDpiScaling` is always on), rounding fractional scale
+    /// factors such as 1.5 on some platforms. Passing true keeps those factors unrounded for
+    /// crisp rendering; passing false turns scaling off and renders at device pixels, like
+    /// setting `QT_ENABLE_HIGHDPI_SCALING=0`. Both are applied right away on the calling thread
+    /// and affect the whole process; passing true after false removes the variable again.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::TrayApp;
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// app.set_high_dpi(true)?;
+    /// let tray = app.tray();
+    /// app.start();
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_high_dpi(&self, enabled: bool) -> Result<(), Error> {
+        self.ensure_not_started()?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_high_dpi(handle.as_ptr(), enabled);
+        }
+        Ok(())
+    }
+
+    /// Makes Ctrl+C quit the Qt event loop gracefully instead of leaving SIGINT to the Rust
+    /// side.
+    ///
+    /// By default, the crate installs no signal handling: SIGINT keeps its default action,
+    /// which kills the process without `Event::Quit`, or whatever handler the app installs
+    /// itself, e.g. with the `ctrlc` crate. Such a handler runs outside the Qt thread; it
+    /// should signal the main thread, which then calls `stop` for a graceful shutdown.
+    ///
+    /// When enabled, the Qt thread handles SIGINT for as long as the event loop runs: Ctrl+C
+    /// quits the event loop, `Event::Quit` is emitted and `last_exit_code` becomes `Some(0)`,
+    /// so the app's usual quit handling applies. The previous handler is restored when the
+    /// event loop returns. On Windows, this handles the console's Ctrl+C instead. Don't
+    /// combine it with a handler of your own, since only one of them receives the signal.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, TrayApp};
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// app.set_quit_on_interrupt(true)?;
+    /// let tray = app.tray();
+    /// app.start();
+    /// loop {
+    ///     if tray.poll_event()? == Event::Quit {
+    ///         break; // Ctrl+C was pressed, or the session is ending.
+    ///     }
+    /// #   break;
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_quit_on_interrupt(&self, enabled: bool) -> Result<(), Error> {
+        self.ensure_not_started()?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_quit_on_interrupt(handle.as_ptr(), enabled);
+        }
+        Ok(())
+    }
+
+    /// Sets how long `start` waits for the systray host when it isn't up yet, e.g. because the
+    /// app is launched at login before the desktop panel.
+    ///
+    /// By default the event loop doesn't start at all without a host: `last_exit_code` becomes
+    /// `Some(-1)` and the icons never appear. With a timeout, the event loop starts anyway and
+    /// checks for the host with a growing delay, from 100 ms up to 2 s between checks. Once
+    /// it appears, each tray receives `Event::VisibilityChanged { visible: true }` and
+    /// `Event::TrayReconnected`, and Qt registers the icons. If it doesn't appear within
+    /// `timeout`, the event loop exits with `-1` and [`TrayApp::last_backend_error`] says why. A
+    /// zero `timeout` restores the default.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, TrayApp};
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// app.set_tray_wait_timeout(Duration::from_secs(30))?;
+    /// let tray = app.tray();
+    /// app.start();
+    /// loop {
+    ///     match tray.poll_event_blocking_up_to(Duration::from_secs(1))? {
+    ///         Event::TrayReconnected => {} // The panel is up; the icon is shown now.
+    ///         Event::Quit => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_tray_wait_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        self.ensure_not_started()?;
+        let handle = self.handle.lock().unwrap();
+        unsafe {
+            bind::set_tray_wait_timeout(
+                handle.as_ptr(),
+                timeout.as_millis().min(i32::MAX as u128) as c_int,
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns `Error::AlreadyStarted` if the Qt event loop has been started.
+    fn ensure_not_started(&self) -> Result<(), Error> {
+        if self.instance.lock().unwrap().is_some() {
+            return Err(Error::AlreadyStarted);
+        }
+        Ok(())
+    }
+
+    /// Starts the Qt event loop in a new thread.
+    ///
+    /// This is a non-blocking operation. Events can be polled using `SystemTray::poll_event` on
+    /// each tray. Does nothing if the event loop has already been started.
+    ///
+    /// If `init` hasn't been called yet, it is called first; if it fails, the error is printed,
+    /// the event loop isn't started and `last_exit_code` returns `Some(-1)`.
+    pub fn start(&self) {
+        let mut instance = self.instance.lock().unwrap();
+        if instance.is_some() {
+            return;
+        }
+        if let Err(e) = self.spawn_event_loop(&mut instance) {
+            eprintln!("Failed to initialize the system tray: {}", e);
+            *self.exit_code.lock().unwrap() = Some(-1);
+        }
+    }
+
+    /// Calls `init` and spawns the Qt thread, storing it in `instance`, which the caller holds
+    /// locked so that no other `start` can slip in between.
+    fn spawn_event_loop(&self, instance: &mut Option<JoinHandle<()>>) -> Result<(), Error> {
+        self.init()?;
+        let exit_code = Arc::clone(&self.exit_code);
+        let handle = {
+            let handle_guard = self.handle.lock().unwrap();
+            *handle_guard
+        };
+        *exit_code.lock().unwrap() = None;
+        let join_handle = std::thread::spawn(move || {
+            let mut argv: Vec<*mut c_char> = Vec::new(); // Currently unused in the bind, but required by Qt signature
+            let result = unsafe { bind::run_qt_app(handle.as_ptr(), 0, argv.as_mut_ptr()) };
+            if result != 0 {
+                eprintln!("Qt application exited with code: {}", result);
+            }
+            *exit_code.lock().unwrap() = Some(result);
+        });
+        *instance = Some(join_handle);
+        Ok(())
+    }
+
+    /// Starts the Qt event loop like `start`, returning a guard that stops it when dropped.
+    ///
+    /// Once the guard has been dropped, the application can be started again; its trays keep
+    /// their icons, menus and other settings.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AlreadyStarted` if the event loop has already been started, since the guard
+    /// would otherwise stop a loop it didn't start, or the error returned by `init`.
+    pub fn start_scoped(&self) -> Result<StartedTray, Error> {
+        let mut instance = self.instance.lock().unwrap();
+        if instance.is_some() {
+            return Err(Error::AlreadyStarted);
+        }
+        self.spawn_event_loop(&mut instance)?;
+        Ok(StartedTray { app: self.clone() })
+    }
+
+    /// Returns the exit code of the Qt event loop once its thread has finished.
+    ///
+    /// `Some(0)` means a clean quit; any other value means the event loop failed or exited with
+    /// an error (e.g. `-1` when no system tray is available). Returns `None` before `start` and
+    /// while the event loop is still running.
+    pub fn last_exit_code(&self) -> Option<i32> {
+        *self.exit_code.lock().unwrap()
+    }
+
+    /// Reads a preference saved with `set_setting`.
+    ///
+    /// Settings are stored with Qt's `QSettings` under the organization and app id passed to
+    /// `new`: in the registry on Windows, in a property list on macOS, and in
+    /// `~/.config/<organization>/<app id>.conf` on Linux. Keys may contain `/` to group
+    /// values. This works before `start`, and from any thread.
+    ///
+    /// # Returns
+    ///
+    /// The value, or `None` if the key doesn't exist or contains null bytes.
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        let c_org = CString::new(self.organization.as_str()).ok()?;
+        let c_id = CString::new(self.app_id.as_str()).ok()?;
+        let c_key = CString::new(key).ok()?;
+        unsafe {
+            take_backend_string(bind::read_setting(
+                c_org.as_ptr(),
+                c_id.as_ptr(),
+                c_key.as_ptr(),
+            ))
+        }
+    }
+
+    /// Saves a preference, such as a user's choice, so that `get_setting` returns it in later
+    /// runs.
+    ///
+    /// The value is written to permanent storage before this returns. See `get_setting` for
+    /// where settings are stored.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `key`, `value`, the organization name or the app id contains null bytes,
+    /// or `Error::Settings` if the settings couldn't be written, e.g. because the file isn't
+    /// writable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::TrayApp;
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// app.set_setting("sync/interval", "15")?;
+    /// assert_eq!(app.get_setting("sync/interval").as_deref(), Some("15"));
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), Error> {
+        let c_org = CString::new(self.organization.as_str())?;
+        let c_id = CString::new(self.app_id.as_str())?;
+        let c_key = CString::new(key)?;
+        let c_value = CString::new(value)?;
+        let written = unsafe {
+            bind::write_setting(
+                c_org.as_ptr(),
+                c_id.as_ptr(),
+                c_key.as_ptr(),
+                c_value.as_ptr(),
+            )
+        };
+        if !written {
+            return Err(Error::Settings(key.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Describes the most recent failure of the Qt application itself, such as the event loop
+    /// giving up because no system tray is available.
+    ///
+    /// Failures of individual trays are reported by `SystemTray::last_backend_error`.
+    pub fn last_backend_error(&self) -> Option<String> {
+        let handle = self.handle.lock().unwrap();
+        unsafe { take_backend_string(bind::last_error(handle.as_ptr())) }
+    }
+
+    /// Requests the Qt application to quit and waits for the Qt event loop thread to finish.
+    ///
+    /// This removes every tray of the application. This method is blocking until the Qt thread
+    /// has terminated. The application can be started again afterwards.
+    pub fn stop(&self) {
+        if let Some(join_handle) = self.request_stop() {
+            join_handle.join().unwrap_or_else(|e| {
+                eprintln!("Failed to join Qt thread: {:?}", e);
+            });
+        }
+    }
+
+    /// Requests the Qt application to quit without waiting, and returns whether the Qt event
+    /// loop thread has finished.
+    ///
+    /// Call it again later, e.g. once per iteration of the app's own loop, until it returns
+    /// true; the quit is only requested again, which does no harm. Once it has returned true,
+    /// the thread has been joined as by `stop`, and the application can be started again.
+    /// Returns true right away if the event loop isn't running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::TrayApp;
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// app.start();
+    /// while !app.try_stop() {
+    ///     // Keep doing other work while Qt shuts down.
+    ///     std::thread::sleep(Duration::from_millis(10));
+    /// }
+    /// ```
+    pub fn try_stop(&self) -> bool {
+        {
+            let handle = self.handle.lock().unwrap();
+            unsafe {
+                bind::request_quit_qt_app_safe(handle.as_ptr());
+            }
+        }
+        let mut instance = self.instance.lock().unwrap();
+        if instance
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+        {
+            return false;
+        }
+        if let Some(join_handle) = instance.take() {
+            join_handle.join().unwrap_or_else(|e| {
+                eprintln!("Failed to join Qt thread: {:?}", e);
+            });
+        }
+        true
+    }
+
+    /// Requests the Qt application to quit and waits for the Qt event loop thread to finish
+    /// without blocking the calling thread, for async code.
+    ///
+    /// The Qt thread is joined on a helper thread that completes the returned future, so it
+    /// doesn't depend on any particular runtime. If the future is dropped early, the
+    /// application still shuts down, and its Qt resources are freed once the Qt thread has
+    /// finished and the last clone is gone, just like after `stop`. Don't call `start` again
+    /// until the future has completed.
+    ///
+    /// Requires the `async` feature.
+    #[cfg(feature = "async")]
+    pub async fn stop_async(&self) {
+        stop::StopFuture::join(self.request_stop(), self.clone()).await
+    }
+
+    /// Requests the Qt application to quit, returning the Qt thread to join if it was started.
+    fn request_stop(&self) -> Option<JoinHandle<()>> {
+        {
+            let handle = self.handle.lock().unwrap();
+            unsafe {
+                bind::request_quit_qt_app_safe(handle.as_ptr());
+            }
+        }
+        self.instance.lock().unwrap().take()
+    }
+}
+
+impl Drop for TrayApp {
+    /// Stops the event loop and frees the Qt application once the last clone is dropped.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.handle) > 1 {
+            return;
+        }
+        self.stop();
+        let handle = self.handle.lock().unwrap();
+        if !handle.as_ptr().is_null() {
+            unsafe {
+                bind::cleanup_qt_app(handle.as_ptr());
+            }
+        }
+    }
+}
+
+impl SystemTray {
+    /// Creates a new `SystemTray` instance.
+    ///
+    /// This creates a private `TrayApp` for the tray; use `TrayApp::tray` to show several tray
+    /// icons from one application. Like `TrayApp::new`, this doesn't create the `QApplication`
+    /// yet; the fallible setup happens in `init`, which `start` calls automatically if it
+    /// hasn't been called yet. Nothing is registered with the systray host until `start`, which
+    /// shows the icon unless `hide` was called first.
+    ///
+    /// # Arguments
+    ///
+    /// * `organization` - The organization name for the application.
+    /// * `app_id` - A unique application identifier.
+    pub fn new(organization: &str, app_id: &str) -> Self {
+        TrayApp::new(organization, app_id).tray()
+    }
+
+    /// Returns the `TrayApp` this tray runs on.
+    pub fn app(&self) -> &TrayApp {
+        &self.app
+    }
+
+    /// Returns the id of this tray among the trays of its `TrayApp`, as reported by
+    /// `TrayApp::poll_event`. Clones of a tray share its id.
+    pub fn id(&self) -> TrayId {
+        self.id
+    }
+
+    /// Shows the tray icon, registering it with the systray host.
+    ///
+    /// The icon is shown by default, so this is only needed after `hide`. Before `start`, this
+    /// decides whether `start` shows the icon; afterwards it takes effect right away.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn show(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tray_icon_shown(handle.as_ptr(), true);
+        }
+        Ok(())
+    }
+
+    /// Hides the tray icon, unregistering it from the systray host while the application and
+    /// its event loop keep running.
+    ///
+    /// Called before `start`, the icon is never registered until `show` is called, e.g. for
+    /// apps that only show an icon depending on a setting. The menu, icon and other settings are
+    /// kept while hidden. Hidden icons can't show notifications on most platforms.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// # let show_tray_setting = true;
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.hide()?;
+    /// tray.start();
+    /// if show_tray_setting {
+    ///     tray.show()?;
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn hide(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tray_icon_shown(handle.as_ptr(), false);
+        }
+        Ok(())
+    }
+
+    /// Applies the current icon, tooltip and menu to the tray icon again, making the systray
+    /// host redraw them.
+    ///
+    /// This works around hosts that keep showing a stale or blank icon, e.g. after the panel
+    /// restarted, the desktop theme changed or the session resumed. A themed icon picks the
+    /// variant for the current color scheme; an animation keeps running. Does nothing before
+    /// `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.start();
+    /// loop {
+    ///     match tray.poll_event_blocking_up_to(Duration::from_secs(1))? {
+    ///         Event::TrayReconnected => tray.refresh()?,
+    ///         Event::Quit => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn refresh(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::refresh_tray(handle.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Keeps the tray alive until the process exits, instead of removing it when this
+    /// `SystemTray` and its clones are dropped.
+    ///
+    /// This suits daemons that set up their tray once and never tear it down. Other clones of
+    /// this `SystemTray` keep working, but dropping them no longer removes the tray either.
+    ///
+    /// # Returns
+    ///
+    /// The `TrayApp` the tray runs on, which can still `stop` the event loop later. Dropping it
+    /// doesn't stop the event loop, since the leaked tray keeps the application alive.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// fn setup() {
+    ///     let tray = SystemTray::new("MyOrg", "MyDaemon");
+    ///     tray.start();
+    ///     tray.leak();
+    /// } // The tray stays up after `tray` goes out of scope.
+    /// ```
+    pub fn leak(self) -> TrayApp {
+        let app = self.app.clone();
+        std::mem::forget(self);
+        app
+    }
+
+    /// Initializes the underlying Qt application (see `TrayApp::init`).
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Ffi` if the organization name or app id contains null bytes.
+    /// * `Error::Init` if no display is available to show the tray icon on.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// if let Err(e) = tray.init() {
+    ///     eprintln!("Running without a tray icon: {e}");
+    /// }
+    /// ```
+    pub fn init(&self) -> Result<(), Error> {
+        self.ensure_open()?;
+        self.app.init()
+    }
+
+    /// Adds a menu item to the system tray's context menu.
+    ///
+    /// This method consumes `self` and returns a new `SystemTray` instance, allowing for
+    /// method chaining. Items added after `start` (here or with `add_progress_item`) are
+    /// appended to the live menu, which is then exported again so that StatusNotifierItem
+    /// hosts such as KDE Plasma show them too.
+    ///
+    /// # Arguments
+    ///
+    /// * `menu` - The `Menu` item to add, or anything convertible into one such as a
+    ///   `(text, id)` pair.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the `menu.text`, `menu.id` or URL strings contain null bytes, or
+    /// after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Menu, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp")
+    ///     .menu(("Open", "open"))
+    ///     .menu(Menu::new("Quit", "quit".into()));
+    /// ```
+    pub fn menu(self, menu: impl Into<Menu>) -> Self {
+        // The payloads are merged in only after the handle is released: adding an item waits
+        // for the Qt thread, which locks the payloads to decode clicks.
+        let mut payloads = HashMap::new();
+        {
+            let handle = self.lock_handle().unwrap();
+            add_menu(&handle, &mut payloads, menu.into(), None).unwrap();
+        }
+        self.payloads.lock().unwrap().extend(payloads);
+        self
+    }
+
+    /// Replaces the whole context menu with `items` in one step.
+    ///
+    /// The new menu is built off-screen on the Qt thread while the current one stays in place,
+    /// then swapped in at once, so the user never sees an empty or partially built menu, even
+    /// if the menu is open. Before `start`, the items replace the ones added so far. Payloads
+    /// of the previous items are dropped.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if a text, id or URL contains null bytes, in which case the menu is left
+    /// unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Menu, MenuEntry, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.replace_menu(vec![
+    ///     Menu::new("Open", "open".into()).into(),
+    ///     MenuEntry::Separator,
+    ///     ("Quit", "quit").into(),
+    /// ])?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn replace_menu(&self, items: Vec<MenuEntry>) -> Result<(), Error> {
+        self.rebuild_menu(|handle, payloads| {
+            items.into_iter().try_for_each(|entry| match entry {
+                MenuEntry::Item(menu) => add_menu(handle, payloads, menu, None),
+                MenuEntry::Separator => add_separator(handle, None),
+            })
+        })
+    }
+
+    /// Replaces the whole context menu with the menu tree `root` in one step.
+    ///
+    /// The tree is built with the correct nesting and swapped in at once, exactly like
+    /// `replace_menu`, which is the better fit when items need options such as data or check
+    /// marks. `MenuNode` describes the tree without chained calls, e.g. when the menu comes
+    /// from a configuration file.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if a text or id contains null bytes, in which case the menu is left
+    /// unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{MenuNode, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.set_menu_tree(vec![
+    ///     MenuNode::Submenu {
+    ///         text: "Recent".into(),
+    ///         id: "recent".into(),
+    ///         children: vec![MenuNode::Item { text: "doc1.txt".into(), id: "doc1".into() }],
+    ///     },
+    ///     MenuNode::Separator,
+    ///     MenuNode::Item { text: "Quit".into(), id: "quit".into() },
+    /// ])?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_menu_tree(&self, root: Vec<MenuNode>) -> Result<(), Error> {
+        self.rebuild_menu(|handle, payloads| {
+            root.into_iter()
+                .try_for_each(|node| add_menu_node(handle, payloads, node, None))
+        })
+    }
+
+    /// Sets a separate menu that a left click on the tray icon opens, while a right click still
+    /// opens the context menu.
+    ///
+    /// This gives the common two-menu layout: quick actions on a left click, settings on a
+    /// right click. Clicking an item of the left-click menu emits `Event::MenuItemClicked` with
+    /// its path, like the context menu, so ids shouldn't clash between the two menus. Methods
+    /// that change items by id, such as `set_enabled`, only apply to the context menu.
+    ///
+    /// While the left-click menu has items, it replaces the behavior set for the left button
+    /// with `set_button_event`; passing an empty tree removes it again. On macOS, where a left
+    /// click also opens the context menu, the platform may show the context menu instead.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if a text or id contains null bytes, in which case the left-click menu is
+    /// left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{MenuNode, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Settings", "settings"));
+    /// tray.set_left_click_menu(vec![
+    ///     MenuNode::Item { text: "Pause sync".into(), id: "pause".into() },
+    ///     MenuNode::Item { text: "Sync now".into(), id: "sync".into() },
+    /// ])?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_left_click_menu(&self, menu_tree: Vec<MenuNode>) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        // Items of the left-click menu carry no payloads.
+        let mut payloads = HashMap::new();
+        unsafe {
+            bind::begin_left_click_menu(handle.as_ptr());
+        }
+        let result = menu_tree
+            .into_iter()
+            .try_for_each(|node| add_menu_node(&handle, &mut payloads, node, None));
+        unsafe {
+            bind::finish_left_click_menu(handle.as_ptr(), result.is_ok());
+        }
+        result
+    }
+
+    /// Builds a replacement menu with `build` and swaps it in if `build` succeeds, keeping the
+    /// current menu and payloads otherwise.
+    fn rebuild_menu(
+        &self,
+        build: impl FnOnce(&SafeQtAppHandle, &mut HashMap<String, Vec<u8>>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut payloads = HashMap::new();
+        let result = {
+            let handle = self.lock_handle()?;
+            unsafe {
+                bind::begin_tray_menu_replace(handle.as_ptr());
+            }
+            let result = build(&handle, &mut payloads);
+            unsafe {
+                bind::finish_tray_menu_replace(handle.as_ptr(), result.is_ok());
+            }
+            result
+        };
+        if result.is_ok() {
+            *self.payloads.lock().unwrap() = payloads;
+        }
+        result
+    }
+
+    /// Sets the icon for the system tray, and the application icon used for windows and the
+    /// taskbar.
+    ///
+    /// This method consumes `self` and returns a new `SystemTray` instance, allowing for
+    /// method chaining. To use different images for the two, see `set_tray_icon` and
+    /// `set_application_icon`.
+    ///
+    /// # Arguments
+    ///
+    /// * `icon_data` - A static slice of bytes representing the icon data.
+    /// * `icon_format` - The format of the icon data (e.g., "png", "ico").
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the `icon_format` string contains null bytes, or after `close`.
+    pub fn icon(self, icon_data: &'static [u8], icon_format: &str) -> Self {
+        let c_format = CString::new(icon_format).map_err(Error::Ffi).unwrap();
+        unsafe {
+            bind::set_app_icon_from_data(
+                self.lock_handle().unwrap().as_ptr(),
+                icon_data.as_ptr(),
+                icon_data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        self
+    }
+
+    /// Sets the image shown in the system tray, leaving the application icon alone.
+    ///
+    /// This may be called before or after `start`. The data is copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `icon_data` - The encoded image.
+    /// * `icon_format` - The format of the icon data (e.g., "png", "svg").
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `icon_format` contains null bytes.
+    pub fn set_tray_icon(&self, icon_data: &[u8], icon_format: &str) -> Result<(), Error> {
+        let c_format = CString::new(icon_format)?;
+        unsafe {
+            bind::set_tray_icon_from_data(
+                self.lock_handle()?.as_ptr(),
+                icon_data.as_ptr(),
+                icon_data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the image shown in the system tray as a template image, which the platform
+    /// recolors to suit the panel, e.g. white on a dark menu bar and black on a light one.
+    ///
+    /// Template images are usually black shapes on a transparent background; only their alpha
+    /// channel matters. The icon is marked with `QIcon::setIsMask`. Platform support:
+    ///
+    /// * macOS: the icon becomes a template `NSImage` and follows the menu bar's appearance.
+    /// * Windows and Linux: Qt doesn't recolor mask icons there, so the image is shown as
+    ///   is. Pick one that reads well on the usual panel colors, or switch icons with
+    ///   `set_tray_icon` when the theme changes.
+    ///
+    /// The application icon is left alone, and setting the tray icon any other way clears the
+    /// mark. This may be called before or after `start`. The data is copied.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `icon_format` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.set_icon_template(include_bytes!("../icon.svg"), "svg")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_icon_template(&self, icon_data: &[u8], icon_format: &str) -> Result<(), Error> {
+        let c_format = CString::new(icon_format)?;
+        unsafe {
+            bind::set_tray_icon_template(
+                self.lock_handle()?.as_ptr(),
+                icon_data.as_ptr(),
+                icon_data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the image shown in the system tray from SVG markup, e.g. generated at runtime to
+    /// recolor the icon.
+    ///
+    /// This is `set_tray_icon` with the text as the data and `"svg"` as the format: Qt's SVG
+    /// image plugin renders it with `QSvgRenderer` at the icon's size (see
+    /// `set_preferred_icon_size`), so it stays sharp. Markup that can't be rendered is
+    /// reported by `last_backend_error`, and the previous icon is replaced by the fallback
+    /// icon. Like `set_tray_icon`, this leaves the application icon alone and may be called
+    /// before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// let color = if cfg!(target_os = "macos") { "black" } else { "#2a7ae2" };
+    /// tray.set_icon_svg_str(&format!(
+    ///     r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 16"><circle cx="8" cy="8" r="7" fill="{}"/></svg>"#,
+    ///     color
+    /// ))?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_icon_svg_str(&self, svg: &str) -> Result<(), Error> {
+        unsafe {
+            bind::set_tray_icon_from_data(
+                self.lock_handle()?.as_ptr(),
+                svg.as_ptr(),
+                svg.len(),
+                c"svg".as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the image shown in the system tray from an `image::DynamicImage`.
+    ///
+    /// The image is converted to 8-bit RGBA and handed to Qt as raw pixels, so an image that is
+    /// already in memory doesn't have to be encoded as a PNG just to be decoded again. Like
+    /// `set_tray_icon`, this leaves the application icon alone, may be called before or after
+    /// `start`, and copies the pixels. Requires the `image` feature.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use image::{DynamicImage, Rgba, RgbaImage};
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// // A 16x16 red square.
+    /// let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255])));
+    /// tray.set_icon_image(&image)?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    #[cfg(feature = "image")]
+    pub fn set_icon_image(&self, image: &image::DynamicImage) -> Result<(), Error> {
+        let rgba = image.to_rgba8();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tray_icon_rgba(
+                handle.as_ptr(),
+                rgba.as_raw().as_ptr(),
+                rgba.width(),
+                rgba.height(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the tray icon from several renditions of the same image, so that it stays crisp on
+    /// HiDPI screens.
+    ///
+    /// Instead of scaling a single image, Qt picks the rendition that best fits the size the
+    /// icon is drawn at in device pixels, e.g. the 32 px one for a 16 px tray slot on a screen
+    /// with a device pixel ratio of 2. Qt 6 always enables high-DPI scaling, so
+    /// `Qt::AA_EnableHighDpiScaling` doesn't need to be (and can't usefully be) set; the ratio is
+    /// controlled by the platform and variables such as `QT_SCALE_FACTOR`. The size hint from
+    /// `set_preferred_icon_size` doesn't apply to the renditions. This replaces an icon set with
+    /// `icon` or `set_tray_icon`, and may be called before or after `start`. The data is copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `variants` - The nominal size in pixels of each rendition, and its image data. Images
+    ///   are scaled to fit their nominal size; 0 keeps an image's own size.
+    /// * `format` - The format of the image data (e.g., "png").
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `format` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.icon_multi(
+    ///     &[
+    ///         (16, include_bytes!("../icon.svg")),
+    ///         (32, include_bytes!("../icon.svg")),
+    ///     ],
+    ///     "svg",
+    /// )?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn icon_multi(&self, variants: &[(u32, &[u8])], format: &str) -> Result<(), Error> {
+        let c_format = CString::new(format)?;
+        let data: Vec<*const u8> = variants.iter().map(|(_, data)| data.as_ptr()).collect();
+        let sizes: Vec<usize> = variants.iter().map(|(_, data)| data.len()).collect();
+        let pixel_sizes: Vec<u32> = variants.iter().map(|(size, _)| *size).collect();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tray_icon_variants(
+                handle.as_ptr(),
+                data.as_ptr(),
+                sizes.as_ptr(),
+                pixel_sizes.as_ptr(),
+                variants.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the tray icon from renditions for a light and a dark color scheme, e.g. four assets
+    /// for light and dark at 1x and 2x, and keeps showing the ones matching the desktop.
+    ///
+    /// Within each scheme the renditions work as with `icon_multi`: Qt picks the one that best
+    /// fits the size the icon is drawn at in device pixels. When the color scheme changes, the
+    /// icon is switched before `Event::ColorSchemeChanged` is emitted, so there's no need to
+    /// call `set_tray_icon` from the event loop. Qt reports the scheme from version 6.5 on;
+    /// before that, or when the platform doesn't say, a palette with light text counts as dark
+    /// and the scheme is only checked when an icon is set and at `start`.
+    ///
+    /// This replaces an icon set any other way, and is replaced in turn by `set_tray_icon`,
+    /// `icon_multi` and the like. It may be called before or after `start`. The data is copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `light` - The nominal size in pixels of each rendition for a light color scheme, and
+    ///   its image data, as for `icon_multi`.
+    /// * `dark` - The same for a dark color scheme.
+    /// * `format` - The format of the image data (e.g., "png").
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `format` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.set_icon_themed(
+    ///     &[
+    ///         (16, include_bytes!("../icon.svg")),
+    ///         (32, include_bytes!("../icon.svg")),
+    ///     ],
+    ///     &[
+    ///         (16, include_bytes!("../icon.svg")),
+    ///         (32, include_bytes!("../icon.svg")),
+    ///     ],
+    ///     "svg",
+    /// )?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_icon_themed(
+        &self,
+        light: &[(u32, &[u8])],
+        dark: &[(u32, &[u8])],
+        format: &str,
+    ) -> Result<(), Error> {
+        let c_format = CString::new(format)?;
+        let variants = light
+            .iter()
+            .map(|variant| (variant, false))
+            .chain(dark.iter().map(|variant| (variant, true)));
+        let data: Vec<*const u8> = variants
+            .clone()
+            .map(|((_, data), _)| data.as_ptr())
+            .collect();
+        let sizes: Vec<usize> = variants.clone().map(|((_, data), _)| data.len()).collect();
+        let pixel_sizes: Vec<u32> = variants.clone().map(|((size, _), _)| *size).collect();
+        let is_dark: Vec<bool> = variants.map(|(_, is_dark)| is_dark).collect();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tray_icon_themed(
+                handle.as_ptr(),
+                data.as_ptr(),
+                sizes.as_ptr(),
+                pixel_sizes.as_ptr(),
+                is_dark.as_ptr(),
+                data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Replaces the icon shown when no tray icon has been set, or the one set can't be decoded.
+    ///
+    /// By default a generic icon compiled into the crate is used, so a tray without an icon
+    /// doesn't show up blank. This may be called before or after `start`. The data is copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `icon_data` - The encoded image.
+    /// * `icon_format` - The format of the icon data (e.g., "png", "svg").
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `icon_format` contains null bytes.
+    pub fn set_fallback_icon(&self, icon_data: &[u8], icon_format: &str) -> Result<(), Error> {
+        let c_format = CString::new(icon_format)?;
+        unsafe {
+            bind::set_fallback_icon_from_data(
+                self.lock_handle()?.as_ptr(),
+                icon_data.as_ptr(),
+                icon_data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the application icon, which Qt uses for the app's windows and taskbar entries,
+    /// leaving the tray icon alone.
+    ///
+    /// Until this is called, the tray icon doubles as the application icon. This may be called
+    /// before or after `start`. The data is copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `icon_data` - The encoded image.
+    /// * `icon_format` - The format of the icon data (e.g., "png", "svg").
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `icon_format` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.set_tray_icon(include_bytes!("../icon.svg"), "svg")?;
+    /// tray.set_application_icon(include_bytes!("../icon.svg"), "svg")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_application_icon(&self, icon_data: &[u8], icon_format: &str) -> Result<(), Error> {
+        let c_format = CString::new(icon_format)?;
+        unsafe {
+            bind::set_application_icon_from_data(
+                self.lock_handle()?.as_ptr(),
+                icon_data.as_ptr(),
+                icon_data.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Enables or disables the menu item with the given `id`.
+    ///
+    /// To update many items at once, prefer `set_enabled_batch`, which needs a single round trip
+    /// to the Qt thread.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`.
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<(), Error> {
+        self.set_enabled_batch(&[(id, enabled)])
+            .map_err(|e| match e {
+                Error::UnknownMenuItems(_) => Error::UnknownMenuItem(id.to_string()),
+                e => e,
+            })
+    }
+
+    /// Enables or disables several menu items in one call.
+    ///
+    /// All changes are applied together on the Qt thread. Known ids are updated even if some
+    /// ids are unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `states` - Pairs of menu item ids and their new enabled state.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItems` listing every id that didn't match a menu item, or `Error::Ffi`
+    /// if an id contains null bytes.
+    pub fn set_enabled_batch(&self, states: &[(&str, bool)]) -> Result<(), Error> {
+        let c_ids = states
+            .iter()
+            .map(|(id, _)| CString::new(*id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let id_ptrs: Vec<*const c_char> = c_ids.iter().map(|id| id.as_ptr()).collect();
+        let enabled: Vec<bool> = states.iter().map(|(_, enabled)| *enabled).collect();
+        let mut found = vec![false; states.len()];
+        {
+            let handle = self.lock_handle()?;
+            unsafe {
+                bind::set_menu_items_enabled(
+                    handle.as_ptr(),
+                    id_ptrs.as_ptr(),
+                    enabled.as_ptr(),
+                    states.len(),
+                    found.as_mut_ptr(),
+                );
+            }
+        }
+        let unknown: Vec<String> = states
+            .iter()
+            .zip(&found)
+            .filter(|(_, found)| !**found)
+            .map(|((id, _), _)| id.to_string())
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::UnknownMenuItems(unknown))
+        }
+    }
+
+    /// Shows or hides the menu item with the given `id`.
+    ///
+    /// Like every method taking a menu item id, this accepts the path of an item in a
+    /// submenu, e.g. `"file/recent/doc1"`. Hiding a submenu hides everything in it.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`, or `Error::Ffi` if `id`
+    /// contains null bytes.
+    pub fn set_visible(&self, id: &str, visible: bool) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let handle = self.lock_handle()?;
+        if unsafe { bind::set_menu_item_visible(handle.as_ptr(), c_id.as_ptr(), visible) } {
+            Ok(())
+        } else {
+            Err(Error::UnknownMenuItem(id.to_string()))
+        }
+    }
+
+    /// Changes the text of the menu item with the given `id`, or the title of a submenu.
+    ///
+    /// `&` is shown as is, like in `Menu::new`. The id of the item stays the same.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`, or `Error::Ffi` if `id` or
+    /// `text` contain null bytes.
+    pub fn set_menu_text(&self, id: &str, text: &str) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let c_text = CString::new(menu_text(text, false))?;
+        let handle = self.lock_handle()?;
+        if unsafe { bind::set_menu_item_text(handle.as_ptr(), c_id.as_ptr(), c_text.as_ptr()) } {
+            Ok(())
+        } else {
+            Err(Error::UnknownMenuItem(id.to_string()))
+        }
+    }
+
+    /// Returns whether the context menu has an item with the given `id`, a path for items in
+    /// submenus.
+    ///
+    /// Items count from the moment they are added, also before `start`, until they are
+    /// removed. Separators have no id and are never found; neither are the items of the
+    /// left-click menu (see `set_left_click_menu`). An `id` with null bytes is never found, and
+    /// nothing is found after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Sync now", "sync"));
+    /// tray.start();
+    /// if tray.has_menu_item("sync") {
+    ///     tray.set_enabled("sync", false)?;
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn has_menu_item(&self, id: &str) -> bool {
+        let c_id = match CString::new(id) {
+            Ok(c_id) => c_id,
+            Err(_) => return false,
+        };
+        match self.lock_handle() {
+            Ok(handle) => unsafe { bind::has_menu_item(handle.as_ptr(), c_id.as_ptr()) },
+            Err(_) => false,
+        }
+    }
+
+    /// Removes the menu item with the given `id` from the context menu.
+    ///
+    /// Removing a submenu removes everything in it, along with the payloads attached with
+    /// `Menu::with_data`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`, or `Error::Ffi` if `id`
+    /// contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// Items in submenus are managed by their path, as the top-level items are by their id:
+    ///
+    /// ```
+    /// use system_tray::{Menu, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(
+    ///     Menu::new("File", "file".into())
+    ///         .item(("Open", "open"))
+    ///         .separator()
+    ///         .item(Menu::new("Recent", "recent".into()).item(("doc1.txt", "doc1"))),
+    /// );
+    /// tray.set_enabled("file/open", false)?;
+    /// tray.set_visible("file/recent", false)?;
+    /// tray.set_menu_text("file/recent/doc1", "notes.txt")?;
+    /// tray.remove_menu_item("file/recent/doc1")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn remove_menu_item(&self, id: &str) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let removed = {
+            let handle = self.lock_handle()?;
+            unsafe { bind::remove_menu_item(handle.as_ptr(), c_id.as_ptr()) }
+        };
+        if !removed {
+            return Err(Error::UnknownMenuItem(id.to_string()));
+        }
+        self.payloads
+            .lock()
+            .unwrap()
+            .retain(|path, _| !menu_id::is_at_or_below(path, id));
+        Ok(())
+    }
+
+    /// Moves a menu item, or a submenu with everything in it, to position `index` within its
+    /// menu. Separators count as entries; an `index` past the last entry moves the item to the
+    /// end.
+    ///
+    /// Keyboard navigation (arrow keys and Tab) always follows the order the entries are shown
+    /// in, skipping separators and disabled or hidden items, so it follows the move as well.
+    /// There is no navigation order apart from that: group items that belong together next to
+    /// each other, e.g. between separators. The new order is kept if the tray is set up again.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`, or `Error::Ffi` if `id`
+    /// contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::{Menu, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp")
+    ///     .menu(("Open", "open"))
+    ///     .menu(Menu::new("Recent", "recent".into()).item(("doc1.txt", "doc1")))
+    ///     .menu(("Quit", "quit"));
+    /// // Open, Quit, Recent: navigating down from Open reaches Quit first.
+    /// tray.move_menu_item("recent", usize::MAX)?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn move_menu_item(&self, id: &str, index: usize) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let handle = self.lock_handle()?;
+        if !unsafe { bind::move_menu_item(handle.as_ptr(), c_id.as_ptr(), index) } {
+            return Err(Error::UnknownMenuItem(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Removes every menu item whose path starts with `prefix`, e.g. the `recent:doc1`,
+    /// `recent:doc2`, ... items added for a list of recent files.
+    ///
+    /// The prefix is matched against the whole path, so items in a submenu are removed with a
+    /// prefix such as `"file/recent:"`. Removing a submenu removes everything in it, along with
+    /// the payloads attached with `Menu::with_data`, as `remove_menu_item` does.
+    ///
+    /// # Returns
+    ///
+    /// The number of menu items removed, which is 0 if none matched, counting a submenu and its
+    /// contents as one item. `Error::Ffi` if `prefix` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::{Menu, SystemTray};
+    ///
+    /// let mut tray = SystemTray::new("MyOrg", "MyApp");
+    /// for doc in ["doc1.txt", "doc2.txt"] {
+    ///     tray = tray.menu(Menu::new(doc, format!("recent:{doc}").into()));
+    /// }
+    /// tray = tray.menu(("Quit", "quit"));
+    /// tray.remove_menu_items_by_prefix("recent:")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn remove_menu_items_by_prefix(&self, prefix: &str) -> Result<usize, Error> {
+        let c_prefix = CString::new(prefix)?;
+        let removed = {
+            let handle = self.lock_handle()?;
+            unsafe { bind::remove_menu_items_by_prefix(handle.as_ptr(), c_prefix.as_ptr()) }
+        };
+        self.payloads
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(prefix));
+        Ok(removed)
+    }
+
+    /// Inverts the check state of the menu item with the given `id` and returns the new state.
+    ///
+    /// This needs a single round trip to the Qt thread. An item that isn't checkable becomes
+    /// checkable (and thus checked).
+    ///
+    /// # Returns
+    ///
+    /// The new check state, `Error::UnknownMenuItem` if no menu item has the given `id`, or
+    /// `Error::Ffi` if `id` contains null bytes.
+    pub fn toggle_checked(&self, id: &str) -> Result<bool, Error> {
+        let c_id = CString::new(id)?;
+        let state = {
+            let handle = self.lock_handle()?;
+            unsafe { bind::toggle_menu_item_checked(handle.as_ptr(), c_id.as_ptr()) }
+        };
+        match state {
+            -1 => Err(Error::UnknownMenuItem(id.to_string())),
+            state => Ok(state == 1),
+        }
+    }
+
+    /// Sets the check state of the menu item with the given `id`.
+    ///
+    /// `CheckState::PartiallyChecked` is only shown by items made tristate with
+    /// `Menu::tristate`; other items show it as unchecked. An item that isn't checkable becomes
+    /// checkable. This may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no menu item has the given `id`, or `Error::Ffi` if `id`
+    /// contains null bytes.
+    pub fn set_check_state(&self, id: &str, state: CheckState) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let handle = self.lock_handle()?;
+        if unsafe {
+            bind::set_menu_item_check_state(handle.as_ptr(), c_id.as_ptr(), state.to_raw())
+        } {
+            Ok(())
+        } else {
+            Err(Error::UnknownMenuItem(id.to_string()))
+        }
+    }
+
+    /// Remembers the check state of the menu item with the given `id` across restarts.
+    ///
+    /// The state is saved with Qt's `QSettings` whenever it changes, whether by a click or by
+    /// `toggle_checked`, under the organization and app id passed to `new` (e.g. the registry
+    /// on Windows, `~/.config/<organization>/<app id>.conf` on Linux). When the item is
+    /// created, e.g. on `start`, its saved state replaces the one it was added with; until a
+    /// state has been saved, the item keeps its own. The item becomes checkable. This may be
+    /// called before the item is added, and applies to every item added later with this id.
+    /// Ids are shared by all trays of a `TrayApp`, since they use the same settings.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `id` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Menu, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp")
+    ///     .menu(Menu::new("Start at login", "autostart".into()).checkable(false));
+    /// tray.persist_checkable("autostart")?;
+    /// tray.start(); // restores the state saved by the previous run
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn persist_checkable(&self, id: &str) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::persist_menu_item_checked(handle.as_ptr(), c_id.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Adds a menu item that shows a progress bar, e.g. for a download, at the end of the menu.
+    ///
+    /// The bar is labelled with `label` followed by the percentage and starts at 0; update it
+    /// with `set_progress`. Clicking the bar emits `Event::MenuItemClicked(id)` like a regular
+    /// item, but leaves the menu open. Menus drawn by the platform rather than Qt, as on macOS
+    /// or with StatusNotifierItem hosts, can't embed widgets and show only the label.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if `id` or `label` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.add_progress_item("download", "Downloading update")?;
+    /// tray.start();
+    /// for percent in (0..=100).step_by(10) {
+    ///     tray.set_progress("download", percent)?;
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn add_progress_item(&self, id: &str, label: &str) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let c_label = CString::new(label)?;
+        let options = bind::MenuItemOptions {
+            enabled: true,
+            visible: true,
+            url: ptr::null(),
+            parent: ptr::null(),
+            submenu: false,
+            accessible_description: ptr::null(),
+            checkable: false,
+            checked: false,
+            separator: false,
+            bold: false,
+            italic: false,
+            progress: true,
+            role: bind::MenuRole_MenuRoleTextHeuristic,
+            shortcut: ptr::null(),
+            shortcut_context: bind::ShortcutContext_ShortcutContextWindow,
+            icon_data: ptr::null(),
+            icon_size: 0,
+            icon_format: ptr::null(),
+            tristate: false,
+        };
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::add_tray_menu_item(handle.as_ptr(), c_label.as_ptr(), c_id.as_ptr(), &options);
+        }
+        Ok(())
+    }
+
+    /// Sets the progress shown by the item added with `add_progress_item`, from 0 to 100.
+    ///
+    /// Values above 100 are shown as 100. The bar updates immediately, even while the menu is
+    /// open. Frequent updates are cheap: the id is passed on without allocating.
+    ///
+    /// # Returns
+    ///
+    /// `Error::UnknownMenuItem` if no progress item has the given `id`, or `Error::Ffi` if `id`
+    /// contains null bytes.
+    pub fn set_progress(&self, id: &str, value: u8) -> Result<(), Error> {
+        let found = self.with_c_str(id, |c_id| {
+            let handle = self.lock_handle()?;
+            Ok(unsafe {
+                bind::set_menu_item_progress(handle.as_ptr(), c_id.as_ptr(), c_int::from(value))
+            })
+        })?;
+        if !found {
+            return Err(Error::UnknownMenuItem(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sets the widget style used for the tray's menus, regardless of the system theme.
+    ///
+    /// The style applies to every tray of the `TrayApp` (see `TrayApp::set_style`), and must be
+    /// set before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running, or `Error::Ffi` if `style`
+    /// contains null bytes.
+    pub fn set_style(&self, style: &str) -> Result<(), Error> {
+        self.app.set_style(style)
+    }
+
+    /// Enables or disables a built-in dark palette for the tray's menus, regardless of the
+    /// system theme.
+    ///
+    /// The palette applies to every tray of the `TrayApp` (see `TrayApp::set_dark_palette`),
+    /// and must be set before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    pub fn set_dark_palette(&self, enabled: bool) -> Result<(), Error> {
+        self.app.set_dark_palette(enabled)
+    }
+
+    /// Enables or disables high-DPI scaling (see `TrayApp::set_high_dpi`).
+    ///
+    /// The setting applies to every tray of the `TrayApp`, and must be set before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    pub fn set_high_dpi(&self, enabled: bool) -> Result<(), Error> {
+        self.app.set_high_dpi(enabled)
+    }
+
+    /// Sets the human-friendly name of the application (see `TrayApp::set_display_name`).
+    ///
+    /// The name applies to every tray of the `TrayApp`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if `name` contains null bytes.
+    pub fn set_display_name(&self, name: &str) -> Result<(), Error> {
+        self.app.set_display_name(name)
+    }
+
+    /// Returns the name shown to users (see `TrayApp::display_name`).
+    pub fn display_name(&self) -> String {
+        self.app.display_name()
+    }
+
+    /// Makes Ctrl+C quit the event loop gracefully (see `TrayApp::set_quit_on_interrupt`).
+    ///
+    /// The setting applies to the whole `TrayApp`, and must be set before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    pub fn set_quit_on_interrupt(&self, enabled: bool) -> Result<(), Error> {
+        self.app.set_quit_on_interrupt(enabled)
+    }
+
+    /// Sets how long `start` waits for a systray host that isn't up yet (see
+    /// `TrayApp::set_tray_wait_timeout`).
+    ///
+    /// The setting applies to the whole `TrayApp`, and must be set before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::AlreadyStarted` if the event loop is already running.
+    pub fn set_tray_wait_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        self.app.set_tray_wait_timeout(timeout)
+    }
+
+    /// Draws the user's attention to the tray icon by blinking it.
+    ///
+    /// This is distinct from a notification: nothing pops up, the icon itself blinks about once
+    /// per second. With `Some(count)` the icon blinks `count` times; `Some(0)` stops a running
+    /// blink. With `None` it keeps blinking until the user activates the tray icon. Activating
+    /// the icon always stops the blinking, and a running busy spinner (see `set_busy`) resumes
+    /// afterwards.
+    ///
+    /// Qt doesn't expose the freedesktop StatusNotifierItem "needs attention" state, so the
+    /// blink is used on every platform. This only has an effect after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn request_attention(&self, count: Option<u32>) -> Result<(), Error> {
+        let count = count.map_or(-1, |count| count.min(i32::MAX as u32) as i32);
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::request_attention(handle.as_ptr(), count);
+        }
+        Ok(())
+    }
+
+    /// Sets the font of the tray's context menu and its submenus, e.g. for larger menu text.
+    ///
+    /// If `family` isn't installed, Qt substitutes the closest available font, falling back to
+    /// the menu's default family; pass an empty `family` to only change the size. A
+    /// `point_size` of 0 or less keeps the default size. The platform style may constrain or
+    /// ignore the font, e.g. where the menu is drawn natively (macOS). This may be called
+    /// before or after `start`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Ffi` if `family` contains null bytes.
+    pub fn set_menu_font(&self, family: &str, point_size: f32) -> Result<(), Error> {
+        let c_family = CString::new(family)?;
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_menu_font(handle.as_ptr(), c_family.as_ptr(), f64::from(point_size));
+        }
+        Ok(())
+    }
+
+    /// Lets the user tear the context menu off into a floating window, e.g. to keep frequently
+    /// used actions at hand.
+    ///
+    /// Enabled menus (and their submenus) show a dashed handle at the top; clicking it detaches
+    /// the menu. Clicks in a torn-off menu emit the usual `Event::MenuItemClicked` events. Only
+    /// menus drawn by Qt itself can be torn off, as on Windows and X11 trays using the legacy
+    /// XEmbed protocol. Native menus on macOS and menus exported over StatusNotifierItem (KDE
+    /// Plasma, GNOME with the AppIndicator extension) are drawn by the platform and ignore
+    /// this. This may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_tear_off_enabled(&self, enabled: bool) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_tear_off_enabled(handle.as_ptr(), enabled);
+        }
+        Ok(())
+    }
+
+    /// Sets the name screen readers announce for the tray icon.
+    ///
+    /// Qt has no accessibility API for the tray icon itself. Screen readers announce it by its
+    /// tooltip, so the name is applied as the tray's tooltip, as well as the context menu's
+    /// accessible name. This may be called before or after `start`, and as often as needed,
+    /// e.g. to show a status: like `set_progress`, it doesn't allocate on the Rust side.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Ffi` if `name` contains null bytes.
+    pub fn set_accessible_name(&self, name: &str) -> Result<(), Error> {
+        self.with_c_str(name, |c_name| {
+            let handle = self.lock_handle()?;
+            unsafe {
+                bind::set_accessible_name(handle.as_ptr(), c_name.as_ptr());
+            }
+            Ok(())
+        })
+    }
+
+    /// Makes this process the single instance of the app identified by `key`.
+    ///
+    /// Returns `Ok(true)` if this process is now the primary instance. From then on, later
+    /// launches calling this with the same key are reported as `Event::SecondInstanceLaunched`
+    /// with their arguments. Returns `Ok(false)` if another process already holds the key; it
+    /// has been sent this process's arguments, and this process should usually exit.
+    ///
+    /// This uses `QLocalServer`, so the key is a local socket name (a named pipe on Windows)
+    /// and should be unique to the app, e.g. its app id. This may be called before `start`, in
+    /// which case launches are only listened for once the tray is set up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Ffi` if `key` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "com.example.myapp");
+    /// if !tray.acquire_single_instance("com.example.myapp")? {
+    ///     return Ok(());
+    /// }
+    /// tray.start();
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn acquire_single_instance(&self, key: &str) -> Result<bool, Error> {
+        let c_key = CString::new(key)?;
+        let args: Vec<String> = std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let c_args = CString::new(args.join(&ARGS_SEPARATOR.to_string()))?;
+        let handle = self.lock_handle()?;
+        Ok(unsafe {
+            bind::acquire_single_instance(handle.as_ptr(), c_key.as_ptr(), c_args.as_ptr())
+        })
+    }
+
+    /// Shows a notification balloon from the tray icon.
+    ///
+    /// The notification is shown for `timeout`, although some platforms ignore it and use
+    /// their own duration. If the user clicks it before it disappears, an
+    /// `Event::NotificationClicked` carrying the returned id is emitted. Each tray shows one
+    /// notification at a time: showing another replaces the previous one. It shows the
+    /// information icon; use `show_notification` with `Notification::icon` for another icon.
+    /// With the `dbus-notifications` feature on Linux, the desktop's notification service shows
+    /// it instead whenever that service is running.
+    ///
+    /// # Returns
+    ///
+    /// An id identifying the notification, `Error::NotRunning` if the event loop hasn't created
+    /// the tray icon yet, or `Error::Ffi` if `title` or `body` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// let id = tray.notify("Backup", "Backup finished", Duration::from_secs(5))?;
+    /// if let Event::NotificationClicked { id: clicked } = tray.poll_event()? {
+    ///     assert_eq!(clicked, id);
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn notify(&self, title: &str, body: &str, timeout: Duration) -> Result<u32, Error> {
+        self.show_notification(Notification::new().title(title).body(body).timeout(timeout))
+    }
+
+    /// Shows a notification built with `Notification`, including its action buttons.
+    ///
+    /// This works like `notify`: a click on the notification itself emits an
+    /// `Event::NotificationClicked`, and a click on one of its buttons emits an
+    /// `Event::NotificationAction` carrying the button's id. Where buttons are unsupported, the
+    /// notification is shown without them.
+    ///
+    /// With the `dbus-notifications` feature on Linux, notifications go through the desktop's
+    /// notification service (`org.freedesktop.Notifications`), which shows the buttons, whenever
+    /// it is running; otherwise Qt's balloon is shown.
+    ///
+    /// # Returns
+    ///
+    /// An id identifying the notification, `Error::NotRunning` if the event loop hasn't created
+    /// the tray icon yet, or `Error::Ffi` if any of its text contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, Notification, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// let id = tray.show_notification(
+    ///     Notification::new()
+    ///         .title("Update available")
+    ///         .action("Install", "install"),
+    /// )?;
+    /// if let Event::NotificationAction { notification_id, action_id } = tray.poll_event()? {
+    ///     assert_eq!((notification_id, action_id.as_str()), (id, "install"));
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn show_notification(&self, notification: Notification) -> Result<u32, Error> {
+        self.send_notification(&notification, None, ptr::null_mut())
+    }
+
+    /// Hands `notification` to the backend, which calls `callback` with its outcome unless an
+    /// error is returned.
+    fn send_notification(
+        &self,
+        notification: &Notification,
+        callback: bind::NotificationCallback,
+        user_data: *mut c_void,
+    ) -> Result<u32, Error> {
+        let c_title = CString::new(notification.title.as_str())?;
+        let c_body = CString::new(notification.body.as_str())?;
+        let c_actions = notification
+            .actions
+            .iter()
+            .map(|(id, label)| Ok((CString::new(id.as_str())?, CString::new(label.as_str())?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let buttons: Vec<bind::NotificationButton> = c_actions
+            .iter()
+            .map(|(id, label)| bind::NotificationButton {
+                id: id.as_ptr(),
+                label: label.as_ptr(),
+            })
+            .collect();
+        let (icon_data, c_icon_format) = match &notification.icon {
+            NotificationIcon::Custom(data, format) => {
+                (data.as_slice(), Some(CString::new(format.as_str())?))
+            }
+            _ => (&[][..], None),
+        };
+        let spec = bind::NotificationSpec {
+            title: c_title.as_ptr(),
+            body: c_body.as_ptr(),
+            timeout_ms: notification.timeout.as_millis().min(i32::MAX as u128) as i32,
+            actions: buttons.as_ptr(),
+            action_count: buttons.len(),
+            icon: notification.icon.to_raw(),
+            icon_data: icon_data.as_ptr(),
+            icon_size: icon_data.len(),
+            icon_format: c_icon_format
+                .as_ref()
+                .map_or(ptr::null(), |format| format.as_ptr()),
+        };
+        let handle = self.lock_handle()?;
+        let id = unsafe { bind::show_notification(handle.as_ptr(), &spec, callback, user_data) };
+        if id == 0 {
+            return Err(Error::NotRunning);
+        }
+        Ok(id)
+    }
+
+    /// Shows a notification balloon and waits until it is clicked, times out or is dismissed.
+    ///
+    /// The notification uses the platform's default timeout of 10 seconds. Like with `notify`,
+    /// showing another notification replaces this one, which then resolves as
+    /// `NotificationOutcome::Dismissed`; so does stopping the tray. Clicks are also reported as
+    /// `Event::NotificationClicked`. The future doesn't depend on any particular runtime.
+    ///
+    /// Requires the `async` feature.
+    ///
+    /// # Returns
+    ///
+    /// How the notification ended, `Error::NotRunning` if the event loop hasn't created the
+    /// tray icon yet, or `Error::Ffi` if `title` or `body` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{NotificationOutcome, SystemTray};
+    ///
+    /// # async fn run(tray: SystemTray) -> Result<(), system_tray::Error> {
+    /// if tray.notify_async("Update", "Click to install").await? == NotificationOutcome::Clicked {
+    ///     // install the update
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn notify_async(
+        &self,
+        title: &str,
+        body: &str,
+    ) -> Result<NotificationOutcome, Error> {
+        let (outcome, user_data) = notification::OutcomeFuture::new();
+        let sent = self.send_notification(
+            &Notification::new().title(title).body(body),
+            Some(notification::outcome_callback),
+            user_data,
+        );
+        if let Err(e) = sent {
+            unsafe { notification::OutcomeFuture::release(user_data) };
+            return Err(e);
+        }
+        Ok(outcome.await)
+    }
+
+    /// Sets a size hint for the tray icon.
+    ///
+    /// The icon (and any busy spinner frames) is scaled to fit `px` by `px` pixels, keeping its
+    /// aspect ratio, before it is handed to the tray. Vector formats such as SVG are rendered
+    /// directly at that size. This helps on HiDPI screens or panels whose height doesn't suit the
+    /// icon's native size. The platform may still scale the icon to its final displayed size.
+    /// Passing 0 restores the icon's own size. This may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_preferred_icon_size(&self, px: u32) -> Result<(), Error> {
+        let size = px.min(i32::MAX as u32) as i32;
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_preferred_icon_size(handle.as_ptr(), size);
+        }
+        Ok(())
+    }
+
+    /// Shows or hides the busy spinner on the tray icon.
+    ///
+    /// While `busy` is true, the icon cycles through the spinner frames (the built-in spinner,
+    /// or the frames given to `set_busy_frames`). Setting it back to false restores the regular
+    /// icon. This may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_busy(&self, busy: bool) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_busy(handle.as_ptr(), busy);
+        }
+        Ok(())
+    }
+
+    /// Replaces the built-in busy spinner with custom frames.
+    ///
+    /// The frames are shown in order, about ten per second, while the tray is busy (see
+    /// `set_busy`). Frames that fail to decode are skipped; passing an empty slice restores the
+    /// built-in spinner.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The image data of each frame. The data is copied.
+    /// * `format` - The format of the frame data (e.g., "png", "svg").
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if `format` contains null bytes.
+    pub fn set_busy_frames(&self, frames: &[&[u8]], format: &str) -> Result<(), Error> {
+        let c_format = CString::new(format)?;
+        let frame_ptrs: Vec<*const u8> = frames.iter().map(|frame| frame.as_ptr()).collect();
+        let sizes: Vec<usize> = frames.iter().map(|frame| frame.len()).collect();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_busy_frames(
+                handle.as_ptr(),
+                frame_ptrs.as_ptr(),
+                sizes.as_ptr(),
+                frames.len(),
+                c_format.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Shows the context menu at the given screen position.
+    ///
+    /// This complements the default right-click behavior, e.g. to open the menu in response to a
+    /// keyboard shortcut. The request is handled on the Qt thread, so it has no effect before
+    /// `start` has been called.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The horizontal screen coordinate of the menu's top-left corner.
+    /// * `y` - The vertical screen coordinate of the menu's top-left corner.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn popup_menu(&self, x: i32, y: i32) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::popup_tray_menu(handle.as_ptr(), x, y);
+        }
+        Ok(())
+    }
+
+    /// Shows the context menu at the current cursor position.
+    ///
+    /// Like `popup_menu`, this has no effect before `start` has been called.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn popup_menu_at_cursor(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::popup_tray_menu_at_cursor(handle.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Blocks until every operation requested so far has been applied on the Qt thread.
+    ///
+    /// Most methods, such as `icon` or `set_menu_font`, already wait for the Qt thread before
+    /// returning. A few only post their request, like `popup_menu`; after `flush` returns,
+    /// those have run too. Qt may still need a moment to repaint, and the platform to show the
+    /// result, so this doesn't guarantee that the change is visible on screen yet. Returns
+    /// immediately before `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// tray.popup_menu_at_cursor()?;
+    /// tray.flush()?;
+    /// // The menu has been asked to open; take the screenshot now.
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn flush(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::flush_qt_app(handle.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Reads a preference saved with `set_setting`.
+    ///
+    /// See `TrayApp::get_setting`.
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        self.app.get_setting(key)
+    }
+
+    /// Saves a preference so that `get_setting` returns it in later runs.
+    ///
+    /// See `TrayApp::set_setting`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if `key` or `value` contains null bytes, or `Error::Settings` if the
+    /// settings couldn't be written.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), Error> {
+        self.app.set_setting(key, value)
+    }
+
+    /// Describes the most recent failure of this tray on the Qt side, for diagnostics.
+    ///
+    /// Several operations can't report failures directly because they are applied later on the
+    /// Qt thread, e.g. an icon that can't be decoded, a menu item whose parent submenu doesn't
+    /// exist, or a listener for other instances that can't be set up. Those failures are
+    /// recorded here; the message is kept until the next failure replaces it. Failures of the
+    /// Qt application as a whole are reported by `TrayApp::last_backend_error`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").icon(b"not an image", "png");
+    /// tray.start();
+    /// if let Some(error) = tray.last_backend_error()? {
+    ///     eprintln!("tray problem: {}", error);
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn last_backend_error(&self) -> Result<Option<String>, Error> {
+        let handle = self.lock_handle()?;
+        Ok(unsafe { take_backend_string(bind::last_error(handle.as_ptr())) })
+    }
+
+    /// Returns the ID of the currently highlighted menu item.
+    ///
+    /// This is the item the user is hovering or has navigated to with the keyboard, which is
+    /// useful for status-bar-style previews. Returns `None` if the menu is closed, no item is
+    /// highlighted, or the event loop isn't running.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn active_menu_item(&self) -> Result<Option<String>, Error> {
+        let handle = self.lock_handle()?;
+        Ok(unsafe { take_backend_string(bind::get_active_menu_item(handle.as_ptr())) })
+    }
+
+    /// Highlights the menu item `id` whenever the context menu opens, so that arrow-key
+    /// navigation starts from there.
+    ///
+    /// If the menu is open, the item is highlighted right away. The item is a path as
+    /// described in `Menu::item`; an item inside a submenu highlights the top-level submenu
+    /// containing it. Together with `active_menu_item`, this lets keyboard-driven and
+    /// accessible flows pick up where the user left off. May be called before or after
+    /// `start`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::UnknownMenuItem` if there is no item with the given `id`, or `Error::Ffi` if
+    /// `id` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp")
+    ///     .menu(("Open", "open"))
+    ///     .menu(("Pause", "pause"));
+    /// tray.set_active_menu_item("pause")?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_active_menu_item(&self, id: &str) -> Result<(), Error> {
+        let c_id = CString::new(id)?;
+        let found = {
+            let handle = self.lock_handle()?;
+            unsafe { bind::set_active_menu_item(handle.as_ptr(), c_id.as_ptr()) }
+        };
+        if !found {
+            return Err(Error::UnknownMenuItem(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Returns whether the context menu is currently open.
+    ///
+    /// This helps to defer changes such as `replace_menu` until the user is done with the
+    /// menu; `Event::MenuClosed` reports when that happens. A torn-off copy of the menu (see
+    /// `set_tear_off_enabled`) doesn't count as open. Returns `false` if the event loop isn't
+    /// running.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// let mut rebuild_pending = true;
+    /// loop {
+    ///     let event = tray.poll_event()?;
+    ///     if rebuild_pending && !tray.is_menu_open()? {
+    ///         tray.replace_menu(vec![("Quit", "quit").into()])?;
+    ///         rebuild_pending = false;
+    ///     }
+    ///     if event == Event::Quit {
+    ///         break;
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn is_menu_open(&self) -> Result<bool, Error> {
+        let handle = self.lock_handle()?;
+        Ok(unsafe { bind::is_menu_open(handle.as_ptr()) })
+    }
+
+    /// Limits how many events can wait in the queue until they are polled.
+    ///
+    /// By default the queue is unbounded, so events pile up if `poll_event` isn't called often
+    /// enough. With `OverflowPolicy::DropOldest` or `OverflowPolicy::DropNewest`, at most
+    /// `capacity` events are kept and the policy decides which ones are discarded. Events
+    /// already queued beyond the new capacity are discarded immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of queued events. Ignored for `OverflowPolicy::Unbounded`.
+    /// * `policy` - What to do with new events once the queue is full.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_event_queue_capacity(
+        &self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_event_queue_capacity(handle.as_ptr(), capacity, policy.to_raw());
+        }
+        Ok(())
+    }
+
+    /// Chooses which kinds of events are delivered, e.g. to only receive menu clicks.
+    ///
+    /// The Qt side drops events of other kinds as they happen. They don't fill the queue,
+    /// aren't buffered while events are paused, never reach an event sink, and don't cross the
+    /// FFI boundary at all. Every kind is delivered by default; `EventMask::ALL` restores that.
+    /// With `set_double_click_emulation` enabled, emulated double clicks are built from
+    /// single clicks, so `EventMask::TRAY_DOUBLE_CLICKED` then needs
+    /// `EventMask::TRAY_CLICKED` too. This may be called before or after `start`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, EventMask, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.set_event_mask(EventMask::MENU_ITEM_CLICKED | EventMask::QUIT)?;
+    /// tray.start();
+    /// loop {
+    ///     match tray.poll_event()? {
+    ///         Event::MenuItemClicked(id) if id == "quit" => break,
+    ///         Event::Quit => break,
+    ///         _ => std::thread::sleep(tray.recommended_poll_interval()),
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_event_mask(&self, mask: EventMask) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_event_mask(handle.as_ptr(), mask.bits());
+        }
+        Ok(())
+    }
+
+    /// Pauses the delivery of user input events, e.g. while a modal operation is in progress.
+    ///
+    /// While paused, clicks on the tray icon, its menu items and its notifications, as well as
+    /// menu item hovers, aren't reported; by default they are discarded, see `set_pause_policy`
+    /// to buffer them instead. Other events, such as `Event::Quit`, are still delivered, and
+    /// menu items created with `Menu::open_url` still open their URL. Nothing is torn down;
+    /// `resume_events` restores delivery.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// tray.pause_events()?;
+    /// // run the modal operation
+    /// tray.resume_events()?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn pause_events(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_events_paused(handle.as_ptr(), true);
+        }
+        Ok(())
+    }
+
+    /// Resumes the delivery of user input events paused with `pause_events`.
+    ///
+    /// With `PausePolicy::Buffer`, the events held back while paused are delivered first, in
+    /// order.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn resume_events(&self) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_events_paused(handle.as_ptr(), false);
+        }
+        Ok(())
+    }
+
+    /// Chooses whether user input events are discarded or buffered while delivery is paused.
+    ///
+    /// Switching to `PausePolicy::Discard` while paused drops the events buffered so far.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_pause_policy(&self, policy: PausePolicy) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_pause_policy(handle.as_ptr(), policy.to_raw());
+        }
+        Ok(())
+    }
+
+    /// Delivers events to `sink` instead of queuing them for `poll_event`.
+    ///
+    /// The Qt thread calls `sink.handle` directly with each event, so no polling loop is needed.
+    /// Events queued before this call are delivered to the sink first. Setting another sink
+    /// replaces this one; see `clear_event_sink` to go back to polling.
+    ///
+    /// Don't call this from within `EventSink::handle`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn set_event_sink(&self, sink: Arc<dyn EventSink + Send + Sync>) -> Result<(), Error> {
+        let context = Box::new(SinkContext {
+            sink,
+            payloads: self.payloads.clone(),
+            double_click: self.double_click.clone(),
+        });
+        let mut event_sink = self.event_sink.lock().unwrap();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_event_callback(
+                handle.as_ptr(),
+                Some(sink_callback),
+                &*context as *const SinkContext as *mut c_void,
+            );
+        }
+        // The previous sink is no longer called once set_event_callback returns.
+        *event_sink = Some(context);
+        Ok(())
+    }
+
+    /// Removes the sink set with `set_event_sink`, so that events are queued for `poll_event`
+    /// again.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub fn clear_event_sink(&self) -> Result<(), Error> {
+        let mut event_sink = self.event_sink.lock().unwrap();
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_event_callback(handle.as_ptr(), None, ptr::null_mut());
+        }
+        *event_sink = None;
+        Ok(())
+    }
+
+    /// Returns whether the platform provides a system tray.
+    ///
+    /// The check needs the event loop to be running, so this returns false before `start`. It
+    /// always returns false when the crate is built with the `stub` feature, and after `close`.
+    pub fn is_available(&self) -> bool {
+        match self.lock_handle() {
+            Ok(handle) => unsafe { bind::is_system_tray_available(handle.as_ptr()) },
+            Err(_) => false,
+        }
+    }
+
+    /// Lists the screens attached to the system, e.g. to keep a popup placed near the tray icon
+    /// from going off-edge on multi-monitor setups. The primary screen comes first.
+    ///
+    /// Requires the event loop to be running (see `start`); returns an empty list otherwise.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// if let Event::TrayClicked { x, y } = tray.poll_event()? {
+    ///     let screen = tray
+    ///         .screens()?
+    ///         .into_iter()
+    ///         .find(|screen| screen.geometry.contains(x, y));
+    ///     println!("clicked on {:?}", screen);
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn screens(&self) -> Result<Vec<ScreenInfo>, Error> {
+        let handle = self.lock_handle()?;
+        let mut raw = Vec::new();
+        loop {
+            // The screens can change between calls, so retry until the buffer is large enough.
+            let count =
+                unsafe { bind::get_screens(handle.as_ptr(), raw.as_mut_ptr(), raw.capacity()) };
+            if count <= raw.capacity() {
+                unsafe { raw.set_len(count) };
+                return Ok(raw.iter().map(ScreenInfo::from_raw).collect());
+            }
+            raw.reserve_exact(count);
+        }
+    }
+
+    /// Opens a local file or folder with its default handler, e.g. to reveal a folder in the
+    /// file manager.
+    ///
+    /// This wraps `QDesktopServices::openUrl(QUrl::fromLocalFile(...))` and requires the event
+    /// loop to be running (see `start`).
+    ///
+    /// # Returns
+    ///
+    /// `Error::OpenPath` if the path isn't valid UTF-8, the event loop isn't running, or no
+    /// handler could be launched.
+    pub fn open_path(&self, path: &Path) -> Result<(), Error> {
+        let utf8_path = path
+            .to_str()
+            .ok_or_else(|| Error::OpenPath(path.to_path_buf()))?;
+        let c_path = CString::new(utf8_path)?;
+        let handle = self.lock_handle()?;
+        if unsafe { bind::open_path(handle.as_ptr(), c_path.as_ptr()) } {
+            Ok(())
+        } else {
+            Err(Error::OpenPath(path.to_path_buf()))
+        }
+    }
+
+    /// Shows a modal Yes/No dialog and returns whether the user chose Yes, e.g. to ask "Are you
+    /// sure you want to quit?" before quitting from the menu.
+    ///
+    /// The dialog is a `QMessageBox`. Qt widgets may only be used on the Qt thread, so this
+    /// may be called from any thread: the dialog is always shown on the Qt thread, and the
+    /// calling thread is blocked until it is answered. Closing the dialog counts as No. The
+    /// event loop keeps running meanwhile, so events keep being queued for `poll_event`; when
+    /// this is called from an `EventSink`, which runs on the Qt thread, the sink may be called
+    /// again before this returns, so don't hold a lock the sink takes while calling it.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if `title` or `text` contain null bytes, and `Error::NotRunning` if the
+    /// event loop isn't running.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.start();
+    /// if let Event::MenuItemClicked(id) = tray.poll_event()? {
+    ///     if id == "quit" && tray.confirm("Quit", "Are you sure you want to quit?")? {
+    ///         tray.stop();
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn confirm(&self, title: &str, text: &str) -> Result<bool, Error> {
+        let c_title = CString::new(title)?;
+        let c_text = CString::new(text)?;
+        // The handle isn't kept locked while the dialog is shown, so that other threads can
+        // keep using the tray; `close` waits for the dialog instead.
+        let _dialog = self.dialogs.read().unwrap();
+        let handle = self.lock_handle()?.as_ptr();
+        match unsafe { bind::confirm_dialog(handle, c_title.as_ptr(), c_text.as_ptr()) } {
+            -1 => Err(Error::NotRunning),
+            answer => Ok(answer == 1),
+        }
+    }
+
+    /// Returns the underlying `QSystemTrayIcon*` for calling Qt APIs this crate doesn't wrap.
+    ///
+    /// This is an escape hatch for advanced users. The pointer is null until `start` has created
+    /// the tray icon, and it is distinct from the application handle used internally.
+    ///
+    /// # Safety
+    ///
+    /// The `QSystemTrayIcon` is owned by the Qt thread:
+    ///
+    /// * Only use it from the Qt thread, e.g. by posting a call with
+    ///   `QMetaObject::invokeMethod(icon, ..., Qt::QueuedConnection)` from C++ code.
+    /// * Don't delete it or change its parent.
+    /// * Don't use it after `stop` has been called or the `SystemTray` has been dropped.
+    /// * Changes made through it may be overwritten by later calls on this `SystemTray`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    pub unsafe fn tray_icon_ptr(&self) -> Result<*mut c_void, Error> {
+        let handle = self.lock_handle()?;
+        Ok(bind::get_tray_icon_ptr(handle.as_ptr()))
+    }
+
+    /// Sets what clicking the tray icon with `button` does.
+    ///
+    /// By default, the left button emits `Event::TrayClicked`, the right button opens the
+    /// context menu and the middle button does nothing. Double clicks are always reported as
+    /// `Event::TrayDoubleClicked`. May be called before or after `start`.
+    ///
+    /// Platforms show the context menu on their own when it's attached to the icon, so it is
+    /// detached while the right button does anything but `ButtonBehavior::OpenMenu`. On macOS,
+    /// an attached menu also opens on a left click. Some hosts (e.g. several
+    /// StatusNotifierItem panels) don't report middle clicks at all.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Ffi` if the item id of `ButtonBehavior::TriggerItem` contains null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{ButtonBehavior, MouseButton, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Show window", "show"));
+    /// // Left click opens the menu, middle click shows the window.
+    /// tray.set_button_event(MouseButton::Left, ButtonBehavior::OpenMenu)?;
+    /// tray.set_button_event(MouseButton::Middle, ButtonBehavior::TriggerItem("show".into()))?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn set_button_event(
+        &self,
+        button: MouseButton,
+        behavior: ButtonBehavior,
+    ) -> Result<(), Error> {
+        let (raw_behavior, c_item) = match behavior {
+            ButtonBehavior::EmitClick => (bind::ButtonBehavior_ButtonEmitClick, None),
+            ButtonBehavior::OpenMenu => (bind::ButtonBehavior_ButtonOpenMenu, None),
+            ButtonBehavior::TriggerItem(id) => (
+                bind::ButtonBehavior_ButtonTriggerItem,
+                Some(CString::new(id)?),
+            ),
+            ButtonBehavior::Ignore => (bind::ButtonBehavior_ButtonIgnore, None),
+        };
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::set_button_behavior(
+                handle.as_ptr(),
+                button.to_raw(),
+                raw_behavior,
+                c_item.as_ref().map_or(ptr::null(), |item| item.as_ptr()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Enables or disables double click emulation.
+    ///
+    /// Some tray hosts (notably several Wayland compositors) never deliver a native double
+    /// click. When emulation is enabled, two `Event::TrayClicked` events within the double click
+    /// interval are reported as `Event::TrayClicked` followed by `Event::TrayDoubleClicked`.
+    /// Native double clicks are still reported as usual. Emulation is disabled by default.
+    ///
+    /// Clicks are timed when they are polled, so the interval should comfortably exceed the
+    /// interval at which `poll_event` is called.
+    pub fn set_double_click_emulation(&self, enabled: bool) {
+        let mut double_click = self.double_click.lock().unwrap();
+        double_click.enabled = enabled;
+        double_click.last_click = None;
+    }
+
+    /// Sets the window in which two clicks are combined into an emulated double click.
+    ///
+    /// The default is 400 milliseconds. This only has an effect while double click emulation is
+    /// enabled (see `set_double_click_emulation`).
+    pub fn set_double_click_interval(&self, interval: Duration) {
+        self.double_click.lock().unwrap().interval = interval;
+    }
+
+    /// Starts the Qt event loop of the tray's `TrayApp` in a new thread.
+    ///
+    /// This is a non-blocking operation. Events can be polled using `poll_event`. Does nothing
+    /// if the event loop has already been started, e.g. by another tray of the same `TrayApp`.
+    ///
+    /// If `init` hasn't been called yet, it is called first; if it fails, the error is printed,
+    /// the event loop isn't started and `last_exit_code` returns `Some(-1)`.
+    pub fn start(&self) {
+        self.app.start();
+    }
+
+    /// Starts the Qt event loop like `start`, returning a guard that stops it when dropped.
+    ///
+    /// Once the guard has been dropped, the tray can be started again, keeping its icon, menu
+    /// and other settings. Like `stop`, dropping the guard stops the whole `TrayApp`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::AlreadyStarted` if the event loop has already been started, or the error
+    /// returned by `init`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// fn show_while_busy(tray: &SystemTray) -> Result<(), system_tray::Error> {
+    ///     let _guard = tray.start_scoped()?;
+    ///     // do the work while the tray is shown
+    ///     Ok(())
+    /// } // the event loop stops here
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// show_while_busy(&tray)?;
+    /// show_while_busy(&tray)?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn start_scoped(&self) -> Result<StartedTray, Error> {
+        self.ensure_open()?;
+        self.app.start_scoped()
+    }
+
+    /// Returns the exit code of the Qt event loop once its thread has finished.
+    ///
+    /// See `TrayApp::last_exit_code`.
+    pub fn last_exit_code(&self) -> Option<i32> {
+        self.app.last_exit_code()
+    }
+
+    /// Requests the Qt application to quit and waits for the Qt event loop thread to finish.
+    ///
+    /// This stops the whole `TrayApp`, including any other trays spawned from it. This method
+    /// is blocking until the Qt thread has terminated.
+    pub fn stop(&self) {
+        self.app.stop();
+    }
+
+    /// Requests the Qt application to quit without waiting, and returns whether the Qt event
+    /// loop thread has finished.
+    ///
+    /// Like `stop`, this stops the whole `TrayApp`. See `TrayApp::try_stop`.
+    pub fn try_stop(&self) -> bool {
+        self.app.try_stop()
+    }
+
+    /// Requests the Qt application to quit and waits for the Qt event loop thread to finish
+    /// without blocking the calling thread, for async code.
+    ///
+    /// Like `stop`, this stops the whole `TrayApp`. The tray's resources stay alive until the
+    /// Qt thread has finished, even if the future is dropped early. See `TrayApp::stop_async`.
+    ///
+    /// Requires the `async` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::SystemTray;
+    ///
+    /// # async fn run(tray: SystemTray) {
+    /// tray.stop_async().await;
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn stop_async(&self) {
+        let thread = self.app.request_stop();
+        stop::StopFuture::join(thread, self.clone()).await
+    }
+
+    /// Stops either this tray or the whole Qt application, depending on `scope`.
+    ///
+    /// With `StopScope::ThisTray`, the icon and its menu are removed and no further events are
+    /// delivered for this tray, while the event loop keeps running for the other trays of its
+    /// `TrayApp`; this doesn't block. Events already queued can still be polled. The tray can't
+    /// be shown again afterwards; for a tray created with `SystemTray::new`, its private event
+    /// loop keeps running without an icon until it is dropped. With `StopScope::Application`,
+    /// this is the same as `stop`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::Closed` after `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{StopScope, TrayApp};
+    ///
+    /// let app = TrayApp::new("MyOrg", "MyApp");
+    /// let status = app.tray();
+    /// let sync = app.tray();
+    /// app.start();
+    /// // Remove the sync icon once syncing is done, keeping the status icon.
+    /// sync.stop_with(StopScope::ThisTray)?;
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn stop_with(&self, scope: StopScope) -> Result<(), Error> {
+        let handle = self.lock_handle()?;
+        match scope {
+            StopScope::ThisTray => unsafe {
+                bind::remove_app_tray(self.app.handle.lock().unwrap().as_ptr(), handle.as_ptr());
+            },
+            StopScope::Application => {
+                drop(handle);
+                self.app.stop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls for a new event from the system tray.
+    ///
+    /// This method is non-blocking and returns an `Event` immediately. Clicks on menu items that
+    /// carry a payload are reported as `Event::MenuItemClickedWithData`. A menu click the backend
+    /// reports without an item id is skipped, and the next event is returned instead.
+    ///
+    /// Polling an empty queue is cheap enough for tight loops: it takes no lock besides the
+    /// handle's own mutex and performs no allocation before returning `Event::None`. The handle is
+    /// only locked while the event is taken off the queue, so other threads can keep calling
+    /// methods such as `set_tray_icon` while one thread polls.
+    ///
+    /// While an event sink is set (see `set_event_sink`), events go to the sink and this returns
+    /// `Event::None`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing an `Event`, `Error::Closed` after `close`, or a `SystemTrayError` if
+    /// an unknown event type is received.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// let poller = tray.clone();
+    /// let events = thread::spawn(move || loop {
+    ///     if let Ok(Event::Quit) = poller.poll_event() {
+    ///         break;
+    ///     }
+    /// });
+    /// tray.set_tray_icon(include_bytes!("../icon.svg"), "svg")?;
+    /// # events.join().unwrap();
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn poll_event(&self) -> Result<Event, Error> {
+        loop {
+            // The event owns its strings, so it can be decoded after the handle is unlocked.
+            let event = {
+                let handle = self.lock_handle()?;
+                unsafe { bind::poll_event(handle.as_ptr()) }
+            };
+            let queued = event.type_ != bind::AppEventType_None;
+
+            match decode_event(event, &self.payloads, &self.double_click)? {
+                // A skipped event, so the next one is polled.
+                Event::None if queued => continue,
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Returns how long a polling loop should pause after `poll_event` returned `Event::None`.
+    ///
+    /// The interval balances latency against CPU usage: a click waits at most this long to be
+    /// noticed, which is well below what users perceive as a delay, while polling an empty
+    /// queue this often costs next to nothing (see `poll_event`). `poll_event_blocking_up_to`
+    /// uses it, so loops built on that don't need to sleep at all.
+    pub fn recommended_poll_interval(&self) -> Duration {
+        RECOMMENDED_POLL_INTERVAL
+    }
+
+    /// Waits up to `timeout` for an event, returning `Event::None` if none arrived in time.
+    ///
+    /// This polls the queue every `recommended_poll_interval`, sleeping in between, so a loop
+    /// calling it doesn't need a sleep of its own. The handle isn't locked while sleeping, so
+    /// other threads can keep using the tray. Returns as soon as `timeout` has elapsed, even
+    /// if it's shorter than the interval; a zero `timeout` behaves like `poll_event`.
+    ///
+    /// While an event sink is set (see `set_event_sink`), events go to the sink, so this always
+    /// waits for the whole `timeout` and returns `Event::None`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the first `Event` to arrive or `Event::None`, or the error returned
+    /// by `poll_event`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.start();
+    /// loop {
+    ///     match tray.poll_event_blocking_up_to(Duration::from_secs(1))? {
+    ///         Event::None => {} // Time for periodic work.
+    ///         Event::MenuItemClicked(id) if id == "quit" => break,
+    ///         Event::Quit => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn poll_event_blocking_up_to(&self, timeout: Duration) -> Result<Event, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let event = self.poll_event()?;
+            let now = Instant::now();
+            if !matches!(event, Event::None) || now >= deadline {
+                return Ok(event);
+            }
+            std::thread::sleep(self.recommended_poll_interval().min(deadline - now));
+        }
+    }
+
+    /// Takes every pending event off the queue and calls `f` for each, then returns.
+    ///
+    /// This gives callback-style handling while the caller keeps control of when events are
+    /// processed, e.g. once per iteration of its own loop. It never blocks; events that arrive
+    /// while `f` runs are processed too. `f` may call methods on the tray.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the number of events passed to `f`, or the error returned by
+    /// `poll_event`. Events processed before the error have already been passed to `f`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp").menu(("Quit", "quit"));
+    /// tray.start();
+    /// let mut running = true;
+    /// while running {
+    ///     tray.process_events(|event| match event {
+    ///         Event::MenuItemClicked(id) if id == "quit" => running = false,
+    ///         Event::Quit => running = false,
+    ///         _ => {}
+    ///     })?;
+    ///     // The application's own work goes here.
+    ///     std::thread::sleep(Duration::from_millis(16));
+    /// }
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn process_events(&self, mut f: impl FnMut(Event)) -> Result<usize, Error> {
+        let mut count = 0;
+        loop {
+            match self.poll_event()? {
+                Event::None => return Ok(count),
+                event => {
+                    f(event);
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    /// Removes the tray icon and frees its Qt resources right away, instead of when the last
+    /// clone is dropped.
+    ///
+    /// Unlike `stop`, this leaves the other trays of the `TrayApp` alone: the event loop is
+    /// only stopped, waiting for the Qt thread to finish, if this was the last tray spawned
+    /// from it. The tray is removed for good, for every clone: methods that return a `Result`
+    /// fail with `Error::Closed`, other methods do nothing, and dropping the tray frees nothing
+    /// more. Settings of the `TrayApp` itself, such as `set_setting`, keep working. Calling
+    /// `close` again does nothing. If a dialog such as `confirm` is open, `close` waits until
+    /// it has been answered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use system_tray::{Error, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.start();
+    /// tray.close();
+    /// assert!(matches!(tray.poll_event(), Err(Error::Closed)));
+    /// ```
+    pub fn close(&self) {
+        let last_tray = {
+            let mut registry = self.app.trays.lock().unwrap();
+            registry.trays.retain(|tray| tray.id != self.id);
+            registry.trays.is_empty()
+        };
+        {
+            let _dialogs = self.dialogs.write().unwrap();
+            let mut handle = self.handle.lock().unwrap();
+            if handle.as_ptr().is_null() {
+                return;
+            }
+            unsafe {
+                bind::remove_app_tray(self.app.handle.lock().unwrap().as_ptr(), handle.as_ptr());
+                bind::cleanup_qt_app(handle.as_ptr());
+                *handle = SafeQtAppHandle::new(ptr::null_mut());
+            }
+        }
+        // The backend no longer calls the sink, so its context can go.
+        self.event_sink.lock().unwrap().take();
+        if last_tray {
+            self.app.stop();
+        }
+    }
+
+    /// Locks the handle, or returns `Error::Closed` once `close` has freed it.
+    fn lock_handle(&self) -> Result<MutexGuard<'_, SafeQtAppHandle>, Error> {
+        let handle = self.handle.lock().unwrap();
+        if handle.as_ptr().is_null() {
+            return Err(Error::Closed);
+        }
+        Ok(handle)
+    }
+
+    /// Returns `Error::Closed` once `close` has freed the handle.
+    fn ensure_open(&self) -> Result<(), Error> {
+        self.lock_handle().map(|_| ())
+    }
+
+    /// Calls `f` with `text` as a C string held in the reusable `c_buffer`, avoiding the
+    /// allocation of a `CString` per call.
+    ///
+    /// Returns `Error::Ffi` if `text` contains null bytes.
+    fn with_c_str<R>(
+        &self,
+        text: &str,
+        f: impl FnOnce(&CStr) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut buffer = self.c_buffer.lock().unwrap();
+        buffer.clear();
+        buffer.extend_from_slice(text.as_bytes());
+        buffer.push(0);
+        match CStr::from_bytes_with_nul(&buffer) {
+            Ok(c_text) => f(c_text),
+            Err(_) => Err(CString::new(text).unwrap_err().into()),
+        }
+    }
+}
+
+/// Converts an event from the backend into an `Event`, taking ownership of its strings.
+///
+/// Events that carry nothing to report, such as a menu click without an id, become `Event::None`.
+fn decode_event(
+    event: bind::AppEvent,
+    payloads: &Mutex<HashMap<String, Vec<u8>>>,
+    double_click: &Mutex<DoubleClickEmulation>,
+) -> Result<Event, Error> {
+    match event.type_ {
+        bind::AppEventType_None => Ok(Event::None),
+        bind::AppEventType_TrayClicked => {
+            Ok(double_click.lock().unwrap().on_click(event.x, event.y))
+        }
+        bind::AppEventType_TrayDoubleClicked => Ok(double_click
+            .lock()
+            .unwrap()
+            .on_double_click(event.x, event.y)),
+        bind::AppEventType_TrayButtonClicked => Ok(Event::TrayButtonClicked {
+            button: MouseButton::from_raw(event.button),
+            x: event.x,
+            y: event.y,
+        }),
+        bind::AppEventType_Quit => Ok(Event::Quit),
+        bind::AppEventType_TrayReconnected => Ok(Event::TrayReconnected),
+        bind::AppEventType_VisibilityChanged => Ok(Event::VisibilityChanged {
+            visible: event.visible,
+        }),
+        bind::AppEventType_NotificationClicked => Ok(Event::NotificationClicked {
+            id: event.notification_id,
+        }),
+        bind::AppEventType_SecondInstanceLaunched => {
+            let args = unsafe { take_backend_string(event.args_str as *mut c_char) }
+                .filter(|args| !args.is_empty())
+                .map_or_else(Vec::new, |args| {
+                    args.split(ARGS_SEPARATOR).map(str::to_string).collect()
+                });
+            Ok(Event::SecondInstanceLaunched { args })
+        }
+        bind::AppEventType_MenuAboutToShow => Ok(Event::MenuAboutToShow),
+        bind::AppEventType_MenuItemHovered => {
+            let id = unsafe { take_backend_string(event.menu_id_str as *mut c_char) };
+            Ok(Event::MenuItemHovered(id.unwrap_or_default().into()))
+        }
+        bind::AppEventType_MenuClosed => Ok(Event::MenuClosed),
+        bind::AppEventType_ColorSchemeChanged => Ok(Event::ColorSchemeChanged { dark: event.dark }),
+        bind::AppEventType_NotificationActionInvoked => {
+            let action_id = unsafe { take_backend_string(event.menu_id_str as *mut c_char) };
+            Ok(Event::NotificationAction {
+                notification_id: event.notification_id,
+                action_id: action_id.unwrap_or_default(),
+            })
+        }
+        bind::AppEventType_MenuItemClicked => {
+            if event.menu_id_str.is_null() {
+                // A click that can't be attributed to an item has nothing to report, so it is
+                // skipped like an empty queue.
+                return Ok(Event::None);
+            }
+            // The id was allocated with malloc by the C++ side, so it must be released with
+            // free_char_ptr rather than by Rust's allocator. Ids are passed through byte for
+            // byte, so anything other than the original UTF-8 is reported instead of mangled.
+            let id = unsafe { CStr::from_ptr(event.menu_id_str) }
+                .to_str()
+                .map(str::to_string)
+                .map_err(|e| {
+                    Error::PollEventError(format!("Menu item id is not valid UTF-8: {}", e))
+                });
+            unsafe { bind::free_char_ptr(event.menu_id_str) };
+            let id = id?;
+            match payloads.lock().unwrap().get(&id) {
+                Some(data) => Ok(Event::MenuItemClickedWithData {
+                    id: id.into(),
+                    data: data.clone(),
+                }),
+                None => Ok(Event::MenuItemClicked(id.into())),
+            }
+        }
+        _ => Err(Error::PollEventError(format!(
+            "Unknown event type value: {}",
+            event.type_
+        ))),
+    }
+}
+
+/// The state behind the `user_data` of the backend's event callback.
+struct SinkContext {
+    sink: Arc<dyn EventSink + Send + Sync>,
+    payloads: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    double_click: Arc<Mutex<DoubleClickEmulation>>,
+}
+
+/// The `EventCallback` passed to `set_event_callback`. Called on the Qt thread.
+unsafe extern "C" fn sink_callback(user_data: *mut c_void, event: bind::AppEvent) {
+    let context = &*(user_data as *const SinkContext);
+    // Unknown and skipped events have no `Event` to deliver.
+    match decode_event(event, &context.payloads, &context.double_click) {
+        Ok(Event::None) | Err(_) => {}
+        Ok(event) => context.sink.handle(event),
+    }
+}
+
+impl Default for SystemTray {
+    /// Creates a default `SystemTray` instance with "MyOrganization" and "MyApp" as identifiers.
+    fn default() -> Self {
+        Self::new("MyOrganization", "MyApp")
+    }
+}
+
+impl Drop for SystemTray {
+    /// Removes the tray icon and frees its resources once the last clone is dropped.
+    ///
+    /// The Qt application is shut down once its `TrayApp` and every tray spawned from it have
+    /// been dropped.
+    fn drop(&mut self) {
+        // `TrayApp::poll_event` only holds references to the tray while the registry is locked,
+        // and can't obtain any once the tray is unregistered.
+        {
+            let mut registry = self.app.trays.lock().unwrap();
+            if Arc::strong_count(&self.handle) > 1 {
+                return;
+            }
+            registry.trays.retain(|tray| tray.id != self.id);
+        }
+        let handle = self.handle.lock().unwrap();
+        if !handle.as_ptr().is_null() {
+            unsafe {
+                bind::remove_app_tray(self.app.handle.lock().unwrap().as_ptr(), handle.as_ptr());
                 bind::cleanup_qt_app(handle.as_ptr());
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "stub", feature = "testing"))]
+mod tests {
+    use super::*;
+
+    /// Returns the entries of the menu at `parent` as the stub keeps them.
+    fn menu_items(tray: &SystemTray, parent: &str) -> Vec<bind::StubMenuItem> {
+        unsafe { bind::stub_menu_items(tray.handle.lock().unwrap().as_ptr(), parent) }
+    }
+
+    /// Returns the ids of the entries of the menu at `parent` in order, with `-` for
+    /// separators.
+    fn menu_ids(tray: &SystemTray, parent: &str) -> Vec<String> {
+        menu_items(tray, parent)
+            .into_iter()
+            .map(|item| {
+                if item.separator {
+                    "-".to_string()
+                } else {
+                    item.id
+                }
+            })
+            .collect()
+    }
+
+    /// A File menu with a separator and a Recent submenu in it.
+    fn two_level_tray() -> SystemTray {
+        SystemTray::default()
+            .menu(
+                Menu::new("File", "file".into())
+                    .item(("Open", "open"))
+                    .separator()
+                    .item(
+                        Menu::new("Recent", "recent".into())
+                            .item(("doc1.txt", "doc1"))
+                            .separator()
+                            .item(("doc2.txt", "doc2")),
+                    )
+                    .item(("Close", "close")),
+            )
+            .menu(("Quit", "quit"))
+    }
+
+    /// Injects `count` notification clicks, numbered from 0, so that their order can be checked.
+    fn flood(tray: &SystemTray, count: u32) {
+        for id in 0..count {
+            tray.inject_event(&Event::NotificationClicked { id })
+                .unwrap();
+        }
+    }
+
+    /// Polls every queued event and returns the ids of the notification clicks among them.
+    fn drain(tray: &SystemTray) -> Vec<u32> {
+        let mut ids = Vec::new();
+        loop {
+            match tray.poll_event().unwrap() {
+                Event::None => return ids,
+                Event::NotificationClicked { id } => ids.push(id),
+                event => panic!("unexpected event {:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn unbounded_queue_keeps_every_event() {
+        let tray = SystemTray::default();
+        tray.set_event_queue_capacity(3, OverflowPolicy::Unbounded)
+            .unwrap();
+        flood(&tray, 100);
+        assert_eq!(drain(&tray), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest_events() {
+        let tray = SystemTray::default();
+        tray.set_event_queue_capacity(3, OverflowPolicy::DropOldest)
+            .unwrap();
+        flood(&tray, 100);
+        assert_eq!(drain(&tray), vec![97, 98, 99]);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_first_events() {
+        let tray = SystemTray::default();
+        tray.set_event_queue_capacity(3, OverflowPolicy::DropNewest)
+            .unwrap();
+        flood(&tray, 100);
+        assert_eq!(drain(&tray), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn menu_clicks_without_an_id_are_skipped() {
+        let tray = SystemTray::default();
+        let click = bind::AppEvent {
+            type_: bind::AppEventType_MenuItemClicked,
+            menu_id_str: std::ptr::null(),
+            x: 0,
+            y: 0,
+            visible: false,
+            notification_id: 0,
+            args_str: std::ptr::null(),
+            button: bind::MouseButton_MouseButtonLeft,
+            dark: false,
+        };
+        unsafe { bind::inject_event(tray.lock_handle().unwrap().as_ptr(), &click) };
+        tray.inject_event(&Event::MenuItemClicked("quit".into()))
+            .unwrap();
+        unsafe { bind::inject_event(tray.lock_handle().unwrap().as_ptr(), &click) };
+        assert_eq!(
+            tray.poll_event().unwrap(),
+            Event::MenuItemClicked("quit".into())
+        );
+        assert_eq!(tray.poll_event().unwrap(), Event::None);
+    }
+
+    #[test]
+    fn methods_fail_after_close() {
+        let tray = SystemTray::default().menu(("Quit", "quit"));
+        tray.close();
+        let closed = |result: Result<(), Error>| matches!(result, Err(Error::Closed));
+        assert!(closed(tray.set_preferred_icon_size(16)));
+        assert!(closed(tray.set_busy(true)));
+        assert!(closed(tray.popup_menu(0, 0)));
+        assert!(closed(tray.popup_menu_at_cursor()));
+        assert!(closed(tray.flush()));
+        assert!(closed(
+            tray.set_event_queue_capacity(4, OverflowPolicy::DropOldest)
+        ));
+        assert!(closed(tray.set_event_mask(EventMask::ALL)));
+        assert!(closed(tray.pause_events()));
+        assert!(closed(tray.resume_events()));
+        assert!(closed(tray.set_pause_policy(PausePolicy::Buffer)));
+        assert!(closed(
+            tray.set_event_sink(Arc::new(crate::testing::RecordingSink::new()))
+        ));
+        assert!(closed(tray.clear_event_sink()));
+        assert!(closed(tray.stop_with(StopScope::ThisTray)));
+        assert!(closed(tray.stop_with(StopScope::Application)));
+        #[cfg(feature = "image")]
+        assert!(closed(
+            tray.set_icon_image(&image::DynamicImage::new_rgba8(16, 16))
+        ));
+        assert!(matches!(tray.last_backend_error(), Err(Error::Closed)));
+        assert!(matches!(tray.active_menu_item(), Err(Error::Closed)));
+        assert!(matches!(tray.is_menu_open(), Err(Error::Closed)));
+        assert!(matches!(tray.screens(), Err(Error::Closed)));
+        assert!(matches!(
+            unsafe { tray.tray_icon_ptr() },
+            Err(Error::Closed)
+        ));
+        assert!(!tray.has_menu_item("quit"));
+        assert!(!tray.is_available());
+        let builder = |f: fn(SystemTray) -> SystemTray| {
+            let tray = tray.clone();
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || f(tray))).is_err()
+        };
+        assert!(builder(|tray| tray.menu(("Open", "open"))));
+        assert!(builder(|tray| tray.icon(b"", "png")));
+    }
+
+    #[test]
+    fn zero_capacity_drops_every_event() {
+        for policy in [OverflowPolicy::DropOldest, OverflowPolicy::DropNewest] {
+            let tray = SystemTray::default();
+            tray.set_event_queue_capacity(0, policy).unwrap();
+            flood(&tray, 10);
+            assert_eq!(drain(&tray), Vec::<u32>::new(), "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn lowering_the_capacity_trims_queued_events() {
+        for (policy, kept) in [
+            (OverflowPolicy::DropOldest, vec![6, 7, 8, 9]),
+            (OverflowPolicy::DropNewest, vec![0, 1, 2, 3]),
+        ] {
+            let tray = SystemTray::default();
+            flood(&tray, 10);
+            tray.set_event_queue_capacity(4, policy).unwrap();
+            assert_eq!(drain(&tray), kept, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn setters_and_polling_run_concurrently() {
+        const ROUNDS: u32 = 500;
+        let tray = SystemTray::default().menu(("Status", "status"));
+        let mut threads = Vec::new();
+        for thread in 0..2 {
+            let tray = tray.clone();
+            threads.push(std::thread::spawn(move || {
+                for round in 0..ROUNDS {
+                    let text = format!("{}: {}", thread, round);
+                    tray.set_accessible_name(&text).unwrap();
+                    tray.set_menu_text("status", &text).unwrap();
+                    tray.set_tray_icon(include_bytes!("../icon.svg"), "svg")
+                        .unwrap();
+                }
+            }));
+        }
+        let builder = tray.clone();
+        threads.push(std::thread::spawn(move || {
+            for round in 0..ROUNDS {
+                let id = format!("doc{}", round);
+                builder
+                    .clone()
+                    .menu(Menu::new("Doc", id.into()).with_data(vec![1]));
+            }
+        }));
+        let injector = tray.clone();
+        threads.push(std::thread::spawn(move || flood(&injector, ROUNDS)));
+        let poller = tray.clone();
+        threads.push(std::thread::spawn(move || {
+            let mut received = 0;
+            while received < ROUNDS {
+                match poller.poll_event().unwrap() {
+                    Event::None => std::thread::yield_now(),
+                    _ => received += 1,
+                }
+            }
+        }));
+
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            done.send(()).unwrap();
+        });
+        finished
+            .recv_timeout(Duration::from_secs(30))
+            .expect("the threads deadlocked or panicked");
+    }
+
+    #[test]
+    fn tricky_menu_ids_come_back_exactly() {
+        let tray = SystemTray::default().menu(
+            Menu::new("Files", "ファイル".into())
+                .item(Menu::new("Doc", "最近/é".into()).with_data(vec![7]))
+                .item(Menu::new("Empty", "".into()).with_data(vec![8])),
+        );
+        for (id, data) in [("ファイル/最近/é", 7), ("ファイル/", 8)] {
+            tray.inject_event(&Event::MenuItemClicked(id.into()))
+                .unwrap();
+            assert_eq!(
+                tray.poll_event().unwrap(),
+                Event::MenuItemClickedWithData {
+                    id: id.into(),
+                    data: vec![data],
+                }
+            );
+        }
+        tray.remove_menu_item("ファイル").unwrap();
+        tray.inject_event(&Event::MenuItemClicked("ファイル/最近/é".into()))
+            .unwrap();
+        assert_eq!(
+            tray.poll_event().unwrap(),
+            Event::MenuItemClicked("ファイル/最近/é".into())
+        );
+    }
+
+    #[test]
+    fn menu_text_escapes_ampersands() {
+        assert_eq!(menu_text("Save & Quit", false), "Save && Quit");
+        assert_eq!(menu_text("&Open", false), "&&Open");
+        assert_eq!(menu_text("Tom && Jerry", false), "Tom &&&& Jerry");
+        assert_eq!(menu_text("Open", false), "Open");
+        assert_eq!(menu_text("開く&", false), "開く&&");
+    }
+
+    #[test]
+    fn menu_text_keeps_mnemonics() {
+        assert_eq!(menu_text("&Open", true), "&Open");
+        assert_eq!(menu_text("Tom && Jerry", true), "Tom && Jerry");
+        assert_eq!(menu_text("開く(&O)", true), "開く(&O)");
+    }
+
+    #[test]
+    fn submenus_keep_their_separators() {
+        let tray = two_level_tray();
+        assert_eq!(menu_ids(&tray, ""), ["file", "quit"]);
+        assert_eq!(menu_ids(&tray, "file"), ["open", "-", "recent", "close"]);
+        assert_eq!(menu_ids(&tray, "file/recent"), ["doc1", "-", "doc2"]);
+    }
+
+    #[test]
+    fn nested_items_are_found_by_path() {
+        let tray = two_level_tray();
+        for id in [
+            "file",
+            "file/open",
+            "file/recent",
+            "file/recent/doc2",
+            "quit",
+        ] {
+            assert!(tray.has_menu_item(id), "{}", id);
+        }
+        for id in ["open", "recent/doc1", "file/doc1", "file/", "file/recent/"] {
+            assert!(!tray.has_menu_item(id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn nested_items_can_be_disabled_hidden_and_renamed() {
+        let tray = two_level_tray();
+        tray.set_enabled("file/recent/doc1", false).unwrap();
+        tray.set_visible("file/recent", false).unwrap();
+        tray.set_menu_text("file/open", "Open & Edit").unwrap();
+
+        let recent = &menu_items(&tray, "file/recent");
+        assert!(!recent[0].enabled);
+        assert!(recent[2].enabled);
+        let file = &menu_items(&tray, "file");
+        assert_eq!(file[0].text, "Open && Edit");
+        assert!(!file[2].visible);
+        assert!(file[3].visible);
+
+        for result in [
+            tray.set_enabled("recent/doc1", false),
+            tray.set_visible("file/recent/doc3", false),
+            tray.set_menu_text("doc2", "notes.txt"),
+        ] {
+            assert!(
+                matches!(result, Err(Error::UnknownMenuItem(_))),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn removing_a_submenu_removes_its_contents() {
+        let tray = two_level_tray();
+        tray.remove_menu_item("file/recent/doc1").unwrap();
+        assert_eq!(menu_ids(&tray, "file/recent"), ["-", "doc2"]);
+
+        tray.remove_menu_item("file/recent").unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["open", "-", "close"]);
+        assert!(menu_items(&tray, "file/recent").is_empty());
+        assert!(!tray.has_menu_item("file/recent/doc2"));
+        assert!(matches!(
+            tray.remove_menu_item("file/recent"),
+            Err(Error::UnknownMenuItem(_))
+        ));
+    }
+
+    /// Returns the ids of the menu at `parent` in the order keyboard navigation would visit
+    /// them, going by the stub's bookkeeping: the order they are kept in, skipping separators
+    /// and disabled or hidden items. Qt's own `QMenu::actions()` order isn't checked.
+    fn navigation_order(tray: &SystemTray, parent: &str) -> Vec<String> {
+        menu_items(tray, parent)
+            .into_iter()
+            .filter(|item| !item.separator && item.enabled && item.visible)
+            .map(|item| item.id)
+            .collect()
+    }
+
+    #[test]
+    fn moving_items_reorders_the_stub_bookkeeping() {
+        let tray = two_level_tray();
+        tray.move_menu_item("quit", 0).unwrap();
+        assert_eq!(navigation_order(&tray, ""), ["quit", "file"]);
+
+        tray.move_menu_item("file/close", 0).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["close", "open", "-", "recent"]);
+        assert_eq!(navigation_order(&tray, "file"), ["close", "open", "recent"]);
+
+        tray.set_enabled("file/open", false).unwrap();
+        tray.move_menu_item("file/recent/doc2", 0).unwrap();
+        assert_eq!(navigation_order(&tray, "file"), ["close", "recent"]);
+        assert_eq!(navigation_order(&tray, "file/recent"), ["doc2", "doc1"]);
+    }
+
+    #[test]
+    fn separators_count_as_entries_when_moving() {
+        let tray = two_level_tray();
+        tray.move_menu_item("file/close", 1).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["open", "close", "-", "recent"]);
+        tray.move_menu_item("file/open", 2).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["close", "-", "open", "recent"]);
+    }
+
+    #[test]
+    fn submenus_move_with_their_contents() {
+        let tray = two_level_tray();
+        tray.move_menu_item("file/recent", 0).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["recent", "open", "-", "close"]);
+        assert_eq!(menu_ids(&tray, "file/recent"), ["doc1", "-", "doc2"]);
+
+        tray.move_menu_item("file", 1).unwrap();
+        assert_eq!(menu_ids(&tray, ""), ["quit", "file"]);
+        assert_eq!(menu_ids(&tray, "file"), ["recent", "open", "-", "close"]);
+        assert_eq!(menu_ids(&tray, "file/recent"), ["doc1", "-", "doc2"]);
+    }
+
+    #[test]
+    fn moving_past_the_end_moves_to_the_end() {
+        let tray = two_level_tray();
+        tray.move_menu_item("file/open", 4).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["-", "recent", "close", "open"]);
+        tray.move_menu_item("file/recent", usize::MAX).unwrap();
+        assert_eq!(menu_ids(&tray, "file"), ["-", "close", "open", "recent"]);
+        assert_eq!(menu_ids(&tray, "file/recent"), ["doc1", "-", "doc2"]);
+        tray.move_menu_item("file", 100).unwrap();
+        assert_eq!(menu_ids(&tray, ""), ["quit", "file"]);
+    }
+
+    #[test]
+    fn moving_an_unknown_item_fails() {
+        let tray = two_level_tray();
+        for id in ["recent", "file/recent/doc3", ""] {
+            assert!(matches!(
+                tray.move_menu_item(id, 0),
+                Err(Error::UnknownMenuItem(_))
+            ));
+        }
+        assert_eq!(menu_ids(&tray, "file"), ["open", "-", "recent", "close"]);
+    }
+}
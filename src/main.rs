@@ -4,7 +4,7 @@ fn main() {
     handle.join().unwrap();
 }
 fn process() {
-    use std::{thread, time::Duration};
+    use std::time::Duration;
     use system_tray::{Event, Menu, SystemTray};
 
     // Create a system tray instance
@@ -12,23 +12,58 @@ fn process() {
 
     // Add menu items
     tray = tray
-        .menu(Menu::new("Open".to_string(), "open".to_string()))
-        .menu(Menu::new("Exit".to_string(), "exit".to_string()));
+        .menu(("Open", "open"))
+        .menu(Menu::new("Exit", "exit".into()));
 
     // Set a placeholder icon (minimal PNG for testing)
     static ICON: &[u8] = include_bytes!("../icon.svg");
     tray = tray.icon(ICON, "SVG");
 
+    // Initialize Qt up front so a missing display is reported here
+    if let Err(e) = tray.init() {
+        eprintln!("{}", e);
+        return;
+    }
+
     // Start the system tray event loop
     tray.start();
 
     // Poll for events in the main thread
     loop {
-        match tray.poll_event() {
+        match tray.poll_event_blocking_up_to(Duration::from_secs(1)) {
             Ok(event) => match event {
                 Event::None => {}
-                Event::TrayClicked => println!("Tray icon clicked"),
-                Event::TrayDoubleClicked => println!("Tray icon double-clicked"),
+                Event::TrayClicked { x, y } => println!("Tray icon clicked at ({}, {})", x, y),
+                Event::TrayDoubleClicked { x, y } => {
+                    println!("Tray icon double-clicked at ({}, {})", x, y)
+                }
+                Event::TrayButtonClicked { button, x, y } => {
+                    println!(
+                        "Tray icon clicked with {:?} button at ({}, {})",
+                        button, x, y
+                    )
+                }
+                Event::MenuItemClickedWithData { id, data } => {
+                    println!("Menu item clicked: {} ({} bytes of data)", id, data.len());
+                }
+                Event::TrayReconnected => println!("Tray host reconnected"),
+                Event::VisibilityChanged { visible } => println!("Tray icon visible: {}", visible),
+                Event::NotificationClicked { id } => println!("Notification {} clicked", id),
+                Event::SecondInstanceLaunched { args } => {
+                    println!("Launched again with {:?}", args)
+                }
+                Event::MenuAboutToShow => println!("Menu opened"),
+                Event::MenuItemHovered(id) => println!("Menu item hovered: {}", id),
+                Event::MenuClosed => println!("Menu closed"),
+                Event::NotificationAction {
+                    notification_id,
+                    action_id,
+                } => println!("Notification {} action: {}", notification_id, action_id),
+                Event::ColorSchemeChanged { dark } => println!("Dark color scheme: {}", dark),
+                Event::Quit => {
+                    println!("Tray application is quitting");
+                    break;
+                }
                 Event::MenuItemClicked(id) => {
                     println!("Menu item clicked: {}", id);
                     if id == "exit" {
@@ -45,7 +80,5 @@ fn process() {
                 break;
             }
         }
-        // Prevent busy-waiting
-        thread::sleep(Duration::from_millis(100));
     }
 }
@@ -0,0 +1,178 @@
+//! The `MenuId` newtype, which keeps menu item ids apart from their display text.
+
+use std::{borrow::Borrow, fmt, ops::Deref};
+
+/// The id of a menu item, as passed to `Menu::new` and reported by `Event::MenuItemClicked`.
+///
+/// Both the text and the id of a menu item are strings, so a separate type makes swapping them
+/// a compile error. Ids of items in submenus are paths such as `"file/recent/doc1"`.
+///
+/// A `MenuId` dereferences to `str`, so it can be compared with string literals and passed to
+/// the methods taking an id as `&str`, such as `SystemTray::set_enabled`.
+///
+/// # Examples
+///
+/// ```
+/// use system_tray::{Event, Menu, MenuId};
+///
+/// let quit = Menu::new("Quit", MenuId::from("quit"));
+/// let open = Menu::new("Open", "open".into());
+///
+/// let event = Event::MenuItemClicked("quit".into());
+/// if let Event::MenuItemClicked(id) = event {
+///     assert_eq!(id, "quit");
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MenuId(String);
+
+impl MenuId {
+    /// Creates a `MenuId` from any string.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Returns the id as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the id as an owned `String`.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+/// Returns the path of the menu item `id` in the submenu at `parent` (a path), or at the top
+/// level.
+///
+/// The id is joined as it is: one containing `/` reads like a deeper path, and an empty one
+/// leaves an empty last segment, as the backend's paths do.
+pub(crate) fn join_path(parent: Option<&str>, id: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}/{}", parent, id),
+        None => id.to_string(),
+    }
+}
+
+/// Returns whether `path` is the menu item at `ancestor` or anything in it, if it's a submenu.
+pub(crate) fn is_at_or_below(path: &str, ancestor: &str) -> bool {
+    path.strip_prefix(ancestor)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+impl From<&str> for MenuId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<String> for MenuId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<MenuId> for String {
+    fn from(id: MenuId) -> Self {
+        id.0
+    }
+}
+
+impl Deref for MenuId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for MenuId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for MenuId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for MenuId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<str> for MenuId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for MenuId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for MenuId {
+    fn eq(&self, other: &String) -> bool {
+        &self.0 == other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn paths_keep_ids_as_they_are() {
+        assert_eq!(join_path(None, "open"), "open");
+        assert_eq!(join_path(Some("file/recent"), "doc1"), "file/recent/doc1");
+        assert_eq!(join_path(Some("file"), "a/b"), "file/a/b");
+        assert_eq!(join_path(Some("file"), ""), "file/");
+        assert_eq!(join_path(Some(""), "open"), "/open");
+        assert_eq!(
+            join_path(Some("ファイル"), "最近使ったもの"),
+            "ファイル/最近使ったもの"
+        );
+        assert_eq!(join_path(Some("🗂"), "é"), "🗂/é");
+    }
+
+    #[test]
+    fn only_whole_segments_are_below_a_path() {
+        assert!(is_at_or_below("file", "file"));
+        assert!(is_at_or_below("file/open", "file"));
+        assert!(is_at_or_below("file//open", "file"));
+        assert!(is_at_or_below("file/", "file"));
+        assert!(!is_at_or_below("files/open", "file"));
+        assert!(!is_at_or_below("fil", "file"));
+        assert!(is_at_or_below("file//open", "file/"));
+        assert!(!is_at_or_below("file/open", "file/"));
+        assert!(is_at_or_below("ファイル/最近/doc", "ファイル/最近"));
+        assert!(!is_at_or_below("ファイル/最近使った", "ファイル/最近"));
+    }
+
+    #[test]
+    fn ids_round_trip_exactly() {
+        for id in ["a/b", "", "//", "ファイル/最近", "é", "e\u{301}", "🗂 docs"] {
+            let menu_id = MenuId::from(id);
+            assert_eq!(menu_id, id);
+            assert_eq!(menu_id.as_str().as_bytes(), id.as_bytes());
+            assert_eq!(menu_id.to_string(), id);
+            assert_eq!(MenuId::new(id.to_string()).into_string(), id);
+        }
+    }
+
+    #[test]
+    fn ids_look_up_by_str() {
+        let ids: HashMap<MenuId, u8> =
+            [(MenuId::from("é"), 1), (MenuId::from("e\u{301}"), 2)].into();
+        assert_eq!(ids.get("é"), Some(&1));
+        assert_eq!(ids.get("e\u{301}"), Some(&2));
+        assert_eq!(ids.get("e"), None);
+    }
+}
@@ -0,0 +1,93 @@
+//! Waiting for the outcome of a notification, for `SystemTray::notify_async`.
+
+use crate::bind;
+use std::{
+    ffi::c_void,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// How a notification shown with `SystemTray::notify_async` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationOutcome {
+    /// The user clicked the notification.
+    Clicked,
+    /// The notification's timeout elapsed without a click.
+    TimedOut,
+    /// The notification was replaced by another one, or the tray icon went away.
+    Dismissed,
+}
+
+impl NotificationOutcome {
+    fn from_raw(result: bind::NotificationResult) -> Self {
+        match result {
+            bind::NotificationResult_NotificationResultClicked => NotificationOutcome::Clicked,
+            bind::NotificationResult_NotificationResultTimedOut => NotificationOutcome::TimedOut,
+            _ => NotificationOutcome::Dismissed,
+        }
+    }
+}
+
+/// The state shared between the Qt callback and the waiting future.
+#[derive(Default)]
+struct Slot {
+    outcome: Option<NotificationOutcome>,
+    waker: Option<Waker>,
+}
+
+/// Resolves once the backend reports the outcome of a notification.
+pub(crate) struct OutcomeFuture(Arc<Mutex<Slot>>);
+
+impl OutcomeFuture {
+    /// Creates the future along with the `user_data` pointer to pass to `show_notification`.
+    ///
+    /// The pointer must be handed to `outcome_callback` exactly once, or to `release` if the
+    /// backend didn't take it.
+    pub(crate) fn new() -> (Self, *mut c_void) {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let user_data = Box::into_raw(Box::new(slot.clone())) as *mut c_void;
+        (OutcomeFuture(slot), user_data)
+    }
+
+    /// Frees a `user_data` pointer from `new` that the backend never took.
+    ///
+    /// # Safety
+    ///
+    /// `user_data` must come from `new` and must not have been passed to the callback.
+    pub(crate) unsafe fn release(user_data: *mut c_void) {
+        drop(Box::from_raw(user_data as *mut Arc<Mutex<Slot>>));
+    }
+}
+
+impl Future for OutcomeFuture {
+    type Output = NotificationOutcome;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        match slot.outcome {
+            Some(outcome) => Poll::Ready(outcome),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The `NotificationCallback` passed to `show_notification`. Called once on the Qt thread.
+pub(crate) unsafe extern "C" fn outcome_callback(
+    user_data: *mut c_void,
+    result: bind::NotificationResult,
+) {
+    let slot = Box::from_raw(user_data as *mut Arc<Mutex<Slot>>);
+    let waker = {
+        let mut slot = slot.lock().unwrap();
+        slot.outcome = Some(NotificationOutcome::from_raw(result));
+        slot.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
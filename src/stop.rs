@@ -0,0 +1,64 @@
+//! Waiting for the Qt thread to finish without blocking, for `TrayApp::stop_async`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread::JoinHandle,
+};
+
+/// The state shared between the joining thread and the waiting future.
+#[derive(Default)]
+struct Slot {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// Resolves once the Qt thread has finished.
+pub(crate) struct StopFuture(Arc<Mutex<Slot>>);
+
+impl StopFuture {
+    /// Joins `thread` on a helper thread, so that no executor thread blocks on it.
+    ///
+    /// `keep_alive` is dropped on the helper thread once `thread` has finished, whether or not
+    /// the future is still awaited. Holding the tray there defers its cleanup, which must not
+    /// race with the Qt thread shutting down.
+    pub(crate) fn join(thread: Option<JoinHandle<()>>, keep_alive: impl Send + 'static) -> Self {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let Some(thread) = thread else {
+            slot.lock().unwrap().done = true;
+            return StopFuture(slot);
+        };
+        let shared = Arc::clone(&slot);
+        std::thread::spawn(move || {
+            thread.join().unwrap_or_else(|e| {
+                eprintln!("Failed to join Qt thread: {:?}", e);
+            });
+            let waker = {
+                let mut slot = shared.lock().unwrap();
+                slot.done = true;
+                slot.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            drop(keep_alive);
+        });
+        StopFuture(slot)
+    }
+}
+
+impl Future for StopFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap();
+        if slot.done {
+            Poll::Ready(())
+        } else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
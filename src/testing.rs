@@ -0,0 +1,158 @@
+//! Helpers for testing code that handles tray events, without a display.
+
+use crate::{bind, Error, Event, EventSink, SystemTray, ARGS_SEPARATOR};
+use std::{ffi::CString, ptr, sync::Mutex};
+
+/// An `EventSink` that records every event it receives, for asserting on in tests.
+///
+/// It can be installed with `SystemTray::set_event_sink` to capture the events a real tray
+/// delivers, or handed directly to code that takes an `EventSink`, feeding it events by calling
+/// `handle`.
+///
+/// Requires the `testing` feature.
+///
+/// # Examples
+///
+/// ```
+/// use system_tray::{Event, EventSink, RecordingSink};
+///
+/// fn on_quit_menu_item(sink: &dyn EventSink) {
+///     sink.handle(Event::MenuItemClicked("quit".into()));
+/// }
+///
+/// let sink = RecordingSink::new();
+/// on_quit_menu_item(&sink);
+/// assert_eq!(sink.events(), vec![Event::MenuItemClicked("quit".into())]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RecordingSink {
+    events: Mutex<Vec<Event>>,
+}
+
+impl RecordingSink {
+    /// Creates a sink that hasn't recorded any events yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the events recorded so far, oldest first.
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Forgets the events recorded so far.
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn handle(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl SystemTray {
+    /// Queues `event` as if the backend had emitted it, for testing and benchmarking event
+    /// handling without a display.
+    ///
+    /// The event goes through the same path as real ones: it is delivered to the event sink if
+    /// one is set (on the calling thread rather than the Qt thread), is subject to the queue
+    /// capacity and to `pause_events`, and is decoded again by `poll_event`. A
+    /// `MenuItemClickedWithData` is injected as a click on its id, so it comes back with the
+    /// payload of that menu item, if any. `Event::None` is ignored. The `stub` backend queues
+    /// injected events the same way, so this also works without Qt.
+    ///
+    /// Requires the `testing` feature.
+    ///
+    /// # Errors
+    ///
+    /// `Error::Ffi` if a string of the event contains null bytes, or `Error::Closed` after
+    /// `close`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use system_tray::{Event, SystemTray};
+    ///
+    /// let tray = SystemTray::new("MyOrg", "MyApp");
+    /// tray.inject_event(&Event::MenuItemClicked("quit".into()))?;
+    /// assert_eq!(tray.poll_event()?, Event::MenuItemClicked("quit".into()));
+    /// # Ok::<(), system_tray::Error>(())
+    /// ```
+    pub fn inject_event(&self, event: &Event) -> Result<(), Error> {
+        let mut raw = bind::AppEvent {
+            type_: bind::AppEventType_None,
+            menu_id_str: ptr::null(),
+            x: 0,
+            y: 0,
+            visible: false,
+            notification_id: 0,
+            args_str: ptr::null(),
+            button: bind::MouseButton_MouseButtonLeft,
+            dark: false,
+        };
+        let mut text = None;
+        let mut args = None;
+        match event {
+            Event::None => return Ok(()),
+            Event::TrayClicked { x, y } => {
+                (raw.type_, raw.x, raw.y) = (bind::AppEventType_TrayClicked, *x, *y);
+            }
+            Event::TrayDoubleClicked { x, y } => {
+                (raw.type_, raw.x, raw.y) = (bind::AppEventType_TrayDoubleClicked, *x, *y);
+            }
+            Event::TrayButtonClicked { button, x, y } => {
+                (raw.type_, raw.x, raw.y) = (bind::AppEventType_TrayButtonClicked, *x, *y);
+                raw.button = button.to_raw();
+            }
+            Event::MenuItemClicked(id) | Event::MenuItemClickedWithData { id, .. } => {
+                raw.type_ = bind::AppEventType_MenuItemClicked;
+                text = Some(CString::new(id.as_str())?);
+            }
+            Event::Quit => raw.type_ = bind::AppEventType_Quit,
+            Event::TrayReconnected => raw.type_ = bind::AppEventType_TrayReconnected,
+            Event::VisibilityChanged { visible } => {
+                raw.type_ = bind::AppEventType_VisibilityChanged;
+                raw.visible = *visible;
+            }
+            Event::NotificationClicked { id } => {
+                raw.type_ = bind::AppEventType_NotificationClicked;
+                raw.notification_id = *id;
+            }
+            Event::SecondInstanceLaunched {
+                args: instance_args,
+            } => {
+                raw.type_ = bind::AppEventType_SecondInstanceLaunched;
+                args = Some(CString::new(
+                    instance_args.join(&ARGS_SEPARATOR.to_string()),
+                )?);
+            }
+            Event::MenuAboutToShow => raw.type_ = bind::AppEventType_MenuAboutToShow,
+            Event::MenuItemHovered(id) => {
+                raw.type_ = bind::AppEventType_MenuItemHovered;
+                text = Some(CString::new(id.as_str())?);
+            }
+            Event::MenuClosed => raw.type_ = bind::AppEventType_MenuClosed,
+            Event::NotificationAction {
+                notification_id,
+                action_id,
+            } => {
+                raw.type_ = bind::AppEventType_NotificationActionInvoked;
+                raw.notification_id = *notification_id;
+                text = Some(CString::new(action_id.as_str())?);
+            }
+            Event::ColorSchemeChanged { dark } => {
+                raw.type_ = bind::AppEventType_ColorSchemeChanged;
+                raw.dark = *dark;
+            }
+        }
+        raw.menu_id_str = text.as_ref().map_or(ptr::null(), |text| text.as_ptr());
+        raw.args_str = args.as_ref().map_or(ptr::null(), |args| args.as_ptr());
+        let handle = self.lock_handle()?;
+        unsafe {
+            bind::inject_event(handle.as_ptr(), &raw);
+        }
+        Ok(())
+    }
+}